@@ -0,0 +1,110 @@
+use encoding_rs::UTF_8;
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use crate::{
+    lines::{mapped_file_key, MappedFileState},
+    resolve_input_path, strip_bom_chars,
+    transform::describe_line_endings,
+    EncodingSource, OpenFileResponse,
+};
+
+/// Path reported for pasted content that was not persisted to disk.
+const CLIPBOARD_PATH: &str = "<clipboard>";
+
+static NEXT_CLIPBOARD_FILE: AtomicUsize = AtomicUsize::new(1);
+
+/// Temp files backing persisted clipboard content, keyed by normalized path. They are removed
+/// when closed and any left over are removed when the app exits.
+#[derive(Default)]
+pub(crate) struct ClipboardState {
+    files: Mutex<HashMap<String, PathBuf>>,
+}
+
+impl ClipboardState {
+    pub fn cleanup(&self) {
+        let mut files = self.files.lock().expect("clipboard state poisoned");
+        for (_, path) in files.drain() {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Treats pasted text like a loaded file. With `persist`, the text is also written as UTF-8 to
+/// a temp file whose path is returned, so range reads and search work on it.
+#[tauri::command]
+pub(crate) fn load_ntr_from_string(
+    state: tauri::State<ClipboardState>,
+    contents: String,
+    persist: Option<bool>,
+    strip_bom_chars: Option<bool>,
+) -> Result<OpenFileResponse, String> {
+    let ends_with_newline = contents.ends_with('\n');
+    let mut contents = contents;
+    let stripped_bom_count = if strip_bom_chars.unwrap_or(false) {
+        self::strip_bom_chars(&mut contents)
+    } else {
+        0
+    };
+
+    let path = if persist.unwrap_or(false) {
+        let temp_path = std::env::temp_dir().join(format!(
+            "ntr-clipboard-{}-{}.ntr",
+            std::process::id(),
+            NEXT_CLIPBOARD_FILE.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::write(&temp_path, &contents)
+            .map_err(|err| format!("Failed to write clipboard file: {err}"))?;
+        let normalized = mapped_file_key(&temp_path);
+        let mut files = state.files.lock().expect("clipboard state poisoned");
+        files.insert(normalized.clone(), temp_path);
+        normalized
+    } else {
+        CLIPBOARD_PATH.to_string()
+    };
+
+    let line_ending = describe_line_endings(&contents);
+    let ascii_only = contents.is_ascii();
+    Ok(OpenFileResponse {
+        path,
+        contents,
+        stripped_bom_count,
+        tabs_expanded: false,
+        ends_with_newline,
+        encoding: UTF_8.name().to_string(),
+        encoding_source: EncodingSource::Detected,
+        had_bom: false,
+        line_ending,
+        ascii_only,
+    })
+}
+
+/// Deletes a persisted clipboard file and drops its memory map. Returns false for paths that
+/// are not clipboard files.
+#[tauri::command]
+pub(crate) fn close_clipboard_file(
+    state: tauri::State<ClipboardState>,
+    mapped: tauri::State<MappedFileState>,
+    path: String,
+) -> Result<bool, String> {
+    let removed = {
+        let mut files = state.files.lock().expect("clipboard state poisoned");
+        files.remove(&mapped_file_key(&resolve_input_path(&path)))
+    };
+    let Some(temp_path) = removed else {
+        return Ok(false);
+    };
+    {
+        let mut guard = mapped.inner.lock().expect("mapped file state poisoned");
+        guard.remove(&mapped_file_key(&temp_path));
+    }
+    std::fs::remove_file(&temp_path)
+        .map_err(|err| format!("Failed to remove clipboard file: {err}"))?;
+    Ok(true)
+}
@@ -0,0 +1,140 @@
+use serde::Serialize;
+
+/// Above this many LCS cells the middle section is reported as one replaced block instead of
+/// being diffed line by line, keeping memory bounded for wholesale rewrites.
+const MAX_LCS_CELLS: usize = 4_000_000;
+
+/// A contiguous change: `old_count` lines starting at `old_start` were replaced by `new_count`
+/// lines starting at `new_start`. Starts are 1-based line numbers.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct DiffHunk {
+    pub old_start: usize,
+    pub old_count: usize,
+    pub new_start: usize,
+    pub new_count: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct LineRange {
+    pub start: usize,
+    pub count: usize,
+}
+
+impl DiffHunk {
+    pub fn removed(&self) -> Option<LineRange> {
+        (self.old_count > 0).then_some(LineRange {
+            start: self.old_start,
+            count: self.old_count,
+        })
+    }
+
+    pub fn added(&self) -> Option<LineRange> {
+        (self.new_count > 0).then_some(LineRange {
+            start: self.new_start,
+            count: self.new_count,
+        })
+    }
+}
+
+pub(crate) fn diff_sequences<T: PartialEq>(old: &[T], new: &[T]) -> Vec<DiffHunk> {
+    let prefix = old
+        .iter()
+        .zip(new.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+    if old_middle.is_empty() && new_middle.is_empty() {
+        return Vec::new();
+    }
+
+    let cells = (old_middle.len() + 1).saturating_mul(new_middle.len() + 1);
+    if old_middle.is_empty() || new_middle.is_empty() || cells > MAX_LCS_CELLS {
+        return vec![DiffHunk {
+            old_start: prefix + 1,
+            old_count: old_middle.len(),
+            new_start: prefix + 1,
+            new_count: new_middle.len(),
+        }];
+    }
+
+    lcs_hunks(old_middle, new_middle, prefix)
+}
+
+fn lcs_hunks<T: PartialEq>(old: &[T], new: &[T], offset: usize) -> Vec<DiffHunk> {
+    let width = new.len() + 1;
+    let mut table = vec![0u32; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            table[i * width + j] = if old[i] == new[j] {
+                table[(i + 1) * width + j + 1] + 1
+            } else {
+                table[(i + 1) * width + j].max(table[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut hunks = Vec::new();
+    let mut current: Option<DiffHunk> = None;
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            hunks.extend(current.take());
+            i += 1;
+            j += 1;
+            continue;
+        }
+        let hunk = current.get_or_insert(DiffHunk {
+            old_start: offset + i + 1,
+            old_count: 0,
+            new_start: offset + j + 1,
+            new_count: 0,
+        });
+        let take_old = j == new.len()
+            || (i < old.len() && table[(i + 1) * width + j] >= table[i * width + j + 1]);
+        if take_old {
+            hunk.old_count += 1;
+            i += 1;
+        } else {
+            hunk.new_count += 1;
+            j += 1;
+        }
+    }
+    hunks.extend(current);
+    hunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_replaced_and_inserted_lines() {
+        let old = ["RO 1", "RO 2", "BOG 3", "TEE 4"];
+        let new = ["RO 1", "RO 2b", "BOG 3", "RED 5", "TEE 4"];
+        assert_eq!(
+            diff_sequences(&old, &new),
+            vec![
+                DiffHunk {
+                    old_start: 2,
+                    old_count: 1,
+                    new_start: 2,
+                    new_count: 1
+                },
+                DiffHunk {
+                    old_start: 4,
+                    old_count: 0,
+                    new_start: 4,
+                    new_count: 1
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,750 @@
+use encoding_rs::{
+    Encoding, BIG5, EUC_KR, GB18030, ISO_8859_15, ISO_8859_2, KOI8_R, SHIFT_JIS, UTF_16BE,
+    UTF_16LE, UTF_8, WINDOWS_1250, WINDOWS_1251, WINDOWS_1252,
+};
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use crate::{
+    bom_len_for, configured_fallbacks, detection_candidates, diff::LineRange, encode_contents,
+    ensure_byte_lines, parse_encoding_label, read_options_for, resolve_input_path, scan_decode,
+    scan_decode_reader, settings::SettingsState, write_atomic, DecodeScan, ReadOptions,
+    DEFAULT_FALLBACK_ENCODINGS, MAX_REPORTED_DECODE_ERRORS, SUPPORTED_ENCODINGS,
+};
+
+#[tauri::command]
+pub(crate) fn list_supported_encodings() -> Vec<String> {
+    SUPPORTED_ENCODINGS
+        .iter()
+        .map(|encoding| encoding.name().to_string())
+        .collect()
+}
+
+/// Region and display name per supported encoding, in the order regions are listed.
+const ENCODING_DISPLAY_NAMES: &[(&Encoding, &str, &str)] = &[
+    (UTF_8, "Unicode", "Unicode (UTF-8)"),
+    (UTF_16LE, "Unicode", "Unicode (UTF-16 little-endian)"),
+    (UTF_16BE, "Unicode", "Unicode (UTF-16 big-endian)"),
+    (
+        WINDOWS_1252,
+        "Western European",
+        "Western European (Windows-1252)",
+    ),
+    (
+        ISO_8859_15,
+        "Western European",
+        "Western European (ISO-8859-15)",
+    ),
+    (
+        WINDOWS_1250,
+        "Central European",
+        "Central European (Windows-1250)",
+    ),
+    (
+        ISO_8859_2,
+        "Central European",
+        "Central European (ISO-8859-2)",
+    ),
+    (WINDOWS_1251, "Cyrillic", "Cyrillic (Windows-1251)"),
+    (KOI8_R, "Cyrillic", "Cyrillic (KOI8-R)"),
+    (SHIFT_JIS, "East Asian", "Japanese (Shift_JIS)"),
+    (GB18030, "East Asian", "Chinese Simplified (GB18030)"),
+    (BIG5, "East Asian", "Chinese Traditional (Big5)"),
+    (EUC_KR, "East Asian", "Korean (EUC-KR)"),
+];
+
+#[derive(Serialize)]
+struct FriendlyEncoding {
+    /// Label accepted by every command taking an encoding.
+    label: String,
+    display_name: &'static str,
+}
+
+#[derive(Serialize)]
+pub(crate) struct EncodingGroup {
+    region: &'static str,
+    encodings: Vec<FriendlyEncoding>,
+}
+
+/// `SUPPORTED_ENCODINGS` with display names, grouped by region for the encoding picker. An
+/// encoding without an entry in `ENCODING_DISPLAY_NAMES` is listed under "Other" by its name.
+#[tauri::command]
+pub(crate) fn list_encodings_friendly() -> Vec<EncodingGroup> {
+    let mut groups: Vec<EncodingGroup> = Vec::new();
+    for &encoding in SUPPORTED_ENCODINGS {
+        let (region, display_name) = ENCODING_DISPLAY_NAMES
+            .iter()
+            .find(|(known, _, _)| *known == encoding)
+            .map_or(("Other", encoding.name()), |&(_, region, name)| {
+                (region, name)
+            });
+        let entry = FriendlyEncoding {
+            label: encoding.name().to_string(),
+            display_name,
+        };
+        match groups.iter_mut().find(|group| group.region == region) {
+            Some(group) => group.encodings.push(entry),
+            None => groups.push(EncodingGroup {
+                region,
+                encodings: vec![entry],
+            }),
+        }
+    }
+    groups
+}
+
+#[tauri::command]
+pub(crate) fn get_fallback_encodings(settings: tauri::State<SettingsState>) -> Vec<String> {
+    let configured = configured_fallbacks(&settings);
+    let chain = if configured.is_empty() {
+        DEFAULT_FALLBACK_ENCODINGS
+    } else {
+        &configured
+    };
+    chain
+        .iter()
+        .map(|encoding| encoding.name().to_string())
+        .collect()
+}
+
+/// Replaces the fallback chain; an empty list restores `DEFAULT_FALLBACK_ENCODINGS`.
+#[tauri::command]
+pub(crate) fn set_fallback_encodings(
+    settings: tauri::State<SettingsState>,
+    labels: Vec<String>,
+) -> Result<Vec<String>, String> {
+    let names = labels
+        .iter()
+        .map(|label| {
+            let encoding = parse_encoding_label(label)?;
+            if encoding == UTF_8 {
+                return Err("UTF-8 is always tried first and cannot be a fallback".to_string());
+            }
+            Ok(encoding.name().to_string())
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    settings.update(|settings| settings.fallback_encodings = names.clone())?;
+    Ok(names)
+}
+
+/// The narrowest encoding that can hold `contents` without loss: "us-ascii" for pure ASCII,
+/// else the first fallback-chain encoding that can represent every character, else "utf-8".
+#[tauri::command]
+pub(crate) fn minimal_encoding_for(
+    settings: tauri::State<SettingsState>,
+    contents: String,
+) -> String {
+    if contents.is_ascii() {
+        return "us-ascii".into();
+    }
+    let options = ReadOptions {
+        fallbacks: configured_fallbacks(&settings),
+        ..ReadOptions::default()
+    };
+    options
+        .fallback_chain()
+        .iter()
+        .find(|&&encoding| encode_contents(&contents, encoding).is_ok())
+        .map_or(UTF_8, |&encoding| encoding)
+        .name()
+        .to_ascii_lowercase()
+}
+
+#[derive(Serialize)]
+pub(crate) struct DecodeReport {
+    ok: bool,
+    /// The encoding the file decodes with; on failure, the candidate with the fewest errors,
+    /// which `first_error_offset` refers to.
+    encoding: Option<String>,
+    first_error_offset: Option<u64>,
+    /// For files that decode as UTF-8, whether they are in fact pure US-ASCII; `None` for
+    /// every other outcome.
+    ascii_only: Option<bool>,
+}
+
+/// Runs the same detection and decoding as opening the file but reports only the outcome, so
+/// a whole folder can be validated without sending contents to the frontend. The file is read
+/// and decoded in small chunks, keeping memory flat for large files.
+#[tauri::command(async)]
+pub(crate) fn can_decode(
+    settings: tauri::State<SettingsState>,
+    path: String,
+) -> Result<DecodeReport, String> {
+    let resolved = resolve_input_path(&path);
+    if !resolved.is_file() {
+        return Err("Path does not point to a file".into());
+    }
+    decode_report(&resolved, &read_options_for(&settings, &resolved))
+}
+
+fn decode_report(path: &Path, options: &ReadOptions) -> Result<DecodeReport, String> {
+    let open = || File::open(path).map_err(|err| format!("Failed to open file: {err}"));
+    let read_error = |err: std::io::Error| format!("Failed to read file: {err}");
+    let mut leading = Vec::new();
+    // Enough for the longest BOM.
+    open()?
+        .take(3)
+        .read_to_end(&mut leading)
+        .map_err(read_error)?;
+
+    let candidates = match options
+        .encoding
+        .or(Encoding::for_bom(&leading).map(|(bom, _)| bom))
+    {
+        Some(encoding) => vec![encoding],
+        None => detection_candidates(&leading, options.fallback_chain()),
+    };
+    let mut best: Option<(&'static Encoding, DecodeScan, usize)> = None;
+    for encoding in candidates {
+        let bom_len = bom_len_for(encoding, &leading);
+        let mut file = open()?;
+        file.seek(SeekFrom::Start(bom_len as u64))
+            .map_err(read_error)?;
+        let scan = scan_decode_reader(encoding, BufReader::new(file)).map_err(read_error)?;
+        if scan.error_count == 0 {
+            return Ok(DecodeReport {
+                ok: true,
+                encoding: Some(encoding.name().to_string()),
+                first_error_offset: None,
+                ascii_only: (encoding == UTF_8)
+                    .then_some(bom_len == 0 && scan.non_ascii_count == 0),
+            });
+        }
+        if best
+            .as_ref()
+            .is_none_or(|(_, current, _)| scan.error_count < current.error_count)
+        {
+            best = Some((encoding, scan, bom_len));
+        }
+    }
+    Ok(DecodeReport {
+        ok: false,
+        encoding: best
+            .as_ref()
+            .map(|(encoding, _, _)| encoding.name().to_string()),
+        first_error_offset: best.and_then(|(_, scan, bom_len)| {
+            scan.error_offsets
+                .first()
+                .map(|&offset| (offset + bom_len) as u64)
+        }),
+        ascii_only: None,
+    })
+}
+
+#[derive(Serialize)]
+pub(crate) struct EncodingFix {
+    offset: usize,
+    /// The malformed sequence as space-separated uppercase hex.
+    bad_bytes: String,
+    /// The sequence read as Windows-1252, the usual origin of stray bytes in UTF-8 exports.
+    suggested_char: String,
+}
+
+/// Start offset and length of each malformed sequence in `bytes` read as UTF-8.
+fn invalid_utf8_sequences(bytes: &[u8]) -> Vec<(usize, usize)> {
+    let mut sequences = Vec::new();
+    let mut start = 0;
+    while let Err(err) = std::str::from_utf8(&bytes[start..]) {
+        let offset = start + err.valid_up_to();
+        let len = err.error_len().unwrap_or(bytes.len() - offset);
+        sequences.push((offset, len));
+        start = offset + len;
+    }
+    sequences
+}
+
+/// Replaces every malformed UTF-8 sequence with the UTF-8 encoding of its Windows-1252 reading
+/// and returns the repaired bytes with the number of sequences fixed.
+fn repair_utf8_bytes(bytes: &[u8]) -> (Vec<u8>, usize) {
+    let sequences = invalid_utf8_sequences(bytes);
+    let mut repaired = Vec::with_capacity(bytes.len() + sequences.len());
+    let mut copied = 0;
+    for &(offset, len) in &sequences {
+        repaired.extend_from_slice(&bytes[copied..offset]);
+        let (suggested, _) = WINDOWS_1252.decode_without_bom_handling(&bytes[offset..offset + len]);
+        repaired.extend_from_slice(suggested.as_bytes());
+        copied = offset + len;
+    }
+    repaired.extend_from_slice(&bytes[copied..]);
+    (repaired, sequences.len())
+}
+
+/// Lists the malformed UTF-8 sequences (at most `MAX_REPORTED_DECODE_ERRORS`) with the
+/// character each most likely stood for; `repair_utf8` applies all of them.
+#[tauri::command]
+pub(crate) fn suggest_encoding_fix(path: String) -> Result<Vec<EncodingFix>, String> {
+    let bytes = std::fs::read(resolve_input_path(&path))
+        .map_err(|err| format!("Failed to read file bytes: {err}"))?;
+    ensure_byte_lines(&ReadOptions::default(), &bytes, "Repairing")?;
+    Ok(invalid_utf8_sequences(&bytes)
+        .into_iter()
+        .take(MAX_REPORTED_DECODE_ERRORS)
+        .map(|(offset, len)| {
+            let bad = &bytes[offset..offset + len];
+            EncodingFix {
+                offset,
+                bad_bytes: bad
+                    .iter()
+                    .map(|byte| format!("{byte:02X}"))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                suggested_char: WINDOWS_1252.decode_without_bom_handling(bad).0.into_owned(),
+            }
+        })
+        .collect())
+}
+
+/// Writes `path` to `out_path` (which may be `path` itself) with every suggestion of
+/// `suggest_encoding_fix` applied, leaving valid UTF-8 untouched. Returns the number of fixes.
+#[tauri::command]
+pub(crate) fn repair_utf8(path: String, out_path: String) -> Result<usize, String> {
+    let bytes = std::fs::read(resolve_input_path(&path))
+        .map_err(|err| format!("Failed to read file bytes: {err}"))?;
+    ensure_byte_lines(&ReadOptions::default(), &bytes, "Repairing")?;
+    let (repaired, fixed) = repair_utf8_bytes(&bytes);
+    write_atomic(&resolve_input_path(&out_path), &repaired)?;
+    Ok(fixed)
+}
+
+#[derive(Serialize)]
+pub(crate) struct EncodingScore {
+    name: String,
+    had_errors: bool,
+    error_count: usize,
+    non_ascii_count: usize,
+}
+
+/// Scores the detection candidates first, then every other supported encoding, so the UI can
+/// suggest the encoding with the fewest errors.
+#[tauri::command]
+pub(crate) fn encoding_scorecard(
+    settings: tauri::State<SettingsState>,
+    path: String,
+) -> Result<Vec<EncodingScore>, String> {
+    let resolved = resolve_input_path(&path);
+    let bytes =
+        std::fs::read(&resolved).map_err(|err| format!("Failed to read file bytes: {err}"))?;
+    let options = read_options_for(&settings, &resolved);
+    let mut candidates = detection_candidates(&bytes, options.fallback_chain());
+    for &encoding in SUPPORTED_ENCODINGS {
+        if !candidates.contains(&encoding) {
+            candidates.push(encoding);
+        }
+    }
+
+    Ok(candidates
+        .into_iter()
+        .map(|encoding| {
+            let bom_len = bom_len_for(encoding, &bytes);
+            let scan = scan_decode(encoding, &bytes[bom_len..]);
+            EncodingScore {
+                name: encoding.name().to_string(),
+                had_errors: scan.error_count > 0,
+                error_count: scan.error_count,
+                non_ascii_count: scan.non_ascii_count,
+            }
+        })
+        .collect())
+}
+
+/// Leading bytes `sniff_ntr_encoding` scores, cut back to the last line break so a multi-byte
+/// character at the end is not mistaken for an invalid sequence.
+const ENCODING_SNIFF_BYTES: usize = 64 * 1024;
+
+/// Heuristic 0–100 likelihood that `sample` is text in `encoding`. A BOM settles it outright.
+/// UTF-16 is judged by how consistently every other byte is NUL, which holds for mostly-ASCII
+/// text. For the others, NUL bytes or invalid sequences rule the encoding out; valid non-ASCII
+/// UTF-8 is very unlikely by chance, while legacy encodings score by how many of the decoded
+/// non-ASCII characters are letters.
+fn encoding_confidence(sample: &[u8], encoding: &'static Encoding) -> u8 {
+    if let Some((bom_encoding, _)) = Encoding::for_bom(sample) {
+        return if bom_encoding == encoding { 100 } else { 0 };
+    }
+    let percent = |part: usize, whole: usize| (part * 100 / whole.max(1)) as u8;
+    if encoding == UTF_16LE || encoding == UTF_16BE {
+        let high = usize::from(encoding == UTF_16LE);
+        let zeros_at = |index: usize| {
+            sample
+                .chunks_exact(2)
+                .filter(|pair| pair[index] == 0)
+                .count()
+        };
+        let (zero_high, zero_low) = (zeros_at(high), zeros_at(1 - high));
+        return percent(zero_high.saturating_sub(zero_low), sample.len() / 2);
+    }
+    if sample.contains(&0) || scan_decode(encoding, sample).error_count > 0 {
+        return 0;
+    }
+    if sample.is_ascii() {
+        // Every ASCII-compatible encoding reads plain ASCII the same way.
+        return if encoding == UTF_8 { 95 } else { 50 };
+    }
+    if encoding == UTF_8 {
+        return 99;
+    }
+    let (decoded, _) = encoding.decode_without_bom_handling(sample);
+    let non_ascii = decoded.chars().filter(|ch| !ch.is_ascii());
+    let (letters, total) = non_ascii.fold((0, 0), |(letters, total), ch| {
+        (letters + usize::from(ch.is_alphabetic()), total + 1)
+    });
+    let score = percent(letters * 9, total * 10);
+    if std::str::from_utf8(sample).is_ok() {
+        score / 4
+    } else {
+        score
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct EncodingConfidence {
+    encoding: String,
+    /// Percentage from 0 to 100; see `encoding_confidence`.
+    confidence: u8,
+}
+
+/// Scores every supported encoding against the start of the file, most likely first, for a
+/// "95% confident this is UTF-8" readout. Unlike detection, this is a ranking, not a decision.
+#[tauri::command]
+pub(crate) fn sniff_ntr_encoding(path: String) -> Result<Vec<EncodingConfidence>, String> {
+    let file = File::open(resolve_input_path(&path))
+        .map_err(|err| format!("Failed to open file: {err}"))?;
+    let mut sample = Vec::with_capacity(ENCODING_SNIFF_BYTES);
+    file.take(ENCODING_SNIFF_BYTES as u64)
+        .read_to_end(&mut sample)
+        .map_err(|err| format!("Failed to read file: {err}"))?;
+    if sample.len() == ENCODING_SNIFF_BYTES {
+        if let Some(end) = sample.iter().rposition(|&byte| byte == b'\n') {
+            sample.truncate(end + 1);
+        }
+    }
+
+    let mut scores: Vec<EncodingConfidence> = SUPPORTED_ENCODINGS
+        .iter()
+        .map(|&encoding| EncodingConfidence {
+            encoding: encoding.name().to_string(),
+            confidence: encoding_confidence(&sample, encoding),
+        })
+        .collect();
+    scores.sort_by_key(|score| std::cmp::Reverse(score.confidence));
+    Ok(scores)
+}
+
+/// Approximate size of the windows `detect_mixed_encoding` classifies; each window is extended
+/// to the end of its line so multi-byte characters are never split.
+const MIXED_ENCODING_WINDOW: usize = 4096;
+
+#[derive(Serialize)]
+struct EncodingRange {
+    /// Byte offsets, end exclusive.
+    start: usize,
+    end: usize,
+    /// "UTF-8", or the first fallback encoding that decodes the range; "unknown" if none does.
+    encoding: String,
+}
+
+#[derive(Serialize)]
+pub(crate) struct MixedEncodingReport {
+    /// True when some ranges are valid UTF-8 and others only decode as a legacy encoding.
+    probably_mixed: bool,
+    /// Consecutive windows of the same verdict merged; pure-ASCII stretches belong to no range.
+    ranges: Vec<EncodingRange>,
+}
+
+/// `None` for pure ASCII, which every candidate decodes alike; otherwise "UTF-8" when `bytes`
+/// are strict UTF-8, the first of `fallbacks` that decodes them cleanly, or "unknown".
+fn classify_non_ascii(bytes: &[u8], fallbacks: &[&'static Encoding]) -> Option<&'static str> {
+    if bytes.is_ascii() {
+        return None;
+    }
+    if std::str::from_utf8(bytes).is_ok() {
+        return Some(UTF_8.name());
+    }
+    Some(
+        fallbacks
+            .iter()
+            .find(|encoding| scan_decode(encoding, bytes).error_count == 0)
+            .map_or("unknown", |encoding| encoding.name()),
+    )
+}
+
+/// Splits `bytes` into line-aligned windows and classifies those holding non-ASCII bytes as
+/// strict UTF-8 or, failing that, as the first of `fallbacks` that decodes them cleanly. A
+/// window holding both kinds counts as legacy, so boundaries are only as precise as a window.
+/// All-ASCII windows belong to no range and close the one before them.
+fn classify_encoding_windows(bytes: &[u8], fallbacks: &[&'static Encoding]) -> Vec<EncodingRange> {
+    let mut ranges: Vec<EncodingRange> = Vec::new();
+    let mut start = 0;
+    while start < bytes.len() {
+        let mut end = (start + MIXED_ENCODING_WINDOW).min(bytes.len());
+        end = bytes[end..]
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .map_or(bytes.len(), |index| end + index + 1);
+        if let Some(encoding) = classify_non_ascii(&bytes[start..end], fallbacks) {
+            match ranges.last_mut() {
+                Some(last) if last.encoding == encoding && last.end == start => last.end = end,
+                _ => ranges.push(EncodingRange {
+                    start,
+                    end,
+                    encoding: encoding.to_string(),
+                }),
+            }
+        }
+        start = end;
+    }
+    ranges
+}
+
+/// Flags files where part was written as UTF-8 and part in a legacy encoding, which no single
+/// decoding can display correctly. UTF-16 files are rejected.
+#[tauri::command]
+pub(crate) fn detect_mixed_encoding(
+    settings: tauri::State<SettingsState>,
+    path: String,
+) -> Result<MixedEncodingReport, String> {
+    let resolved = resolve_input_path(&path);
+    let bytes =
+        std::fs::read(&resolved).map_err(|err| format!("Failed to read file bytes: {err}"))?;
+    let options = read_options_for(&settings, &resolved);
+    ensure_byte_lines(&options, &bytes, "Checking")?;
+    let bom_len = Encoding::for_bom(&bytes).map_or(0, |(_, bom_len)| bom_len);
+    let mut ranges = classify_encoding_windows(&bytes[bom_len..], options.fallback_chain());
+    for range in &mut ranges {
+        range.start += bom_len;
+        range.end += bom_len;
+    }
+    let utf8 = ranges.iter().any(|range| range.encoding == UTF_8.name());
+    Ok(MixedEncodingReport {
+        probably_mixed: utf8 && ranges.iter().any(|range| range.encoding != UTF_8.name()),
+        ranges,
+    })
+}
+
+#[derive(Serialize)]
+struct LineEncodingGroup {
+    /// "UTF-8", a fallback encoding name or "unknown", as in `classify_non_ascii`.
+    encoding: String,
+    line_count: usize,
+    /// Runs of consecutive lines with this classification.
+    ranges: Vec<LineRange>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct PerLineEncodingReport {
+    /// Pure-ASCII lines, which read the same in every candidate and belong to no group.
+    ascii_lines: usize,
+    /// In order of each classification's first line.
+    groups: Vec<LineEncodingGroup>,
+}
+
+/// Classifies every line read from `reader` on its own with `classify_non_ascii`, holding one
+/// line at a time. A UTF-8 BOM at the start is ignored.
+fn classify_line_encodings(
+    mut reader: impl BufRead,
+    fallbacks: &[&'static Encoding],
+) -> std::io::Result<PerLineEncodingReport> {
+    let mut report = PerLineEncodingReport {
+        ascii_lines: 0,
+        groups: Vec::new(),
+    };
+    let mut line = Vec::new();
+    let mut number = 0;
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(report);
+        }
+        number += 1;
+        let bytes = match number {
+            1 => line.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(&line),
+            _ => &line,
+        };
+        let Some(encoding) = classify_non_ascii(bytes, fallbacks) else {
+            report.ascii_lines += 1;
+            continue;
+        };
+        let group = match report
+            .groups
+            .iter()
+            .position(|group| group.encoding == encoding)
+        {
+            Some(index) => &mut report.groups[index],
+            None => {
+                report.groups.push(LineEncodingGroup {
+                    encoding: encoding.to_string(),
+                    line_count: 0,
+                    ranges: Vec::new(),
+                });
+                report.groups.last_mut().expect("group just pushed")
+            }
+        };
+        group.line_count += 1;
+        match group.ranges.last_mut() {
+            Some(range) if range.start + range.count == number => range.count += 1,
+            _ => group.ranges.push(LineRange {
+                start: number,
+                count: 1,
+            }),
+        }
+    }
+}
+
+/// Line-level companion to `detect_mixed_encoding`: streams the file and reports which lines
+/// are UTF-8 and which only decode under a fallback encoding, so the lines pasted in from a
+/// differently encoded source can be found exactly. A remembered or declared encoding replaces
+/// the fallback chain, so lines are only ever attributed to it. UTF-16 files are rejected.
+#[tauri::command(async)]
+pub(crate) fn per_line_encoding(
+    settings: tauri::State<SettingsState>,
+    path: String,
+) -> Result<PerLineEncodingReport, String> {
+    let resolved = resolve_input_path(&path);
+    let options = read_options_for(&settings, &resolved);
+    let file = File::open(&resolved).map_err(|err| format!("Failed to open file: {err}"))?;
+    let mut reader = BufReader::new(file);
+    let leading = reader
+        .fill_buf()
+        .map_err(|err| format!("Failed to read file: {err}"))?;
+    ensure_byte_lines(&options, leading, "Checking")?;
+    let fallbacks = options
+        .encoding
+        .as_ref()
+        .map_or(options.fallback_chain(), std::slice::from_ref);
+    classify_line_encodings(reader, fallbacks).map_err(|err| format!("Failed to read file: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_supported_encoding_has_a_display_name() {
+        for &encoding in SUPPORTED_ENCODINGS {
+            assert!(
+                ENCODING_DISPLAY_NAMES
+                    .iter()
+                    .any(|(known, _, _)| *known == encoding),
+                "{} has no display name",
+                encoding.name()
+            );
+        }
+    }
+
+    #[test]
+    fn reports_the_decode_outcome_without_contents() {
+        let path = std::env::temp_dir().join(format!("ntr-can-decode-{}.ntr", std::process::id()));
+        std::fs::write(&path, b"RO 1\nRO \xE62\n").expect("write sample");
+        let detected = decode_report(&path, &ReadOptions::default()).expect("report");
+        let forced = ReadOptions {
+            encoding: Some(UTF_8),
+            ..Default::default()
+        };
+        let failed = decode_report(&path, &forced).expect("report");
+        std::fs::remove_file(&path).expect("remove sample");
+
+        assert!(detected.ok);
+        assert_eq!(detected.encoding.as_deref(), Some("windows-1252"));
+        assert_eq!(detected.first_error_offset, None);
+        assert!(!failed.ok);
+        assert_eq!(failed.encoding.as_deref(), Some("UTF-8"));
+        assert_eq!(failed.first_error_offset, Some(8));
+    }
+
+    #[test]
+    fn tells_pure_ascii_apart_from_utf8() {
+        let dir = std::env::temp_dir().join(format!("ntr-ascii-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("create dir");
+        let [ascii, utf8, legacy] = ["a.ntr", "b.ntr", "c.ntr"].map(|name| dir.join(name));
+        std::fs::write(&ascii, "RO NAME=AE\n").expect("write sample");
+        std::fs::write(&utf8, "RO NAME=\u{E6}\n").expect("write sample");
+        std::fs::write(&legacy, b"RO NAME=\xE6\n").expect("write sample");
+        let [ascii, utf8, legacy] = [ascii, utf8, legacy].map(|path| {
+            decode_report(&path, &ReadOptions::default())
+                .expect("report")
+                .ascii_only
+        });
+        std::fs::remove_dir_all(&dir).expect("remove dir");
+
+        assert_eq!(ascii, Some(true));
+        assert_eq!(utf8, Some(false));
+        assert_eq!(legacy, None);
+    }
+
+    #[test]
+    fn repairs_stray_windows_1252_bytes_in_utf8() {
+        let bytes = b"RO NAME=\xC3\xA6\xE6 W=10\x80\nBOG \xE2\x82";
+        assert_eq!(
+            invalid_utf8_sequences(bytes),
+            vec![(10, 1), (16, 1), (22, 2)]
+        );
+        let (repaired, fixed) = repair_utf8_bytes(bytes);
+        assert_eq!(fixed, 3);
+        let repaired = String::from_utf8(repaired).expect("valid UTF-8");
+        assert_eq!(repaired, "RO NAME=ææ W=10€\nBOG â‚");
+    }
+
+    #[test]
+    fn classifies_utf8_and_legacy_halves_of_a_concatenated_file() {
+        let mut bytes = b"RO NAME=\xC3\xA6\n".repeat(400);
+        let utf8_len = bytes.len();
+        bytes.extend(b"RO NAME=\xE6\n".repeat(800));
+        let ranges = classify_encoding_windows(&bytes, &[WINDOWS_1252]);
+        assert_eq!(ranges.len(), 2);
+        assert_eq!((ranges[0].start, ranges[0].encoding.as_str()), (0, "UTF-8"));
+        assert_eq!(ranges[1].encoding, "windows-1252");
+        assert_eq!(ranges[0].end, ranges[1].start);
+        assert!(ranges[0].end <= utf8_len && utf8_len < ranges[0].end + 2 * MIXED_ENCODING_WINDOW);
+        assert_eq!(ranges[1].end, bytes.len());
+
+        let mut bytes = b"RO NAME=\xE6\n".repeat(800);
+        bytes.extend(b"RO NAME=x\n".repeat(1000));
+        bytes.extend(b"RO NAME=\xE6\n".repeat(800));
+        let ranges = classify_encoding_windows(&bytes, &[WINDOWS_1252]);
+        assert_eq!(ranges.len(), 2);
+        assert!(ranges[0].end < ranges[1].start);
+    }
+
+    #[test]
+    fn scores_encoding_confidence_from_byte_patterns() {
+        let utf16: Vec<u8> = "RO P1=1\n"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        assert_eq!(encoding_confidence(&utf16, UTF_16LE), 100);
+        assert_eq!(encoding_confidence(&utf16, UTF_16BE), 0);
+        assert_eq!(encoding_confidence(&utf16, UTF_8), 0);
+
+        let utf8 = "TEXT='Grüße'\n".as_bytes();
+        assert_eq!(encoding_confidence(utf8, UTF_8), 99);
+        assert!(encoding_confidence(utf8, WINDOWS_1252) < 50);
+
+        let (legacy, _, _) = WINDOWS_1252.encode("TEXT='Grüße'\n");
+        assert_eq!(encoding_confidence(&legacy, UTF_8), 0);
+        assert_eq!(encoding_confidence(&legacy, WINDOWS_1252), 90);
+    }
+
+    #[test]
+    fn groups_lines_by_their_encoding() {
+        let mut bytes = b"\xEF\xBB\xBFplain\n".to_vec();
+        bytes.extend_from_slice("Grüße\nRør\n".as_bytes());
+        bytes.extend_from_slice(b"Gr\xFC\xDFe\nok\nR\xF8r\n");
+        let report = classify_line_encodings(&bytes[..], &[WINDOWS_1252]).unwrap();
+        assert_eq!(report.ascii_lines, 2);
+        let ranges = |group: &LineEncodingGroup| -> Vec<(usize, usize)> {
+            group
+                .ranges
+                .iter()
+                .map(|range| (range.start, range.count))
+                .collect()
+        };
+        assert_eq!(report.groups[0].encoding, "UTF-8");
+        assert_eq!(ranges(&report.groups[0]), vec![(2, 2)]);
+        assert_eq!(report.groups[1].encoding, "windows-1252");
+        assert_eq!(report.groups[1].line_count, 2);
+        assert_eq!(ranges(&report.groups[1]), vec![(4, 1), (6, 1)]);
+    }
+}
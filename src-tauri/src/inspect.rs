@@ -0,0 +1,306 @@
+use encoding_rs::{Encoding, UTF_8};
+use flate2::read::MultiGzDecoder;
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use crate::{
+    is_ntr_file, load_ntr_path, read_options_for, resolve_input_path, settings::SettingsState,
+    LoadError, OpenFileResponse,
+};
+
+/// Enough leading bytes to show any BOM `encoding_rs` recognises plus one byte of context.
+const BOM_PROBE_BYTES: usize = 4;
+
+#[derive(Serialize)]
+pub(crate) struct BomSignature {
+    /// Encoding declared by the BOM, or `None` when the file has no BOM.
+    encoding: Option<String>,
+    bom_len: usize,
+    /// The file's first `BOM_PROBE_BYTES` bytes as space-separated uppercase hex.
+    leading_bytes: String,
+}
+
+#[tauri::command]
+pub(crate) fn read_bom(path: String) -> Result<BomSignature, String> {
+    let file = File::open(resolve_input_path(&path))
+        .map_err(|err| format!("Failed to open file: {err}"))?;
+    let mut leading = Vec::with_capacity(BOM_PROBE_BYTES);
+    file.take(BOM_PROBE_BYTES as u64)
+        .read_to_end(&mut leading)
+        .map_err(|err| format!("Failed to read file: {err}"))?;
+    Ok(bom_signature(&leading))
+}
+
+fn bom_signature(leading: &[u8]) -> BomSignature {
+    let bom = Encoding::for_bom(leading);
+    BomSignature {
+        encoding: bom.map(|(encoding, _)| encoding.name().to_string()),
+        bom_len: bom.map_or(0, |(_, bom_len)| bom_len),
+        leading_bytes: leading
+            .iter()
+            .take(BOM_PROBE_BYTES)
+            .map(|byte| format!("{byte:02X}"))
+            .collect::<Vec<_>>()
+            .join(" "),
+    }
+}
+
+/// Bytes shown in the hex dump of `load_ntr_file_debug` unless the caller asks for another
+/// amount, which is capped at `MAX_DEBUG_HEX_BYTES`.
+const DEFAULT_DEBUG_HEX_BYTES: usize = 256;
+const MAX_DEBUG_HEX_BYTES: usize = 4096;
+
+/// Classic 16-bytes-per-row dump: hex offset, the bytes in uppercase hex, then the printable
+/// ASCII ones with `.` standing in for the rest.
+fn hex_dump(bytes: &[u8]) -> String {
+    let mut dump = String::new();
+    for (row, chunk) in bytes.chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|byte| format!("{byte:02X}")).collect();
+        let ascii: String = chunk
+            .iter()
+            .map(|&byte| {
+                if byte.is_ascii_graphic() || byte == b' ' {
+                    char::from(byte)
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        dump.push_str(&format!(
+            "{:08X}  {:<47}  |{ascii}|\n",
+            row * 16,
+            hex.join(" ")
+        ));
+    }
+    dump
+}
+
+#[derive(Serialize)]
+pub(crate) struct DebugLoadResponse {
+    #[serde(flatten)]
+    file: OpenFileResponse,
+    bom: BomSignature,
+    /// `hex_dump` of the first bytes of the raw file.
+    hex_dump: String,
+    /// True when the file is longer than the dumped bytes.
+    hex_truncated: bool,
+    byte_count: u64,
+    char_count: usize,
+}
+
+/// Loads the file like `load_ntr_file` and adds the raw view next to the decoded one, for the
+/// inspect panel: BOM, a hex dump of the first `hex_bytes` bytes, and byte and char counts.
+#[tauri::command]
+pub(crate) fn load_ntr_file_debug(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    hex_bytes: Option<usize>,
+) -> Result<DebugLoadResponse, LoadError> {
+    let resolved = &resolve_input_path(&path);
+    let file = load_ntr_path(resolved, read_options_for(&settings, resolved))?;
+    let hex_bytes = hex_bytes
+        .unwrap_or(DEFAULT_DEBUG_HEX_BYTES)
+        .min(MAX_DEBUG_HEX_BYTES);
+    let raw =
+        File::open(resolved).map_err(|err| LoadError::io(format!("Failed to open file: {err}")))?;
+    let byte_count = raw
+        .metadata()
+        .map_err(|err| LoadError::io(format!("Failed to read file metadata: {err}")))?
+        .len();
+    let mut leading = Vec::with_capacity(hex_bytes.max(BOM_PROBE_BYTES));
+    raw.take(hex_bytes.max(BOM_PROBE_BYTES) as u64)
+        .read_to_end(&mut leading)
+        .map_err(|err| LoadError::io(format!("Failed to read file: {err}")))?;
+    let dumped = &leading[..hex_bytes.min(leading.len())];
+    Ok(DebugLoadResponse {
+        bom: bom_signature(&leading),
+        hex_dump: hex_dump(dumped),
+        hex_truncated: (dumped.len() as u64) < byte_count,
+        byte_count,
+        char_count: file.contents.chars().count(),
+        file,
+    })
+}
+
+#[derive(Serialize)]
+pub(crate) struct FileSummary {
+    path: String,
+    byte_count: u64,
+    line_count: usize,
+    encoding: String,
+    /// "lf", "crlf", "cr" or "mixed"; `None` when the file has no line breaks.
+    line_ending: Option<&'static str>,
+    /// Checked on the raw bytes; see `ends_with_newline`.
+    ends_with_newline: bool,
+}
+
+/// The facts about a file that matter before handing it to another tool, without returning
+/// its contents.
+#[tauri::command(async)]
+pub(crate) fn file_summary(
+    settings: tauri::State<SettingsState>,
+    path: String,
+) -> Result<FileSummary, LoadError> {
+    let resolved = resolve_input_path(&path);
+    let file = load_ntr_path(&resolved, read_options_for(&settings, &resolved))?;
+    let byte_count = std::fs::metadata(&resolved)
+        .map_err(|err| LoadError::io(format!("Failed to read file metadata: {err}")))?
+        .len();
+    Ok(FileSummary {
+        line_count: file.contents.lines().count(),
+        path: file.path,
+        byte_count,
+        encoding: file.encoding,
+        line_ending: file.line_ending,
+        ends_with_newline: file.ends_with_newline,
+    })
+}
+
+/// Leading bytes inspected by `guess_file_type`.
+const TYPE_SNIFF_BYTES: usize = 8192;
+/// Archive and compression signatures recognised by `guess_file_type`.
+const MAGIC_SIGNATURES: &[(&[u8], &str)] = &[
+    (b"\x1F\x8B", "application/gzip"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"PK\x05\x06", "application/zip"),
+    (b"BZh", "application/x-bzip2"),
+    (b"\x28\xB5\x2F\xFD", "application/zstd"),
+    (b"7z\xBC\xAF\x27\x1C", "application/x-7z-compressed"),
+];
+
+/// Treats data as binary when it contains a NUL byte or when more than one in ten bytes is a
+/// control character other than tab, line feed, carriage return or form feed. UTF-16 text is
+/// full of NUL bytes, so data with a UTF-16 BOM is never binary.
+fn looks_binary(leading: &[u8]) -> bool {
+    if matches!(Encoding::for_bom(leading), Some((encoding, _)) if encoding != UTF_8) {
+        return false;
+    }
+    let controls = leading
+        .iter()
+        .filter(|&&byte| byte < 0x20 && !matches!(byte, b'\t' | b'\n' | b'\r' | 0x0C))
+        .count();
+    leading.contains(&0) || controls * 10 > leading.len()
+}
+
+fn guess_mime(leading: &[u8], path: &Path) -> &'static str {
+    if let Some((_, mime)) = MAGIC_SIGNATURES
+        .iter()
+        .find(|(signature, _)| leading.starts_with(signature))
+    {
+        return mime;
+    }
+    if looks_binary(leading) {
+        "application/octet-stream (binary)"
+    } else if is_ntr_file(path) {
+        "text/plain; ntr"
+    } else {
+        "text/plain"
+    }
+}
+
+/// Guesses the file type from its first `TYPE_SNIFF_BYTES` bytes, so compressed or binary
+/// files can be routed elsewhere instead of failing to decode. Text files with an `.ntr`
+/// extension are reported as "text/plain; ntr".
+#[tauri::command]
+pub(crate) fn guess_file_type(path: String) -> Result<String, String> {
+    let resolved = resolve_input_path(&path);
+    let file = File::open(&resolved).map_err(|err| format!("Failed to open file: {err}"))?;
+    let mut leading = Vec::with_capacity(TYPE_SNIFF_BYTES);
+    file.take(TYPE_SNIFF_BYTES as u64)
+        .read_to_end(&mut leading)
+        .map_err(|err| format!("Failed to read file: {err}"))?;
+    Ok(guess_mime(&leading, &resolved).to_string())
+}
+
+/// A gzip member is a 10-byte header and an 8-byte trailer around the deflate data.
+const MIN_GZIP_BYTES: u64 = 18;
+/// Deflate compresses at most about 1032:1, so a gzip file no larger than
+/// `u32::MAX / MAX_DEFLATE_RATIO` bytes cannot expand past 4 GiB.
+const MAX_DEFLATE_RATIO: u64 = 1032;
+
+#[derive(Serialize)]
+pub(crate) struct GzipSize {
+    compressed_bytes: u64,
+    uncompressed_bytes: u64,
+    /// True when read from the footer's ISIZE field, which holds the size modulo 4 GiB; false
+    /// when the file was decompressed and counted because it could have wrapped.
+    from_footer: bool,
+}
+
+/// Reports how large a `.gz` file expands to. Files too small to reach 4 GiB are answered from
+/// the ISIZE footer without decompressing; larger ones are decompressed as a stream and
+/// counted. The footer only covers the last member of a multi-member file.
+#[tauri::command(async)]
+pub(crate) fn gzip_uncompressed_size(path: String) -> Result<GzipSize, String> {
+    let mut file = File::open(resolve_input_path(&path))
+        .map_err(|err| format!("Failed to open file: {err}"))?;
+    let compressed_bytes = file
+        .metadata()
+        .map_err(|err| format!("Failed to read file metadata: {err}"))?
+        .len();
+    let mut magic = [0u8; 2];
+    if compressed_bytes < MIN_GZIP_BYTES
+        || file.read_exact(&mut magic).is_err()
+        || magic != [0x1F, 0x8B]
+    {
+        return Err("Not a gzip file".into());
+    }
+
+    if compressed_bytes <= u64::from(u32::MAX) / MAX_DEFLATE_RATIO {
+        let mut footer = [0u8; 4];
+        file.seek(SeekFrom::End(-4))
+            .and_then(|_| file.read_exact(&mut footer))
+            .map_err(|err| format!("Failed to read gzip footer: {err}"))?;
+        return Ok(GzipSize {
+            compressed_bytes,
+            uncompressed_bytes: u64::from(u32::from_le_bytes(footer)),
+            from_footer: true,
+        });
+    }
+
+    file.seek(SeekFrom::Start(0))
+        .map_err(|err| format!("Failed to read file: {err}"))?;
+    let mut decoder = MultiGzDecoder::new(BufReader::new(file));
+    let uncompressed_bytes = std::io::copy(&mut decoder, &mut std::io::sink())
+        .map_err(|err| format!("Failed to decompress file: {err}"))?;
+    Ok(GzipSize {
+        compressed_bytes,
+        uncompressed_bytes,
+        from_footer: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guesses_archives_binary_and_text_from_leading_bytes() {
+        let ntr = Path::new("export.NTR");
+        assert_eq!(guess_mime(b"\x1F\x8B\x08\x00", ntr), "application/gzip");
+        assert_eq!(guess_mime(b"PK\x03\x04\x14\x00", ntr), "application/zip");
+        assert_eq!(
+            guess_mime(b"RO\x00\x01\x02", ntr),
+            "application/octet-stream (binary)"
+        );
+        assert_eq!(guess_mime(b"\xFF\xFER\x00O\x00", ntr), "text/plain; ntr");
+        assert_eq!(
+            guess_mime(b"RO NAME=1\r\n", Path::new("notes.txt")),
+            "text/plain"
+        );
+    }
+
+    #[test]
+    fn dumps_bytes_as_hex_rows_with_ascii_column() {
+        let dump = hex_dump(b"RO P1=1\r\nBOG P2=2\n");
+        assert_eq!(
+            dump,
+            "00000000  52 4F 20 50 31 3D 31 0D 0A 42 4F 47 20 50 32 3D  |RO P1=1..BOG P2=|\n\
+             00000010  32 0A                                            |2.|\n"
+        );
+    }
+}
@@ -1,8 +1,44 @@
-use encoding_rs::{Encoding, UTF_8, WINDOWS_1252};
-use notify::{Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
-use serde::Serialize;
-use std::{path::{Path, PathBuf}, sync::{Arc, Mutex}};
-use tauri::Emitter;
+mod clipboard;
+mod diff;
+mod encoding;
+mod inspect;
+mod lines;
+mod logging;
+mod multiwatch;
+mod records;
+mod search;
+mod settings;
+mod snapshots;
+mod transform;
+
+use base64::Engine;
+use diff::{diff_sequences, DiffHunk, LineRange};
+use encoding_rs::{
+    DecoderResult, Encoding, BIG5, EUC_KR, GB18030, ISO_8859_15, ISO_8859_2, KOI8_R, SHIFT_JIS,
+    UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1250, WINDOWS_1251, WINDOWS_1252,
+};
+use lines::{line_hashes, MappedFileState};
+use notify::{
+    event::ModifyKind, Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+    WatcherKind,
+};
+use regex::Regex;
+use search::{SearchState, StreamState};
+use serde::{Deserialize, Serialize};
+use settings::SettingsState;
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{BufRead, BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tauri::{Emitter, Manager};
 use tauri_plugin_dialog::DialogExt;
 
 #[derive(Default)]
@@ -11,23 +47,168 @@ struct WatcherState {
 }
 
 struct ActiveWatcher {
+    watcher: RecommendedWatcher,
+    file_path: PathBuf,
+    /// The directory notify actually watches: the file's parent, so deletes and atomic
+    /// replacements are still seen.
+    watched_dir: PathBuf,
+    /// `file_path` normalized with the watched volume's case sensitivity, used for matching.
+    normalized_path: String,
+    /// Probed per watch rather than assumed per OS, since one machine can mount both kinds.
+    case_insensitive: bool,
+    /// Line hashes of the last-known contents for diff mode; `None` when diffing is off or the
+    /// file outgrew `MAX_DIFF_SNAPSHOT_BYTES`.
+    _diff_snapshot: Option<Arc<Mutex<Option<Vec<u64>>>>>,
+    /// Tells the parent-directory monitor thread to exit once this watch is replaced or stopped.
+    monitor_stop: Arc<AtomicBool>,
+    /// Set while the watched file is deleted; the directory watch stays active so a recreated
+    /// file clears it again.
+    orphaned: Arc<AtomicBool>,
+    /// Cleared while the file exists but cannot be opened, e.g. after its permissions were
+    /// revoked; see `emit_readability_change`.
+    readable: Arc<AtomicBool>,
+    events: Arc<WatchEventCounts>,
+    tag: Option<String>,
+}
+
+/// Events seen by a watch since it started, for `watch_debug_info`.
+#[derive(Default)]
+struct WatchEventCounts {
+    /// Every event notify delivered for the watched directory.
+    received: AtomicU64,
+    /// Events whose paths matched the watched file.
+    matched: AtomicU64,
+    /// Matched events that passed the kind filter and were forwarded to the frontend.
+    emitted: AtomicU64,
+    last_matched_ms: AtomicU64,
+}
+
+impl Drop for ActiveWatcher {
+    fn drop(&mut self) {
+        self.monitor_stop.store(true, Ordering::Relaxed);
+    }
+}
+
+const PARENT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Default)]
+struct LatestWatchState {
+    inner: Mutex<Option<LatestWatcher>>,
+}
+
+struct LatestWatcher {
     _watcher: RecommendedWatcher,
-    _file_path: PathBuf,
+    _current: Arc<Mutex<Option<String>>>,
+}
+
+const DIFF_DEBOUNCE: Duration = Duration::from_millis(250);
+/// Modifies arriving within this window of a create (or of each other after one) are folded
+/// into a single "create" event once the new file has settled.
+const CREATE_COALESCE_WINDOW: Duration = Duration::from_millis(300);
+const MAX_DIFF_SNAPSHOT_BYTES: usize = 16 * 1024 * 1024;
+
+#[derive(Clone, Serialize)]
+struct FileDiffPayload {
+    path: String,
+    added: Vec<LineRange>,
+    removed: Vec<LineRange>,
+}
+
+/// Optional behaviour for `start_file_watch`; omitting it watches for plain change events.
+#[derive(Default, Deserialize)]
+struct WatchOptions {
+    /// Emits `ntr-file-diff` with the changed line ranges after each modification.
+    diff: Option<bool>,
+    /// Event kinds (`access`, `modify`, `remove`, ...) that are not forwarded to the frontend;
+    /// defaults to `DEFAULT_IGNORED_KINDS`.
+    ignore_kinds: Option<Vec<String>>,
+    /// Echoed back on every event of this watch so callers can tell watches apart.
+    tag: Option<String>,
+    append: Option<AppendWatchOptions>,
+    snapshots: Option<snapshots::SnapshotWatchOptions>,
+}
+
+/// Enables append mode on `start_file_watch`: bytes added to the end of the file are decoded
+/// and emitted as `ntr-file-appended`, one event per batch of complete lines.
+#[derive(Deserialize)]
+struct AppendWatchOptions {
+    /// Only appends with a line matching this regular expression are emitted, and only the
+    /// matching lines are included; other appends are skipped silently.
+    emit_filter_regex: Option<String>,
+    /// Holds appends back until the file has grown by at least this many bytes since the last
+    /// emission, so chatty producers yield fewer, larger events.
+    min_growth_bytes: Option<u64>,
+    /// With `min_growth_bytes`, how long the file must stay quiet before a smaller growth is
+    /// emitted anyway; defaults to `DEFAULT_APPEND_FLUSH_AFTER`.
+    flush_after_ms: Option<u64>,
+}
+
+/// How long buffered growth below `min_growth_bytes` waits for more appends.
+const DEFAULT_APPEND_FLUSH_AFTER: Duration = Duration::from_secs(5);
+
+/// Growth an append watch accumulates before emitting; see `AppendWatchOptions`.
+struct GrowthThreshold {
+    min_bytes: u64,
+    flush_after: Duration,
 }
 
 #[derive(Clone, Serialize)]
 struct FileChangePayload {
     path: String,
     kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    contents: Option<String>,
+    /// Set on a settled create when re-detection found a different encoding than before;
+    /// other changes report that through `ntr-encoding-changed` once the file is quiet.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    encoding: Option<String>,
+    /// Opaque caller label given to `start_file_watch`, echoed on that watch's events.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tag: Option<String>,
+}
+
+const WATCH_LOG_CAPACITY: usize = 500;
+
+static WATCH_LOGGING: AtomicBool = AtomicBool::new(false);
+static WATCH_LOG: Mutex<VecDeque<WatchLogEntry>> = Mutex::new(VecDeque::new());
+
+#[derive(Clone, Serialize)]
+struct WatchLogEntry {
+    timestamp_ms: u64,
+    message: String,
+}
+
+fn watch_logging_enabled() -> bool {
+    cfg!(debug_assertions) || WATCH_LOGGING.load(Ordering::Relaxed)
 }
 
-#[cfg(debug_assertions)]
 fn log_watch_event(message: &str) {
-    println!("[watch] {message}");
+    log::debug!(target: "watch", "{message}");
+
+    if !WATCH_LOGGING.load(Ordering::Relaxed) {
+        return;
+    }
+    let timestamp_ms = now_ms();
+    let mut log = WATCH_LOG.lock().expect("watch log poisoned");
+    if log.len() == WATCH_LOG_CAPACITY {
+        log.pop_front();
+    }
+    log.push_back(WatchLogEntry {
+        timestamp_ms,
+        message: message.to_string(),
+    });
 }
 
-#[cfg(not(debug_assertions))]
-fn log_watch_event(_message: &str) {}
+#[tauri::command]
+fn set_watch_logging(enabled: bool) {
+    WATCH_LOGGING.store(enabled, Ordering::Relaxed);
+}
+
+#[tauri::command]
+fn get_watch_log() -> Vec<WatchLogEntry> {
+    let log = WATCH_LOG.lock().expect("watch log poisoned");
+    log.iter().cloned().collect()
+}
 
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 #[tauri::command]
@@ -35,14 +216,122 @@ fn greet(name: &str) -> String {
     format!("Hello, {}! You've been greeted from Rust!", name)
 }
 
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum EncodingSource {
+    /// Picked by BOM or auto-detection.
+    Detected,
+    /// Chosen explicitly or remembered for this file.
+    Override,
+    /// Declared by a `<file>.encoding` sidecar next to the file.
+    Sidecar,
+}
+
 #[derive(Serialize)]
 struct OpenFileResponse {
     path: String,
     contents: String,
+    stripped_bom_count: usize,
+    /// Whether any tab in `contents` was expanded to spaces; the file itself is untouched.
+    tabs_expanded: bool,
+    ends_with_newline: bool,
+    encoding: String,
+    encoding_source: EncodingSource,
+    /// Whether the file started with a byte order mark; it is not part of `contents`.
+    had_bom: bool,
+    /// "lf", "crlf", "cr" or "mixed"; `None` when the file has no line breaks.
+    line_ending: Option<&'static str>,
+    /// True when the file decoded as UTF-8 and has no byte above 0x7F, so it is plain
+    /// US-ASCII and safe for systems that reject anything else.
+    ascii_only: bool,
+}
+
+#[derive(Clone, Default)]
+struct ReadOptions {
+    /// Removes every U+FEFF from the decoded text, not just a leading BOM. Off by default so
+    /// intentional zero-width no-break spaces survive.
+    strip_bom_chars: bool,
+    /// Decodes with this encoding instead of running auto-detection.
+    encoding: Option<&'static Encoding>,
+    /// Set when `encoding` was declared by a `<file>.encoding` sidecar rather than chosen.
+    from_sidecar: bool,
+    /// Legacy encodings tried after UTF-8 during detection; empty means
+    /// `DEFAULT_FALLBACK_ENCODINGS`.
+    fallbacks: Vec<&'static Encoding>,
+    /// Expands each `\t` in the returned contents to spaces up to the next multiple of this
+    /// many columns, counted per line.
+    tab_width: Option<usize>,
+}
+
+impl ReadOptions {
+    fn fallback_chain(&self) -> &[&'static Encoding] {
+        if self.fallbacks.is_empty() {
+            DEFAULT_FALLBACK_ENCODINGS
+        } else {
+            &self.fallbacks
+        }
+    }
+}
+
+/// Detection order when no BOM is present: UTF-8 first, then these in order. Single-byte
+/// encodings such as Windows-1252 and ISO-8859-15 accept every byte sequence, so the first
+/// single-byte entry always wins and anything after it is only reached for multi-byte
+/// encodings that can fail. Windows-1252 and ISO-8859-15 differ in eight code points (e.g. the
+/// euro sign is 0x80 in Windows-1252 but 0xA4 in ISO-8859-15); put ISO-8859-15 first in the
+/// configured chain when files come from Latin-9 producers.
+const DEFAULT_FALLBACK_ENCODINGS: &[&Encoding] = &[WINDOWS_1252];
+
+/// Encodings offered for manual overrides and fallback configuration.
+const SUPPORTED_ENCODINGS: &[&Encoding] = &[
+    UTF_8,
+    UTF_16LE,
+    UTF_16BE,
+    WINDOWS_1252,
+    ISO_8859_15,
+    WINDOWS_1250,
+    ISO_8859_2,
+    WINDOWS_1251,
+    KOI8_R,
+    SHIFT_JIS,
+    GB18030,
+    BIG5,
+    EUC_KR,
+];
+
+fn configured_fallbacks(settings: &SettingsState) -> Vec<&'static Encoding> {
+    settings.read(|settings| {
+        settings
+            .fallback_encodings
+            .iter()
+            .filter_map(|label| Encoding::for_label(label.as_bytes()))
+            .collect()
+    })
+}
+
+/// Read options honouring the remembered per-file encoding, then a `<file>.encoding` sidecar,
+/// and the configured fallback chain. Every reader of a file goes through this, so analysis,
+/// export and watch commands decode it exactly like the viewer.
+fn read_options_for(settings: &SettingsState, path: &Path) -> ReadOptions {
+    let remembered = remembered_encoding(settings, path);
+    let sidecar = remembered
+        .is_none()
+        .then(|| sidecar_encoding(path))
+        .flatten();
+    ReadOptions {
+        encoding: remembered.or(sidecar),
+        from_sidecar: sidecar.is_some(),
+        fallbacks: configured_fallbacks(settings),
+        ..ReadOptions::default()
+    }
 }
 
 #[tauri::command]
-fn open_ntr_file(app: tauri::AppHandle) -> Result<Option<OpenFileResponse>, String> {
+fn open_ntr_file(
+    app: tauri::AppHandle,
+    settings: tauri::State<SettingsState>,
+    strip_bom_chars: Option<bool>,
+    tab_width: Option<usize>,
+) -> Result<Option<OpenFileResponse>, LoadError> {
     let selection = app
         .dialog()
         .file()
@@ -54,142 +343,2285 @@ fn open_ntr_file(app: tauri::AppHandle) -> Result<Option<OpenFileResponse>, Stri
     };
 
     let Some(path) = file.as_path() else {
-        return Err("Selected file is not accessible on this platform".into());
+        return Err(LoadError::io(
+            "Selected file is not accessible on this platform",
+        ));
     };
 
-    let response = read_ntr_file(path)?;
+    let options = ReadOptions {
+        strip_bom_chars: strip_bom_chars.unwrap_or(false),
+        tab_width,
+        ..read_options_for(&settings, path)
+    };
+    let response = read_ntr_file(path, options)?;
     Ok(Some(response))
 }
 
 #[tauri::command]
-fn load_ntr_file(path: String) -> Result<OpenFileResponse, String> {
-    let resolved = Path::new(&path);
+fn load_ntr_file(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    strip_bom_chars: Option<bool>,
+    tab_width: Option<usize>,
+) -> Result<OpenFileResponse, LoadError> {
+    let resolved = &resolve_input_path(&path);
+    let options = ReadOptions {
+        strip_bom_chars: strip_bom_chars.unwrap_or(false),
+        tab_width,
+        ..read_options_for(&settings, resolved)
+    };
+    load_ntr_path(resolved, options)
+}
+
+/// Encodes `contents` for saving, UTF-8 unless `encoding` names another. Passing back the
+/// `had_bom` and `line_ending` a load reported reproduces the original file byte for byte;
+/// a `line_ending` of "mixed" leaves the endings in `contents` as they are.
+fn encode_for_save(
+    contents: &str,
+    encoding: &'static Encoding,
+    had_bom: bool,
+    line_ending: Option<&str>,
+) -> Result<Vec<u8>, String> {
+    let converted = match line_ending {
+        None | Some("mixed") => None,
+        Some(name) => {
+            let target = transform::line_ending_named(name)?;
+            Some(transform::convert_line_endings(contents, target).0)
+        }
+    };
+    let mut bytes = Vec::new();
+    if had_bom {
+        if encoding != UTF_8 {
+            let name = encoding.name();
+            return Err(format!(
+                "Writing a byte order mark for {name} is not supported"
+            ));
+        }
+        bytes.extend_from_slice(b"\xEF\xBB\xBF");
+    }
+    bytes.extend(encode_contents(
+        converted.as_deref().unwrap_or(contents),
+        encoding,
+    )?);
+    Ok(bytes)
+}
+
+/// Encodes `contents` with `encode_for_save` and writes it atomically to `path`, remembering
+/// its directory for the next save dialog.
+fn save_contents(
+    settings: &SettingsState,
+    path: &Path,
+    contents: &str,
+    encoding: Option<&str>,
+    had_bom: bool,
+    line_ending: Option<&str>,
+) -> Result<(), String> {
+    let encoding = match encoding {
+        Some(label) => parse_encoding_label(label)?,
+        None => UTF_8,
+    };
+    write_atomic(
+        path,
+        &encode_for_save(contents, encoding, had_bom, line_ending)?,
+    )?;
+    log::info!("Saved {} ({})", path.display(), encoding.name());
+    let directory = path
+        .parent()
+        .map(|dir| normalize_path(&resolve_absolute_path(dir)));
+    settings.update(|settings| settings.last_save_dir = directory)
+}
+
+#[tauri::command]
+fn save_ntr_file(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    contents: String,
+    encoding: Option<String>,
+    had_bom: Option<bool>,
+    line_ending: Option<String>,
+) -> Result<String, String> {
+    let resolved = resolve_input_path(&path);
+    save_contents(
+        &settings,
+        &resolved,
+        &contents,
+        encoding.as_deref(),
+        had_bom.unwrap_or(false),
+        line_ending.as_deref(),
+    )?;
+    Ok(normalize_path(&resolve_absolute_path(&resolved)))
+}
+
+/// Asks for a target with the native save dialog, starting in the directory of the last save,
+/// and writes there. Returns the chosen path, or `None` when the dialog was cancelled.
+#[tauri::command]
+fn save_ntr_file_as(
+    app: tauri::AppHandle,
+    settings: tauri::State<SettingsState>,
+    contents: String,
+    encoding: Option<String>,
+    default_name: Option<String>,
+    had_bom: Option<bool>,
+    line_ending: Option<String>,
+) -> Result<Option<String>, String> {
+    let mut dialog = app.dialog().file().add_filter("NTR files", &["ntr"]);
+    if let Some(directory) = settings.read(|settings| settings.last_save_dir.clone()) {
+        dialog = dialog.set_directory(directory);
+    }
+    if let Some(name) = default_name {
+        dialog = dialog.set_file_name(name);
+    }
+    let Some(file) = dialog.blocking_save_file() else {
+        return Ok(None);
+    };
+    let Some(path) = file.as_path() else {
+        return Err("Selected file is not accessible on this platform".into());
+    };
+
+    save_contents(
+        &settings,
+        path,
+        &contents,
+        encoding.as_deref(),
+        had_bom.unwrap_or(false),
+        line_ending.as_deref(),
+    )?;
+    Ok(Some(normalize_path(&resolve_absolute_path(path))))
+}
+
+/// Compares `contents` with the decoded file after dropping a leading U+FEFF and converting
+/// all line endings to `\n` on both sides, so only real edits count. A missing file always
+/// has unsaved changes.
+#[tauri::command]
+fn has_unsaved_changes(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    contents: String,
+) -> Result<bool, String> {
+    let resolved = resolve_input_path(&path);
     if !resolved.exists() {
-        return Err("File not found".into());
+        return Ok(true);
     }
-    if !resolved.is_file() {
-        return Err("Path does not point to a file".into());
+    let on_disk = read_decoded_text(&settings, &resolved)?;
+    let normalize = |text: &str| {
+        let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+        transform::convert_line_endings(text, "\n").0
+    };
+    Ok(normalize(&on_disk.contents) != normalize(&contents))
+}
+
+#[derive(Serialize)]
+struct DiskDiff {
+    /// Lines of the file on disk that differ from `contents`.
+    on_disk: Vec<LineRange>,
+    /// Lines of `contents` that differ from the file on disk.
+    in_memory: Vec<LineRange>,
+}
+
+/// Line-diffs the decoded file against the editor's `contents` with the same algorithm as the
+/// diff watch mode, so the UI can show what changed on disk during editing. A leading U+FEFF
+/// and line-ending style are ignored like in `has_unsaved_changes`.
+#[tauri::command]
+fn diff_against_disk(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    contents: String,
+) -> Result<DiskDiff, String> {
+    let on_disk = read_decoded_text(&settings, &resolve_input_path(&path))?;
+    Ok(disk_diff(&on_disk.contents, &contents))
+}
+
+fn disk_diff(on_disk: &str, contents: &str) -> DiskDiff {
+    let lines = |text: &str| -> Vec<String> {
+        let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+        text.lines().map(str::to_string).collect()
+    };
+    let hunks = diff_sequences(&lines(on_disk), &lines(contents));
+    DiskDiff {
+        on_disk: hunks.iter().filter_map(DiffHunk::removed).collect(),
+        in_memory: hunks.iter().filter_map(DiffHunk::added).collect(),
     }
-    read_ntr_file(resolved)
 }
 
-fn read_ntr_file(path: &Path) -> Result<OpenFileResponse, String> {
-    let bytes = std::fs::read(path)
-        .map_err(|err| format!("Failed to read file bytes: {err}"))?;
-    let contents = decode_ntr_bytes(&bytes)?;
-    let canonical = path
-        .canonicalize()
-        .unwrap_or_else(|_| path.to_path_buf());
-    Ok(OpenFileResponse {
-        path: normalize_path(&canonical),
-        contents,
+/// A file opened as the starting point for a new one. It carries no save path, so saving has
+/// to go through "save as" and the source is never overwritten.
+#[derive(Serialize)]
+struct TemplateFileResponse {
+    source_path: String,
+    save_path: Option<String>,
+    read_only_source: bool,
+    contents: String,
+    ends_with_newline: bool,
+    encoding: String,
+}
+
+#[tauri::command]
+fn open_ntr_as_template(
+    settings: tauri::State<SettingsState>,
+    path: String,
+) -> Result<TemplateFileResponse, LoadError> {
+    let resolved = &resolve_input_path(&path);
+    let response = load_ntr_path(resolved, read_options_for(&settings, resolved))?;
+    Ok(TemplateFileResponse {
+        source_path: response.path,
+        save_path: None,
+        read_only_source: true,
+        contents: response.contents,
+        ends_with_newline: response.ends_with_newline,
+        encoding: response.encoding,
     })
 }
 
+/// Loads `path` with an explicit encoding and remembers the choice, so later loads and watch
+/// reloads of the same file skip auto-detection.
 #[tauri::command]
-fn start_file_watch(
-    app: tauri::AppHandle,
-    state: tauri::State<WatcherState>,
+fn load_ntr_file_with_encoding(
+    settings: tauri::State<SettingsState>,
     path: String,
-) -> Result<(), String> {
-    log_watch_event(&format!("Starting watch for {}", path));
-    let input_path = PathBuf::from(&path);
-    if !input_path.exists() {
-        return Err("File not found".into());
+    encoding: String,
+    strip_bom_chars: Option<bool>,
+    tab_width: Option<usize>,
+) -> Result<OpenFileResponse, LoadError> {
+    let resolved = &resolve_input_path(&path);
+    let encoding = parse_encoding_label(&encoding)
+        .map_err(|message| LoadError::new(LoadErrorKind::Invalid, message))?;
+    let options = ReadOptions {
+        strip_bom_chars: strip_bom_chars.unwrap_or(false),
+        tab_width,
+        encoding: Some(encoding),
+        ..ReadOptions::default()
+    };
+    let response = load_ntr_path(resolved, options)?;
+    settings
+        .update(|settings| {
+            settings
+                .file_encodings
+                .insert(response.path.clone(), encoding.name().to_string())
+        })
+        .map_err(LoadError::io)?;
+    Ok(response)
+}
+
+/// A file's bytes converted to another encoding, for callers that need the raw bytes rather
+/// than the decoded text.
+#[derive(Serialize)]
+struct TranscodedFile {
+    path: String,
+    source_encoding: String,
+    target_encoding: String,
+    /// The re-encoded bytes, base64-encoded.
+    data: String,
+    /// True when the target encoding could not represent some characters; those were written as
+    /// HTML numeric character references (`&#NNNN;`), as `encoding_rs` does.
+    had_unmappable: bool,
+}
+
+/// Encodes `contents` in `encoding`, substituting for unmappable characters instead of
+/// failing like `encode_contents`. UTF-16 is encoded by hand since `encoding_rs` only decodes
+/// it. Returns the bytes and whether any substitution happened.
+fn transcode_contents(
+    contents: &str,
+    encoding: &'static Encoding,
+) -> Result<(Vec<u8>, bool), String> {
+    if encoding == UTF_16LE {
+        return Ok((
+            contents.encode_utf16().flat_map(u16::to_le_bytes).collect(),
+            false,
+        ));
     }
-    if !input_path.is_file() {
-        return Err("Path is not a file".into());
+    if encoding == UTF_16BE {
+        return Ok((
+            contents.encode_utf16().flat_map(u16::to_be_bytes).collect(),
+            false,
+        ));
+    }
+    let (bytes, used, had_unmappable) = encoding.encode(contents);
+    if used != encoding {
+        return Err(format!("Writing {} is not supported", encoding.name()));
     }
+    Ok((bytes.into_owned(), had_unmappable))
+}
 
-    let canonical_path = input_path
-        .canonicalize()
-        .unwrap_or_else(|_| input_path.clone());
-    let normalized_path = Arc::new(normalize_path(&canonical_path));
-    let emit_path_for_watch = normalized_path.clone();
-    let app_handle = app.clone();
+/// Decodes `path` as a normal load would (remembered encoding or auto-detection) and
+/// re-encodes the text in `target_encoding`. The file itself is left untouched.
+#[tauri::command(async)]
+fn load_ntr_transcoded(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    target_encoding: String,
+) -> Result<TranscodedFile, String> {
+    let target = parse_encoding_label(&target_encoding)?;
+    let resolved = resolve_input_path(&path);
+    let decoded = read_decoded_text(&settings, &resolved)?;
+    let (bytes, had_unmappable) = transcode_contents(&decoded.contents, target)?;
+    Ok(TranscodedFile {
+        path: normalize_path(&resolved),
+        source_encoding: decoded.encoding.name().to_string(),
+        target_encoding: target.name().to_string(),
+        data: base64::engine::general_purpose::STANDARD.encode(bytes),
+        had_unmappable,
+    })
+}
+
+#[tauri::command]
+fn forget_file_encoding(
+    settings: tauri::State<SettingsState>,
+    path: String,
+) -> Result<bool, String> {
+    let key = normalize_path(&resolve_absolute_path(&resolve_input_path(&path)));
+    settings.update(|settings| settings.file_encodings.remove(&key).is_some())
+}
+
+fn remembered_encoding(settings: &SettingsState, path: &Path) -> Option<&'static Encoding> {
+    let key = normalize_path(&resolve_absolute_path(path));
+    settings.read(|settings| {
+        settings
+            .file_encodings
+            .get(&key)
+            .and_then(|label| Encoding::for_label(label.as_bytes()))
+    })
+}
+
+/// Read options for watch-driven reloads, honouring the remembered per-file encoding.
+fn watch_read_options(app: &tauri::AppHandle, path: &Path) -> ReadOptions {
+    read_options_for(&app.state::<SettingsState>(), path)
+}
+
+#[derive(Clone, Serialize)]
+struct EncodingWarningPayload {
+    path: String,
+    #[serde(flatten)]
+    attempt: DecodeAttempt,
+}
+
+#[derive(Clone, Serialize)]
+struct EncodingChangedPayload {
+    path: String,
+    previous: String,
+    encoding: String,
+}
+
+/// Leading bytes a watch reads to re-check the encoding after a change.
+const WATCH_ENCODING_SAMPLE_BYTES: u64 = 64 * 1024;
+
+/// Up to `WATCH_ENCODING_SAMPLE_BYTES` from the start of the file. A full sample is cut after
+/// its last complete line (or UTF-16 code point) so a character split by the cut is not taken
+/// for a malformed one.
+fn read_encoding_sample(path: &Path, encoding: Option<&'static Encoding>) -> Option<Vec<u8>> {
+    let mut sample = Vec::new();
+    File::open(path)
+        .and_then(|file| {
+            file.take(WATCH_ENCODING_SAMPLE_BYTES)
+                .read_to_end(&mut sample)
+        })
+        .ok()?;
+    if (sample.len() as u64) < WATCH_ENCODING_SAMPLE_BYTES {
+        return Some(sample);
+    }
+    let encoding = encoding.or(Encoding::for_bom(&sample).map(|(encoding, _)| encoding));
+    if encoding == Some(UTF_16LE) || encoding == Some(UTF_16BE) {
+        let last = &sample[sample.len() - 2..];
+        let high = if encoding == Some(UTF_16LE) {
+            last[1]
+        } else {
+            last[0]
+        };
+        if (0xD8..0xDC).contains(&high) {
+            sample.truncate(sample.len() - 2);
+        }
+    } else if let Some(newline) = sample.iter().rposition(|&byte| byte == b'\n') {
+        sample.truncate(newline + 1);
+    }
+    Some(sample)
+}
+
+/// Checks that appends to the file can be split into lines without decoding it first, judging
+/// by its leading bytes, and returns the length appends are counted from.
+pub(crate) fn append_watch_start(app: &tauri::AppHandle, path: &Path) -> Result<u64, String> {
+    let len = std::fs::metadata(path)
+        .map_err(|err| format!("Failed to read file metadata: {err}"))?
+        .len();
+    let options = watch_read_options(app, path);
+    let sample = read_encoding_sample(path, options.encoding)
+        .ok_or_else(|| "Failed to read file bytes".to_string())?;
+    ensure_byte_lines(&options, &sample, "Watching appends to")?;
+    Ok(len)
+}
+
+/// The encoding a watch starts out with for `last_detected`: `None` when one is remembered or
+/// declared by a sidecar, since `check_watched_encoding` does not re-detect those.
+fn initial_watch_encoding(app: &tauri::AppHandle, file_path: &Path) -> Option<&'static Encoding> {
+    let options = watch_read_options(app, file_path);
+    if options.encoding.is_some() {
+        return None;
+    }
+    let sample = read_encoding_sample(file_path, None)?;
+    detect_encoding(&sample, options.fallback_chain())
+}
+
+/// Runs after watch reloads, off the notify thread. Files with a remembered or sidecar encoding
+/// keep it (see `warn_on_remembered_errors`); others are re-detected from a leading sample, and
+/// when the result differs from `last_detected` an `ntr-encoding-changed` event is emitted and
+/// the new name returned.
+fn check_watched_encoding(
+    app: &tauri::AppHandle,
+    file_path: &Path,
+    emit_path: &str,
+    last_detected: &Mutex<Option<&'static Encoding>>,
+) -> Option<String> {
+    let options = watch_read_options(app, file_path);
+    let sample = read_encoding_sample(file_path, options.encoding)?;
+    if let Some(encoding) = options.encoding {
+        warn_on_remembered_errors(app, &sample, encoding, emit_path);
+        return None;
+    }
+
+    let detected = detect_encoding(&sample, options.fallback_chain())?;
+    let previous = last_detected
+        .lock()
+        .expect("detected encoding poisoned")
+        .replace(detected)
+        .filter(|&previous| previous != detected)?;
+    log_watch_event(&format!(
+        "Encoding of {emit_path} changed from {} to {}",
+        previous.name(),
+        detected.name()
+    ));
+    let payload = EncodingChangedPayload {
+        path: emit_path.to_string(),
+        previous: previous.name().to_string(),
+        encoding: detected.name().to_string(),
+    };
+    if let Err(err) = app.emit("ntr-encoding-changed", payload) {
+        log::error!("Failed to emit encoding changed event: {err}");
+    }
+    Some(detected.name().to_string())
+}
+
+/// Reloads keep using a remembered encoding rather than re-detecting, so a file that stops
+/// decoding cleanly under it would only surface as a failed reload. Emits
+/// `ntr-file-encoding-warning` in that case so the UI can suggest picking another encoding.
+fn warn_on_remembered_errors(
+    app: &tauri::AppHandle,
+    bytes: &[u8],
+    encoding: &'static Encoding,
+    emit_path: &str,
+) {
+    let bom_len = bom_len_for(encoding, bytes);
+    let attempt = scan_decode_errors(encoding, &bytes[bom_len..]);
+    if !attempt.had_errors {
+        return;
+    }
+    log_watch_event(&format!(
+        "Remembered encoding {} produced {} decode errors for {emit_path}",
+        attempt.encoding, attempt.error_count
+    ));
+    let payload = EncodingWarningPayload {
+        path: emit_path.to_string(),
+        attempt,
+    };
+    if let Err(err) = app.emit("ntr-file-encoding-warning", payload) {
+        log::error!("Failed to emit encoding warning event: {err}");
+    }
+}
+
+fn parse_encoding_label(label: &str) -> Result<&'static Encoding, String> {
+    Encoding::for_label(label.trim().as_bytes()).ok_or_else(|| format!("Unknown encoding: {label}"))
+}
+
+/// Longest wait `is_file_settled` accepts, so a bad argument cannot park a worker for long.
+const MAX_SETTLE_WAIT: Duration = Duration::from_secs(30);
+
+/// Samples the file's size and modification time twice, `quiet_ms` apart, and returns true
+/// when neither changed. Runs off the main thread because it sleeps between samples.
+#[tauri::command(async)]
+fn is_file_settled(path: String, quiet_ms: u64) -> Result<bool, String> {
+    let resolved = resolve_input_path(&path);
+    let sample = || {
+        std::fs::metadata(&resolved)
+            .map(|metadata| (metadata.len(), metadata.modified().ok()))
+            .map_err(|err| format!("Failed to read file metadata: {err}"))
+    };
+    let before = sample()?;
+    thread::sleep(Duration::from_millis(quiet_ms).min(MAX_SETTLE_WAIT));
+    Ok(sample()? == before)
+}
+
+/// Loads `path` on a worker thread and gives up after `timeout_ms`. A timed-out read cannot be
+/// interrupted, so the worker may still finish in the background; its result is discarded.
+#[tauri::command]
+fn load_ntr_file_timeout(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    timeout_ms: u64,
+) -> Result<OpenFileResponse, LoadError> {
+    let resolved = resolve_input_path(&path);
+    let options = read_options_for(&settings, &resolved);
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = sender.send(load_ntr_path(&resolved, options));
+    });
+
+    match receiver.recv_timeout(Duration::from_millis(timeout_ms)) {
+        Ok(result) => result,
+        Err(mpsc::RecvTimeoutError::Timeout) => Err(LoadError::new(
+            LoadErrorKind::Timeout,
+            format!("Timed out reading file after {timeout_ms} ms"),
+        )),
+        Err(mpsc::RecvTimeoutError::Disconnected) => {
+            Err(LoadError::io("File read worker exited unexpectedly"))
+        }
+    }
+}
+
+#[tauri::command]
+fn open_latest_ntr(
+    settings: tauri::State<SettingsState>,
+    dir: String,
+) -> Result<OpenFileResponse, LoadError> {
+    let latest = find_latest_ntr(&resolve_input_path(&dir))
+        .map_err(LoadError::io)?
+        .ok_or_else(|| LoadError::io(format!("No NTR files found in {dir}")))?;
+    let options = read_options_for(&settings, &latest);
+    read_ntr_file(&latest, options)
+}
+
+fn is_ntr_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("ntr"))
+}
+
+fn find_latest_ntr(dir: &Path) -> Result<Option<PathBuf>, String> {
+    let entries =
+        std::fs::read_dir(dir).map_err(|err| format!("Failed to read directory: {err}"))?;
+    let latest = entries
+        .filter_map(Result::ok)
+        .filter(|entry| is_ntr_file(&entry.path()))
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            let modified = metadata.modified().ok()?;
+            metadata.is_file().then(|| (modified, entry.path()))
+        })
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path);
+    Ok(latest)
+}
+
+/// Orders names the way people read them: runs of digits compare by value, so "RO 2" sorts
+/// before "RO 10", and letters compare case-insensitively.
+fn natural_cmp(a: &str, b: &str) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+
+    let digit_run = |text: &str| {
+        text.find(|ch: char| !ch.is_ascii_digit())
+            .unwrap_or(text.len())
+    };
+    let (mut a, mut b) = (a, b);
+    loop {
+        let ordering = match (a.chars().next(), b.chars().next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let (a_len, b_len) = (digit_run(a), digit_run(b));
+                let a_number = a[..a_len].trim_start_matches('0');
+                let b_number = b[..b_len].trim_start_matches('0');
+                a = &a[a_len..];
+                b = &b[b_len..];
+                a_number
+                    .len()
+                    .cmp(&b_number.len())
+                    .then_with(|| a_number.cmp(b_number))
+            }
+            (Some(x), Some(y)) => {
+                a = &a[x.len_utf8()..];
+                b = &b[y.len_utf8()..];
+                x.to_lowercase().cmp(y.to_lowercase())
+            }
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
 
+/// Dot files everywhere, plus entries with the hidden attribute on Windows.
+fn is_hidden(name: &str, metadata: &std::fs::Metadata) -> bool {
+    #[cfg(windows)]
     {
-        let mut guard = state.inner.lock().expect("watcher state poisoned");
-        guard.take();
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+        if metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0 {
+            return true;
+        }
+    }
+    #[cfg(not(windows))]
+    let _ = metadata;
+    name.starts_with('.')
+}
+
+#[derive(Serialize)]
+struct DirectoryEntry {
+    name: String,
+    path: String,
+    is_dir: bool,
+    is_ntr: bool,
+    /// File size in bytes; `None` for directories.
+    size: Option<u64>,
+    /// Last modification as milliseconds since the Unix epoch, when the platform reports it.
+    modified_ms: Option<u64>,
+}
+
+/// Lists `dir` for the built-in file browser: directories first, then files, each in natural
+/// order. Hidden entries are left out unless `show_hidden` is set; symlinks are described by
+/// their target, and entries whose metadata cannot be read are skipped.
+#[tauri::command]
+fn list_directory(dir: String, show_hidden: Option<bool>) -> Result<Vec<DirectoryEntry>, String> {
+    let show_hidden = show_hidden.unwrap_or(false);
+    let entries = std::fs::read_dir(resolve_input_path(&dir))
+        .map_err(|err| format!("Failed to read directory: {err}"))?;
+    let mut listing: Vec<DirectoryEntry> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let path = entry.path();
+            let metadata = std::fs::metadata(&path)
+                .or_else(|_| entry.metadata())
+                .ok()?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !show_hidden && is_hidden(&name, &metadata) {
+                return None;
+            }
+            let is_dir = metadata.is_dir();
+            Some(DirectoryEntry {
+                is_ntr: !is_dir && is_ntr_file(&path),
+                size: (!is_dir).then_some(metadata.len()),
+                modified_ms: metadata
+                    .modified()
+                    .ok()
+                    .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                    .map(|elapsed| elapsed.as_millis() as u64),
+                path: normalize_path(&path),
+                name,
+                is_dir,
+            })
+        })
+        .collect();
+    listing.sort_by(|a, b| {
+        b.is_dir
+            .cmp(&a.is_dir)
+            .then_with(|| natural_cmp(&a.name, &b.name))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    Ok(listing)
+}
+
+/// Reads and decodes `path` for analysis commands with the encoding `read_options_for` picks.
+fn read_decoded(settings: &SettingsState, path: &Path) -> Result<String, String> {
+    read_decoded_text(settings, path).map(|decoded| decoded.contents)
+}
+
+/// Like `read_decoded`, but keeps the encoding and BOM so results can be written back in the
+/// source encoding.
+fn read_decoded_text(settings: &SettingsState, path: &Path) -> Result<DecodedText, String> {
+    if !path.is_file() {
+        return Err("Path does not point to a file".into());
+    }
+    let bytes = std::fs::read(path).map_err(|err| format!("Failed to read file bytes: {err}"))?;
+    Ok(decode_bytes(&bytes, &read_options_for(settings, path))?)
+}
+
+/// The line ending to use when rewriting `contents`: CRLF if the text uses it, LF otherwise.
+fn detect_line_ending(contents: &str) -> &'static str {
+    if contents.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    }
+}
+
+fn load_ntr_path(path: &Path, options: ReadOptions) -> Result<OpenFileResponse, LoadError> {
+    if cfg!(windows) {
+        if let Some(problem) = windows_path_problem(path) {
+            let message = format!("Invalid Windows path: {problem}");
+            return Err(LoadError::new(LoadErrorKind::Invalid, message));
+        }
     }
+    if !path.exists() {
+        log::warn!("Load failed, file not found: {}", path.display());
+        return Err(LoadError::io("File not found"));
+    }
+    if !path.is_file() {
+        log::warn!("Load failed, not a file: {}", path.display());
+        return Err(LoadError::io("Path does not point to a file"));
+    }
+    read_ntr_file(path, options)
+}
+
+fn read_ntr_file(path: &Path, options: ReadOptions) -> Result<OpenFileResponse, LoadError> {
+    if options.tab_width == Some(0) {
+        let message = "Tab width must be greater than zero";
+        return Err(LoadError::new(LoadErrorKind::Invalid, message));
+    }
+    let bytes = std::fs::read(path).map_err(|err| {
+        log::error!("Failed to read {}: {err}", path.display());
+        LoadError::io(format!("Failed to read file bytes: {err}"))
+    })?;
+    let encoding_source = match options.encoding {
+        None => EncodingSource::Detected,
+        Some(_) if options.from_sidecar => EncodingSource::Sidecar,
+        Some(_) => EncodingSource::Override,
+    };
+    let decoded = decode_bytes(&bytes, &options).inspect_err(|diagnostics| {
+        log::warn!(
+            "Failed to decode {}: {}",
+            path.display(),
+            diagnostics.message
+        );
+    })?;
+    log::info!(
+        "Loaded {} ({} bytes, {})",
+        path.display(),
+        bytes.len(),
+        decoded.encoding.name()
+    );
+    let ends_with_newline = ends_with_newline(&bytes, decoded.encoding);
+    let mut contents = decoded.contents;
+    let line_ending = transform::describe_line_endings(&contents);
+    let stripped_bom_count = if options.strip_bom_chars {
+        strip_bom_chars(&mut contents)
+    } else {
+        0
+    };
+    let tabs_expanded = match options.tab_width {
+        Some(width) if contents.contains('\t') => {
+            contents = expand_tabs(&contents, width);
+            true
+        }
+        _ => false,
+    };
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    Ok(OpenFileResponse {
+        path: normalize_path(&canonical),
+        contents,
+        stripped_bom_count,
+        tabs_expanded,
+        ends_with_newline,
+        encoding: decoded.encoding.name().to_string(),
+        encoding_source,
+        had_bom: decoded.had_bom,
+        line_ending,
+        ascii_only: decoded.encoding == UTF_8 && bytes.is_ascii(),
+    })
+}
+
+/// `<file>.encoding` next to `path`, e.g. `export.ntr.encoding`.
+fn sidecar_path(path: &Path) -> PathBuf {
+    let mut sidecar = path.as_os_str().to_owned();
+    sidecar.push(".encoding");
+    PathBuf::from(sidecar)
+}
+
+/// Reads the encoding label from the sidecar; see `sidecar_path`. A missing sidecar or an
+/// unknown label yields `None` so the caller falls back to auto-detection.
+fn sidecar_encoding(path: &Path) -> Option<&'static Encoding> {
+    let label = std::fs::read_to_string(sidecar_path(path)).ok()?;
+    match parse_encoding_label(&label) {
+        Ok(encoding) => Some(encoding),
+        Err(err) => {
+            log::warn!("Ignoring encoding sidecar for {}: {err}", path.display());
+            None
+        }
+    }
+}
+
+const WINDOWS_RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+const WINDOWS_INVALID_CHARS: &[char] = &['<', '>', ':', '"', '|', '?', '*'];
+
+/// Describes why Windows would reject `path`: a reserved device name as any component (with
+/// or without an extension) or a character that is illegal in file names. Drive and UNC
+/// prefixes are not checked, so `C:` is fine.
+fn windows_path_problem(path: &Path) -> Option<String> {
+    path.components().find_map(|component| match component {
+        std::path::Component::Normal(name) => windows_name_problem(&name.to_string_lossy()),
+        _ => None,
+    })
+}
+
+fn windows_name_problem(name: &str) -> Option<String> {
+    if let Some(invalid) = name
+        .chars()
+        .find(|&ch| WINDOWS_INVALID_CHARS.contains(&ch) || ch.is_control())
+    {
+        return Some(format!("illegal character {invalid:?} in {name}"));
+    }
+    let stem = name.split('.').next().unwrap_or_default().trim_end();
+    WINDOWS_RESERVED_NAMES
+        .iter()
+        .find(|reserved| stem.eq_ignore_ascii_case(reserved))
+        .map(|reserved| format!("reserved name {reserved}"))
+}
+
+/// Longest file name most file systems accept: 255 bytes on Unix, 255 UTF-16 units on Windows.
+const MAX_FILE_NAME_LEN: usize = 255;
+/// Characters allowed in a Windows path without the `\\?\` prefix (`MAX_PATH` is 260
+/// including the terminating NUL).
+const WINDOWS_MAX_PATH: usize = 259;
+const WINDOWS_MAX_EXTENDED_PATH: usize = 32_767;
+/// `PATH_MAX` on Linux, also an upper bound on macOS.
+const UNIX_MAX_PATH: usize = 4096;
+
+/// Every reason the platform (Windows when `windows` is set, Unix otherwise) would refuse to
+/// create `path`. Lengths are measured on the absolute path, in the units the platform counts.
+fn save_path_problems(path: &Path, windows: bool) -> Vec<String> {
+    let text = path.to_string_lossy();
+    let length = |text: &str| {
+        if windows {
+            text.encode_utf16().count()
+        } else {
+            text.len()
+        }
+    };
+    let mut problems = Vec::new();
+    match path.file_name().map(|name| name.to_string_lossy()) {
+        None => problems.push("path has no file name".to_string()),
+        Some(name) => {
+            if length(&name) > MAX_FILE_NAME_LEN {
+                problems.push(format!(
+                    "file name is {} long; the limit is {MAX_FILE_NAME_LEN}",
+                    length(&name)
+                ));
+            }
+            if windows && name.ends_with(['.', ' ']) {
+                problems.push(format!("file name {name:?} ends with a dot or space"));
+            }
+        }
+    }
+
+    let extended = text.starts_with(r"\\?\");
+    let max_path = match (windows, extended) {
+        (true, false) => WINDOWS_MAX_PATH,
+        (true, true) => WINDOWS_MAX_EXTENDED_PATH,
+        (false, _) => UNIX_MAX_PATH,
+    };
+    if length(&text) > max_path {
+        problems.push(format!(
+            "path is {} long; the limit is {max_path}{}",
+            length(&text),
+            if windows && !extended {
+                " without the \\\\?\\ prefix"
+            } else {
+                ""
+            }
+        ));
+    }
+
+    for component in path.components() {
+        let std::path::Component::Normal(name) = component else {
+            continue;
+        };
+        let name = name.to_string_lossy();
+        if windows {
+            problems.extend(windows_name_problem(&name));
+        } else if name.contains('\0') {
+            problems.push(format!("illegal character '\\0' in {name}"));
+        }
+    }
+    problems
+}
+
+#[derive(Serialize)]
+struct SavePathValidation {
+    ok: bool,
+    problems: Vec<String>,
+}
+
+/// Checks a "save as" target against this platform's name and length limits before anything is
+/// written, so the dialog can warn instead of surfacing a cryptic OS error. The file does not
+/// need to exist.
+#[tauri::command]
+fn validate_save_path(path: String) -> SavePathValidation {
+    let absolute = resolve_absolute_path(&resolve_input_path(&path));
+    let problems = save_path_problems(&absolute, cfg!(windows));
+    SavePathValidation {
+        ok: problems.is_empty(),
+        problems,
+    }
+}
+
+/// Checks the raw bytes rather than the decoded text; UTF-16 is checked by its last code unit.
+fn ends_with_newline(bytes: &[u8], encoding: &'static Encoding) -> bool {
+    if encoding == UTF_16LE {
+        bytes.ends_with(&[b'\n', 0])
+    } else if encoding == UTF_16BE {
+        bytes.ends_with(&[0, b'\n'])
+    } else {
+        bytes.last() == Some(&b'\n')
+    }
+}
+
+/// Encodes `contents` for writing, failing instead of letting `encoding_rs` substitute numeric
+/// character references for characters the target cannot represent.
+fn encode_contents(contents: &str, encoding: &'static Encoding) -> Result<Vec<u8>, String> {
+    let (bytes, used, had_unmappable) = encoding.encode(contents);
+    if used != encoding {
+        return Err(format!("Writing {} is not supported", encoding.name()));
+    }
+    if had_unmappable {
+        return Err(format!(
+            "Contents contain characters that cannot be represented in {}",
+            encoding.name()
+        ));
+    }
+    Ok(bytes.into_owned())
+}
+
+#[derive(Serialize)]
+struct DiskSpace {
+    available_bytes: u64,
+    total_bytes: u64,
+}
+
+/// Space on the volume holding `path`, queried through its nearest existing ancestor so the
+/// target of a save does not have to exist yet.
+fn disk_space(path: &Path) -> std::io::Result<DiskSpace> {
+    let absolute = resolve_absolute_path(path);
+    let dir = absolute
+        .ancestors()
+        .find(|ancestor| ancestor.is_dir())
+        .unwrap_or(&absolute);
+    volume_space(dir)
+}
+
+#[cfg(unix)]
+fn volume_space(dir: &Path) -> std::io::Result<DiskSpace> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let c_path = CString::new(dir.as_os_str().as_bytes())
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+    // SAFETY: `c_path` is NUL-terminated and `stat` is a plain C struct statvfs fills in.
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // The field widths differ between platforms, so the casts are only redundant on some.
+    #[allow(clippy::unnecessary_cast)]
+    let fragment = stat.f_frsize as u64;
+    #[allow(clippy::unnecessary_cast)]
+    Ok(DiskSpace {
+        available_bytes: (stat.f_bavail as u64).saturating_mul(fragment),
+        total_bytes: (stat.f_blocks as u64).saturating_mul(fragment),
+    })
+}
+
+#[cfg(windows)]
+fn volume_space(dir: &Path) -> std::io::Result<DiskSpace> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let wide: Vec<u16> = dir
+        .as_os_str()
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    let (mut available_bytes, mut total_bytes) = (0u64, 0u64);
+    // SAFETY: `wide` is NUL-terminated and both out-pointers refer to live locals.
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(
+            wide.as_ptr(),
+            &mut available_bytes,
+            &mut total_bytes,
+            std::ptr::null_mut(),
+        )
+    };
+    if ok == 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(DiskSpace {
+        available_bytes,
+        total_bytes,
+    })
+}
+
+#[cfg(not(any(unix, windows)))]
+fn volume_space(_dir: &Path) -> std::io::Result<DiskSpace> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+/// Free and total space of the volume a file at `path` would be written to.
+#[tauri::command]
+fn disk_space_for(path: String) -> Result<DiskSpace, String> {
+    disk_space(&resolve_input_path(&path))
+        .map_err(|err| format!("Failed to query disk space: {err}"))
+}
+
+/// Fails when the volume holding `path` has less than `needed` bytes free. When the space
+/// cannot be queried the write is attempted anyway.
+fn ensure_disk_space(path: &Path, needed: u64) -> Result<(), String> {
+    match disk_space(path) {
+        Ok(space) if space.available_bytes < needed => Err(format!(
+            "Not enough disk space: {needed} bytes needed, {} available",
+            space.available_bytes
+        )),
+        Ok(_) => Ok(()),
+        Err(err) => {
+            log::warn!("Could not check disk space for {}: {err}", path.display());
+            Ok(())
+        }
+    }
+}
+
+/// Writes to a sibling temp file and renames it over `path` so readers never observe a
+/// partially written file. Fails up front if the volume cannot hold `bytes`, rather than
+/// leaving a truncated temp file behind.
+fn write_atomic(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| format!("Invalid output path: {}", path.display()))?;
+    ensure_disk_space(path, bytes.len() as u64)?;
+    let mut temp_name = file_name.to_os_string();
+    temp_name.push(format!(".tmp-{}", std::process::id()));
+    let temp_path = path.with_file_name(temp_name);
+    std::fs::write(&temp_path, bytes)
+        .map_err(|err| format!("Failed to write {}: {err}", temp_path.display()))?;
+    std::fs::rename(&temp_path, path).map_err(|err| {
+        let _ = std::fs::remove_file(&temp_path);
+        format!("Failed to replace {}: {err}", path.display())
+    })
+}
+
+fn strip_bom_chars(contents: &mut String) -> usize {
+    let count = contents.matches('\u{feff}').count();
+    if count > 0 {
+        contents.retain(|ch| ch != '\u{feff}');
+    }
+    count
+}
+
+/// Replaces each tab with spaces up to the next multiple of `width` characters; the column
+/// restarts after every `\n` or `\r`.
+fn expand_tabs(contents: &str, width: usize) -> String {
+    let mut expanded = String::with_capacity(contents.len());
+    let mut column = 0;
+    for ch in contents.chars() {
+        match ch {
+            '\t' => {
+                let spaces = width - column % width;
+                expanded.extend(std::iter::repeat_n(' ', spaces));
+                column += spaces;
+            }
+            '\n' | '\r' => {
+                expanded.push(ch);
+                column = 0;
+            }
+            _ => {
+                expanded.push(ch);
+                column += 1;
+            }
+        }
+    }
+    expanded
+}
+
+/// Streaming commands split on raw `\n` bytes, which is only sound for ASCII-compatible
+/// encodings. Fails for UTF-16 files, judged by the chosen encoding or by what detection picks
+/// for `leading`, so BOM-less UTF-16 from the fallback chain is caught too.
+fn ensure_byte_lines(options: &ReadOptions, leading: &[u8], action: &str) -> Result<(), String> {
+    let encoding = options
+        .encoding
+        .or_else(|| detect_encoding(leading, options.fallback_chain()));
+    ensure_byte_encoding(encoding, action)
+}
+
+fn ensure_byte_encoding(encoding: Option<&'static Encoding>, action: &str) -> Result<(), String> {
+    match encoding.filter(|&encoding| encoding == UTF_16LE || encoding == UTF_16BE) {
+        Some(encoding) => Err(format!(
+            "{action} {} files is not supported",
+            encoding.name()
+        )),
+        None => Ok(()),
+    }
+}
+
+/// The one encoding a line-by-line command decodes `path` with: the chosen one, otherwise what
+/// `detect_file_encoding` picks for the whole file, so a line that happens to be valid UTF-8
+/// is never decoded differently from the rest. Fails for UTF-16.
+fn line_encoding(
+    path: &Path,
+    options: &ReadOptions,
+    action: &str,
+) -> Result<&'static Encoding, String> {
+    let encoding = match options.encoding {
+        Some(encoding) => encoding,
+        None => detect_file_encoding(path, options.fallback_chain())?
+            .ok_or("File does not decode with any of the candidate encodings")?,
+    };
+    ensure_byte_encoding(Some(encoding), action)?;
+    Ok(encoding)
+}
+
+/// Lines of a file decoded one at a time with its `line_encoding`, without their line endings.
+/// The BOM is only dropped from the first line.
+struct DecodedLines {
+    reader: BufReader<File>,
+    encoding: &'static Encoding,
+    raw: Vec<u8>,
+    first: bool,
+    /// Whether the first line started with a BOM.
+    had_bom: bool,
+    /// Whether any line read so far ended with CRLF.
+    crlf: bool,
+}
+
+impl DecodedLines {
+    fn open(path: &Path, options: &ReadOptions, action: &str) -> Result<Self, String> {
+        let encoding = line_encoding(path, options, action)?;
+        let file = File::open(path).map_err(|err| format!("Failed to open file: {err}"))?;
+        Ok(DecodedLines {
+            reader: BufReader::new(file),
+            encoding,
+            raw: Vec::new(),
+            first: true,
+            had_bom: false,
+            crlf: false,
+        })
+    }
+}
+
+impl Iterator for DecodedLines {
+    type Item = Result<String, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.raw.clear();
+        match self.reader.read_until(b'\n', &mut self.raw) {
+            Ok(0) => None,
+            Ok(_) => {
+                let bom_len = if self.first {
+                    bom_len_for(self.encoding, &self.raw)
+                } else {
+                    0
+                };
+                self.had_bom |= bom_len > 0;
+                self.first = false;
+                self.crlf |= self.raw.ends_with(b"\r\n");
+                let line = self.raw[bom_len..]
+                    .strip_suffix(b"\n")
+                    .unwrap_or(&self.raw[bom_len..]);
+                let line = line.strip_suffix(b"\r").unwrap_or(line);
+                let (decoded, had_errors) = self.encoding.decode_without_bom_handling(line);
+                Some(if had_errors {
+                    Err(format!("File is not valid {}", self.encoding.name()))
+                } else {
+                    Ok(decoded.into_owned())
+                })
+            }
+            Err(err) => Some(Err(format!("Failed to read file: {err}"))),
+        }
+    }
+}
+
+#[tauri::command]
+fn start_file_watch(
+    app: tauri::AppHandle,
+    state: tauri::State<WatcherState>,
+    path: String,
+    options: Option<WatchOptions>,
+) -> Result<(), String> {
+    let WatchOptions {
+        diff,
+        ignore_kinds,
+        tag,
+        append,
+        snapshots,
+    } = options.unwrap_or_default();
+    log_watch_event(&format!("Starting watch for {}", path));
+    let ignored_kinds = parse_ignored_kinds(ignore_kinds)?;
+    let input_path = resolve_input_path(&path);
+    if !input_path.exists() {
+        return Err("File not found".into());
+    }
+    if !input_path.is_file() {
+        return Err("Path is not a file".into());
+    }
+
+    let canonical_path = resolve_absolute_path(&input_path);
+    let normalized_path = Arc::new(normalize_path(&canonical_path));
+    let emit_path_for_watch = normalized_path.clone();
+    let case_insensitive = probe_case_insensitive(&canonical_path);
+    log_watch_event(&format!(
+        "Watched volume is case-{}",
+        if case_insensitive {
+            "insensitive"
+        } else {
+            "sensitive"
+        }
+    ));
+    let match_path = Arc::new(normalize_path_cased(&canonical_path, case_insensitive));
+    let app_handle = app.clone();
+
+    {
+        let mut guard = state.inner.lock().expect("watcher state poisoned");
+        guard.take();
+    }
+
+    let diff_snapshot = diff.unwrap_or(false).then(|| {
+        let initial = read_ntr_file(&canonical_path, watch_read_options(&app, &canonical_path));
+        let encoding = initial
+            .as_ref()
+            .ok()
+            .and_then(|response| parse_encoding_label(&response.encoding).ok());
+        let hashes = initial
+            .ok()
+            .map(|response| response.contents)
+            .filter(|contents| contents.len() <= MAX_DIFF_SNAPSHOT_BYTES)
+            .map(|contents| line_hashes(&contents));
+        (Arc::new(Mutex::new(hashes)), encoding)
+    });
+    let append_sender = match append {
+        Some(options) => {
+            let filter = options
+                .emit_filter_regex
+                .as_deref()
+                .map(Regex::new)
+                .transpose()
+                .map_err(|err| format!("Invalid filter pattern: {err}"))?;
+            let start = append_watch_start(&app, &canonical_path)?;
+            let threshold = options.min_growth_bytes.map(|min_bytes| GrowthThreshold {
+                min_bytes,
+                flush_after: options
+                    .flush_after_ms
+                    .map_or(DEFAULT_APPEND_FLUSH_AFTER, Duration::from_millis),
+            });
+            Some(spawn_append_worker(
+                app.clone(),
+                canonical_path.clone(),
+                normalized_path.as_ref().clone(),
+                tag.clone(),
+                start,
+                filter,
+                threshold,
+            ))
+        }
+        None => None,
+    };
+    let snapshot_sender = snapshots
+        .map(|options| {
+            snapshots::spawn_snapshot_worker(
+                app.clone(),
+                canonical_path.clone(),
+                normalized_path.as_ref().clone(),
+                options,
+            )
+        })
+        .transpose()?;
+    let diff_sender = diff_snapshot.as_ref().map(|(snapshot, encoding)| {
+        spawn_diff_worker(
+            app.clone(),
+            canonical_path.clone(),
+            normalized_path.as_ref().clone(),
+            snapshot.clone(),
+            *encoding,
+        )
+    });
+
+    let watch_target = canonical_path
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| canonical_path.clone());
+    let normalized_parent = normalize_path_cased(&watch_target, case_insensitive);
+    let parent_lost = Arc::new(AtomicBool::new(false));
+    let parent_lost_for_watch = parent_lost.clone();
+
+    let file_path_for_match = match_path.clone();
+    let file_path_for_watch = canonical_path.clone();
+    let settling = Arc::new(AtomicBool::new(false));
+    let orphaned = Arc::new(AtomicBool::new(false));
+    let orphaned_for_watch = orphaned.clone();
+    let readable = readability(File::open(&canonical_path)).unwrap_or(true);
+    let readable = Arc::new(AtomicBool::new(readable));
+    let readable_for_watch = readable.clone();
+    let events = Arc::new(WatchEventCounts::default());
+    let events_for_watch = events.clone();
+    let last_detected = Arc::new(Mutex::new(initial_watch_encoding(&app, &canonical_path)));
+    let create_sender = spawn_create_coalescer(
+        app.clone(),
+        canonical_path.clone(),
+        normalized_path.as_ref().clone(),
+        tag.clone(),
+        settling.clone(),
+        last_detected.clone(),
+    );
+    let encoding_sender = spawn_encoding_worker(
+        app.clone(),
+        canonical_path.clone(),
+        normalized_path.as_ref().clone(),
+        last_detected,
+    );
+    let tag_for_watch = tag.clone();
+    let mut watcher = notify::recommended_watcher(move |res: Result<Event, notify::Error>| {
+        match res {
+            Ok(event) => {
+                events_for_watch.received.fetch_add(1, Ordering::Relaxed);
+                let renamed = matches!(event.kind, EventKind::Modify(ModifyKind::Name(_)));
+                let matched = paths_match(
+                    &event.paths,
+                    &file_path_for_match,
+                    case_insensitive,
+                    renamed,
+                );
+                if matched {
+                    events_for_watch.matched.fetch_add(1, Ordering::Relaxed);
+                    events_for_watch
+                        .last_matched_ms
+                        .store(now_ms(), Ordering::Relaxed);
+                }
+                if is_parent_loss_event(&event, &normalized_parent, case_insensitive) {
+                    emit_parent_lost(
+                        &app_handle,
+                        &emit_path_for_watch,
+                        tag_for_watch.as_deref(),
+                        &parent_lost_for_watch,
+                    );
+                    return;
+                }
+                // Checked for every kind but access, which our own open would trigger again,
+                // and remove, which leaves nothing to open.
+                if !matches!(event.kind, EventKind::Access(_) | EventKind::Remove(_))
+                    && matched
+                    && file_path_for_watch.exists()
+                {
+                    if let Some(now_readable) = readability(File::open(&file_path_for_watch)) {
+                        if readable_for_watch.swap(now_readable, Ordering::SeqCst) != now_readable {
+                            emit_readability_change(
+                                &app_handle,
+                                &emit_path_for_watch,
+                                tag_for_watch.clone(),
+                                now_readable,
+                            );
+                        }
+                    }
+                    if !readable_for_watch.load(Ordering::SeqCst) {
+                        return;
+                    }
+                }
+                if matched && should_emit_event(&event.kind, &ignored_kinds) {
+                    events_for_watch.emitted.fetch_add(1, Ordering::Relaxed);
+                    if watch_logging_enabled() {
+                        let paths: Vec<String> = event
+                            .paths
+                            .iter()
+                            .map(|path| normalize_path(path))
+                            .collect();
+                        log_watch_event(&format!(
+                            "Event {:?} for paths {:?}",
+                            event.kind, paths
+                        ));
+                    }
+
+                    if let Some(sender) = &diff_sender {
+                        let _ = sender.send(());
+                    }
+                    if let Some(sender) = &append_sender {
+                        let _ = sender.send(());
+                    }
+                    if let Some(sender) = &snapshot_sender {
+                        let _ = sender.send(());
+                    }
+                    // A remove can arrive after an atomic save already put the file back.
+                    let target_gone = matches!(
+                        event.kind,
+                        EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(_))
+                    ) && !file_path_for_watch.exists();
+                    if target_gone {
+                        if !orphaned_for_watch.swap(true, Ordering::SeqCst) {
+                            log_watch_event(&format!(
+                                "Watched file deleted: {emit_path_for_watch}"
+                            ));
+                            let payload = FileChangePayload {
+                                path: emit_path_for_watch.as_ref().clone(),
+                                kind: "deleted".into(),
+                                contents: None,
+                                encoding: None,
+                                tag: tag_for_watch.clone(),
+                            };
+                            if let Err(err) = app_handle.emit("ntr-file-deleted", payload) {
+                                log::error!("Failed to emit file deleted event: {err}");
+                            }
+                        }
+                    } else if file_path_for_watch.exists()
+                        && orphaned_for_watch.swap(false, Ordering::SeqCst)
+                    {
+                        log_watch_event(&format!("Watched file recreated: {emit_path_for_watch}"));
+                    }
+                    let coalesce = match event.kind {
+                        EventKind::Create(_) => {
+                            settling.store(true, Ordering::SeqCst);
+                            true
+                        }
+                        EventKind::Modify(_) => settling.load(Ordering::SeqCst),
+                        _ => false,
+                    };
+                    if coalesce {
+                        let _ = create_sender.send(());
+                        return;
+                    }
+
+                    let _ = encoding_sender.send(());
+                    let payload = FileChangePayload {
+                        path: emit_path_for_watch.as_ref().clone(),
+                        kind: format_event_kind(&event.kind),
+                        contents: None,
+                        encoding: None,
+                        tag: tag_for_watch.clone(),
+                    };
+                    if let Err(err) = app_handle.emit("ntr-file-changed", payload) {
+                        log::error!("Failed to emit file change event: {err}");
+                    }
+                }
+            }
+            Err(err) => {
+                log::warn!(target: "watch", "File watcher error: {err}");
+                log_watch_event(&format!("Watcher error: {err}"));
+                let _ = app_handle.emit(
+                    "ntr-file-watch-error",
+                    FileChangePayload {
+                        path: emit_path_for_watch.as_ref().clone(),
+                        kind: format!("error:{err}"),
+                        contents: None,
+                        encoding: None,
+                        tag: tag_for_watch.clone(),
+                    },
+                );
+            }
+        }
+    })
+    .map_err(|err| err.to_string())?;
+
+    watcher
+        .configure(Config::default())
+        .map_err(|err| err.to_string())?;
+    watcher
+        .watch(&watch_target, RecursiveMode::NonRecursive)
+        .map_err(|err| err.to_string())?;
+
+    let monitor_stop = Arc::new(AtomicBool::new(false));
+    spawn_parent_monitor(
+        app.clone(),
+        watch_target.clone(),
+        normalized_path.as_ref().clone(),
+        tag.clone(),
+        parent_lost,
+        monitor_stop.clone(),
+    );
+
+    let mut guard = state.inner.lock().expect("watcher state poisoned");
+    *guard = Some(ActiveWatcher {
+        watcher,
+        file_path: canonical_path,
+        watched_dir: watch_target,
+        normalized_path: match_path.as_ref().clone(),
+        case_insensitive,
+        _diff_snapshot: diff_snapshot.map(|(snapshot, _)| snapshot),
+        monitor_stop,
+        orphaned,
+        readable,
+        events,
+        tag,
+    });
+    Ok(())
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[derive(Serialize)]
+struct WatchDebugInfo {
+    /// The canonical path events are compared against.
+    file_path: String,
+    watched_dir: String,
+    /// `file_path` normalized with the volume's case sensitivity; an event matches when one of
+    /// its paths normalizes to exactly this.
+    match_target: String,
+    case_insensitive: bool,
+    orphaned: bool,
+    readable: bool,
+    tag: Option<String>,
+    events_received: u64,
+    events_matched: u64,
+    events_emitted: u64,
+    /// Milliseconds since the Unix epoch of the last matched event; `None` before the first.
+    last_matched_ms: Option<u64>,
+}
+
+/// Describes how the active watch is bound, for working out why changes to `path` are not
+/// reported: events that are received but never matched point at a path mismatch, matched but
+/// not emitted at the kind filter. Errors when `path` is not the watched file.
+#[tauri::command]
+fn watch_debug_info(
+    state: tauri::State<WatcherState>,
+    path: String,
+) -> Result<WatchDebugInfo, String> {
+    let resolved = resolve_absolute_path(&resolve_input_path(&path));
+    let guard = state.inner.lock().expect("watcher state poisoned");
+    let active = guard
+        .as_ref()
+        .filter(|active| {
+            normalize_path_cased(&resolved, active.case_insensitive) == active.normalized_path
+        })
+        .ok_or("Path is not being watched")?;
+    let last_matched_ms = active.events.last_matched_ms.load(Ordering::Relaxed);
+    Ok(WatchDebugInfo {
+        file_path: normalize_path(&active.file_path),
+        watched_dir: normalize_path(&active.watched_dir),
+        match_target: active.normalized_path.clone(),
+        case_insensitive: active.case_insensitive,
+        orphaned: active.orphaned.load(Ordering::SeqCst),
+        readable: active.readable.load(Ordering::SeqCst),
+        tag: active.tag.clone(),
+        events_received: active.events.received.load(Ordering::Relaxed),
+        events_matched: active.events.matched.load(Ordering::Relaxed),
+        events_emitted: active.events.emitted.load(Ordering::Relaxed),
+        last_matched_ms: (last_matched_ms != 0).then_some(last_matched_ms),
+    })
+}
+
+/// Whether an attempt to open the watched file shows it readable. Only a permission error
+/// counts as unreadable; anything else (a rename in progress, a sharing violation) is
+/// transient and yields `None`, leaving the current state alone.
+fn readability(opened: std::io::Result<File>) -> Option<bool> {
+    match opened {
+        Ok(_) => Some(true),
+        Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => Some(false),
+        Err(_) => None,
+    }
+}
+
+/// Emits `ntr-file-permission-lost` when the watched file can no longer be opened and
+/// `ntr-file-permission-restored` once it can again. Change events are held back while the
+/// file is unreadable, since reloading it would only fail.
+fn emit_readability_change(
+    app: &tauri::AppHandle,
+    path: &str,
+    tag: Option<String>,
+    readable: bool,
+) {
+    let (event, kind) = if readable {
+        ("ntr-file-permission-restored", "permission-restored")
+    } else {
+        ("ntr-file-permission-lost", "permission-lost")
+    };
+    log_watch_event(&format!("Watched file {kind}: {path}"));
+    let payload = FileChangePayload {
+        path: path.to_string(),
+        kind: kind.into(),
+        contents: None,
+        encoding: None,
+        tag,
+    };
+    if let Err(err) = app.emit(event, payload) {
+        log::error!("Failed to emit {event} event: {err}");
+    }
+}
+
+/// A remove or rename reported for the watched directory itself; after either, the
+/// non-recursive directory watch no longer delivers events for the target file.
+fn is_parent_loss_event(event: &Event, normalized_parent: &str, case_insensitive: bool) -> bool {
+    matches!(
+        event.kind,
+        EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(_))
+    ) && event
+        .paths
+        .iter()
+        .any(|path| normalize_path_cased(path, case_insensitive) == normalized_parent)
+}
+
+fn emit_parent_lost(
+    app: &tauri::AppHandle,
+    path: &str,
+    tag: Option<&str>,
+    parent_lost: &AtomicBool,
+) {
+    if parent_lost.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    log_watch_event(&format!("Parent directory lost for {path}"));
+    let payload = FileChangePayload {
+        path: path.to_string(),
+        kind: "parent-lost".into(),
+        contents: None,
+        encoding: None,
+        tag: tag.map(str::to_string),
+    };
+    if let Err(err) = app.emit("ntr-watch-parent-lost", payload) {
+        log::error!("Failed to emit parent lost event: {err}");
+    }
+}
+
+/// Polls the watched directory so its disappearance is noticed even when the backend reports
+/// nothing, and re-registers the directory watch once it reappears at the same path.
+fn spawn_parent_monitor(
+    app: tauri::AppHandle,
+    parent: PathBuf,
+    emit_path: String,
+    tag: Option<String>,
+    parent_lost: Arc<AtomicBool>,
+    stop: Arc<AtomicBool>,
+) {
+    thread::spawn(move || loop {
+        thread::sleep(PARENT_POLL_INTERVAL);
+        if stop.load(Ordering::Relaxed) {
+            return;
+        }
+        if !parent.is_dir() {
+            emit_parent_lost(&app, &emit_path, tag.as_deref(), &parent_lost);
+            continue;
+        }
+        if !parent_lost.load(Ordering::Relaxed) {
+            continue;
+        }
+
+        let state = app.state::<WatcherState>();
+        let mut guard = state.inner.lock().expect("watcher state poisoned");
+        if stop.load(Ordering::Relaxed) {
+            return;
+        }
+        let Some(active) = guard.as_mut() else {
+            return;
+        };
+        let _ = active.watcher.unwatch(&parent);
+        match active.watcher.watch(&parent, RecursiveMode::NonRecursive) {
+            Ok(()) => {
+                parent_lost.store(false, Ordering::Relaxed);
+                log_watch_event(&format!("Re-established watch for {emit_path}"));
+                let payload = FileChangePayload {
+                    path: emit_path.clone(),
+                    kind: "parent-restored".into(),
+                    contents: None,
+                    encoding: None,
+                    tag: tag.clone(),
+                };
+                if let Err(err) = app.emit("ntr-watch-parent-restored", payload) {
+                    log::error!("Failed to emit parent restored event: {err}");
+                }
+            }
+            Err(err) => log_watch_event(&format!("Failed to re-establish watch: {err}")),
+        }
+    });
+}
+
+/// Holds back a "create" until no further events have arrived for `CREATE_COALESCE_WINDOW`,
+/// then emits it once. `settling` is set by the watcher while a create is pending so it can
+/// route follow-up modifies here instead of emitting them.
+fn spawn_create_coalescer(
+    app: tauri::AppHandle,
+    file_path: PathBuf,
+    emit_path: String,
+    tag: Option<String>,
+    settling: Arc<AtomicBool>,
+    last_detected: Arc<Mutex<Option<&'static Encoding>>>,
+) -> mpsc::Sender<()> {
+    let (sender, receiver) = mpsc::channel::<()>();
+    thread::spawn(move || {
+        while receiver.recv().is_ok() {
+            loop {
+                match receiver.recv_timeout(CREATE_COALESCE_WINDOW) {
+                    Ok(()) => continue,
+                    Err(mpsc::RecvTimeoutError::Timeout) => break,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => return,
+                }
+            }
+
+            settling.store(false, Ordering::SeqCst);
+            log_watch_event(&format!("Created file settled: {emit_path}"));
+            let encoding = check_watched_encoding(&app, &file_path, &emit_path, &last_detected);
+            let payload = FileChangePayload {
+                path: emit_path.clone(),
+                kind: "create".into(),
+                contents: None,
+                encoding,
+                tag: tag.clone(),
+            };
+            if let Err(err) = app.emit("ntr-file-changed", payload) {
+                log::error!("Failed to emit file change event: {err}");
+            }
+        }
+    });
+    sender
+}
+
+/// Runs `check_watched_encoding` once the file has been quiet for `DIFF_DEBOUNCE` after a
+/// change, so bursts of writes are sampled once and never on the notify thread.
+fn spawn_encoding_worker(
+    app: tauri::AppHandle,
+    file_path: PathBuf,
+    emit_path: String,
+    last_detected: Arc<Mutex<Option<&'static Encoding>>>,
+) -> mpsc::Sender<()> {
+    let (sender, receiver) = mpsc::channel::<()>();
+    thread::spawn(move || {
+        while receiver.recv().is_ok() {
+            loop {
+                match receiver.recv_timeout(DIFF_DEBOUNCE) {
+                    Ok(()) => continue,
+                    Err(mpsc::RecvTimeoutError::Timeout) => break,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => return,
+                }
+            }
+            check_watched_encoding(&app, &file_path, &emit_path, &last_detected);
+        }
+    });
+    sender
+}
+
+/// Collects change notifications until the file has been quiet for `DIFF_DEBOUNCE`, then
+/// re-reads it and emits `ntr-file-diff` against the held snapshot. The worker exits once the
+/// watcher (and with it the sender) is dropped.
+fn spawn_diff_worker(
+    app: tauri::AppHandle,
+    file_path: PathBuf,
+    emit_path: String,
+    snapshot: Arc<Mutex<Option<Vec<u64>>>>,
+    mut encoding: Option<&'static Encoding>,
+) -> mpsc::Sender<()> {
+    let (sender, receiver) = mpsc::channel::<()>();
+    let read = move |app: &tauri::AppHandle, encoding: Option<&'static Encoding>| {
+        let mut options = watch_read_options(app, &file_path);
+        options.encoding = options.encoding.or(encoding);
+        read_ntr_file(&file_path, options)
+    };
+    thread::spawn(move || {
+        while receiver.recv().is_ok() {
+            loop {
+                match receiver.recv_timeout(DIFF_DEBOUNCE) {
+                    Ok(()) => continue,
+                    Err(mpsc::RecvTimeoutError::Timeout) => break,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => return,
+                }
+            }
+
+            // Reuse the encoding the watch started with; only a rewrite that no longer decodes
+            // with it is detected afresh.
+            let response = match read(&app, encoding) {
+                Err(err) if encoding.is_some() && matches!(err.kind, LoadErrorKind::Decode) => {
+                    read(&app, None)
+                }
+                response => response,
+            };
+            let contents = match response {
+                Ok(response) => {
+                    encoding = parse_encoding_label(&response.encoding).ok();
+                    response.contents
+                }
+                Err(err) => {
+                    log_watch_event(&format!("Diff reload failed: {}", err.message));
+                    continue;
+                }
+            };
+
+            let mut guard = snapshot.lock().expect("diff snapshot poisoned");
+            if contents.len() > MAX_DIFF_SNAPSHOT_BYTES {
+                log_watch_event("File exceeds diff snapshot limit; dropping snapshot");
+                guard.take();
+                continue;
+            }
+            let new_hashes = line_hashes(&contents);
+            if let Some(previous) = guard.as_deref() {
+                let hunks = diff_sequences(previous, &new_hashes);
+                if !hunks.is_empty() {
+                    let payload = FileDiffPayload {
+                        path: emit_path.clone(),
+                        added: hunks.iter().filter_map(DiffHunk::added).collect(),
+                        removed: hunks.iter().filter_map(DiffHunk::removed).collect(),
+                    };
+                    if let Err(err) = app.emit("ntr-file-diff", payload) {
+                        log::error!("Failed to emit file diff event: {err}");
+                    }
+                }
+            }
+            *guard = Some(new_hashes);
+        }
+    });
+    sender
+}
+
+/// Decodes the complete lines between `offset` and `len` and advances `offset` past them; a
+/// trailing partial line is left for the next call. Failures are logged and yield `None`.
+fn read_appended_lines(
+    options: &ReadOptions,
+    file_path: &Path,
+    offset: &mut u64,
+    len: u64,
+) -> Option<String> {
+    let mut appended = Vec::new();
+    let read = File::open(file_path).and_then(|mut file| {
+        file.seek(SeekFrom::Start(*offset))?;
+        file.take(len - *offset).read_to_end(&mut appended)
+    });
+    if let Err(err) = read {
+        log_watch_event(&format!("Append read failed: {err}"));
+        return None;
+    }
+    let complete = appended.iter().rposition(|&byte| byte == b'\n')?;
+    *offset += complete as u64 + 1;
+
+    match decode_bytes(&appended[..=complete], options) {
+        Ok(decoded) => Some(decoded.contents),
+        Err(err) => {
+            log_watch_event(&format!("Append decode failed: {}", err.message));
+            None
+        }
+    }
+}
+
+/// Reads what was appended past `offset` once the file has been quiet for `DIFF_DEBOUNCE` and
+/// emits the complete lines of it; a trailing partial line waits for the next change. A file
+/// that shrank is treated as rewritten, and reading restarts from its new end. With a
+/// `threshold`, growth below it is buffered until more arrives or the file stays quiet for
+/// the threshold's `flush_after`.
+fn spawn_append_worker(
+    app: tauri::AppHandle,
+    file_path: PathBuf,
+    emit_path: String,
+    tag: Option<String>,
+    offset: u64,
+    filter: Option<Regex>,
+    threshold: Option<GrowthThreshold>,
+) -> mpsc::Sender<()> {
+    let (sender, receiver) = mpsc::channel::<()>();
+    thread::spawn(move || {
+        follow_appends(
+            &receiver,
+            &file_path,
+            offset,
+            filter.as_ref(),
+            threshold.as_ref(),
+            || watch_read_options(&app, &file_path),
+            |contents| {
+                let payload = FileChangePayload {
+                    path: emit_path.clone(),
+                    kind: "append".into(),
+                    contents: Some(contents),
+                    encoding: None,
+                    tag: tag.clone(),
+                };
+                if let Err(err) = app.emit("ntr-file-appended", payload) {
+                    log::error!("Failed to emit file appended event: {err}");
+                }
+            },
+        );
+    });
+    sender
+}
+
+/// The loop behind `spawn_append_worker`; returns once the sender is dropped.
+fn follow_appends(
+    receiver: &mpsc::Receiver<()>,
+    file_path: &Path,
+    mut offset: u64,
+    filter: Option<&Regex>,
+    threshold: Option<&GrowthThreshold>,
+    read_options: impl Fn() -> ReadOptions,
+    mut emit: impl FnMut(String),
+) {
+    let mut buffered = false;
+    loop {
+        let flush = match threshold {
+            Some(threshold) if buffered => match receiver.recv_timeout(threshold.flush_after) {
+                Ok(()) => false,
+                Err(mpsc::RecvTimeoutError::Timeout) => true,
+                Err(mpsc::RecvTimeoutError::Disconnected) => return,
+            },
+            _ => match receiver.recv() {
+                Ok(()) => false,
+                Err(_) => return,
+            },
+        };
+        if !flush {
+            loop {
+                match receiver.recv_timeout(DIFF_DEBOUNCE) {
+                    Ok(()) => continue,
+                    Err(mpsc::RecvTimeoutError::Timeout) => break,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => return,
+                }
+            }
+        }
+
+        buffered = false;
+        let Ok(len) = std::fs::metadata(file_path).map(|metadata| metadata.len()) else {
+            continue;
+        };
+        if len < offset {
+            log_watch_event(&format!("File shrank; following appends from byte {len}"));
+            offset = len;
+            continue;
+        }
+        if let Some(threshold) = threshold {
+            if !flush && len - offset < threshold.min_bytes {
+                buffered = len > offset;
+                continue;
+            }
+        }
+        let Some(contents) = read_appended_lines(&read_options(), file_path, &mut offset, len)
+        else {
+            continue;
+        };
+        match filter {
+            Some(filter) => {
+                let matching: String = contents
+                    .split_inclusive('\n')
+                    .filter(|line| filter.is_match(line.trim_end_matches(['\r', '\n'])))
+                    .collect();
+                if !matching.is_empty() {
+                    emit(matching);
+                }
+            }
+            None => emit(contents),
+        }
+    }
+}
+
+/// The file and tag of the active watch when it is watching `resolved` and the file exists.
+fn watched_file(
+    state: &WatcherState,
+    resolved: &Path,
+) -> Result<(PathBuf, Option<String>), String> {
+    let guard = state.inner.lock().expect("watcher state poisoned");
+    match guard.as_ref() {
+        Some(active)
+            if normalize_path_cased(resolved, active.case_insensitive)
+                == active.normalized_path =>
+        {
+            if active.orphaned.load(Ordering::SeqCst) {
+                return Err("Watched file was deleted".into());
+            }
+            if !active.readable.load(Ordering::SeqCst) {
+                return Err("Watched file is not readable".into());
+            }
+            Ok((active.file_path.clone(), active.tag.clone()))
+        }
+        _ => Err("Path is not being watched".into()),
+    }
+}
+
+#[tauri::command]
+fn refresh_watched_file(
+    app: tauri::AppHandle,
+    state: tauri::State<WatcherState>,
+    path: String,
+) -> Result<(), String> {
+    let resolved = resolve_absolute_path(&resolve_input_path(&path));
+    let target = normalize_path(&resolved);
+    let (file_path, tag) = watched_file(&state, &resolved)?;
+
+    log_watch_event(&format!("Refreshing watched file {target}"));
+    let response = read_ntr_file(&file_path, watch_read_options(&app, &file_path))?;
+    app.emit(
+        "ntr-file-changed",
+        FileChangePayload {
+            path: response.path,
+            kind: "refresh".into(),
+            contents: Some(response.contents),
+            encoding: None,
+            tag,
+        },
+    )
+    .map_err(|err| format!("Failed to emit refresh event: {err}"))
+}
+
+/// Remembers `encoding` for the watched file, so later watch reloads decode with it, and
+/// re-emits the contents right away as an `ntr-file-changed` event of kind "encoding". The
+/// watch itself keeps running.
+#[tauri::command]
+fn set_watch_encoding(
+    app: tauri::AppHandle,
+    state: tauri::State<WatcherState>,
+    settings: tauri::State<SettingsState>,
+    path: String,
+    encoding: String,
+) -> Result<(), String> {
+    let resolved = resolve_absolute_path(&resolve_input_path(&path));
+    let (file_path, tag) = watched_file(&state, &resolved)?;
+    let encoding = parse_encoding_label(&encoding)?;
+    let options = ReadOptions {
+        encoding: Some(encoding),
+        from_sidecar: false,
+        ..read_options_for(&settings, &file_path)
+    };
+    let response = read_ntr_file(&file_path, options)?;
+    settings.update(|settings| {
+        settings
+            .file_encodings
+            .insert(normalize_path(&file_path), encoding.name().to_string())
+    })?;
+
+    log_watch_event(&format!(
+        "Watch encoding set to {}: {}",
+        encoding.name(),
+        response.path
+    ));
+    app.emit(
+        "ntr-file-changed",
+        FileChangePayload {
+            path: response.path,
+            kind: "encoding".into(),
+            contents: Some(response.contents),
+            encoding: Some(response.encoding),
+            tag,
+        },
+    )
+    .map_err(|err| format!("Failed to emit encoding change event: {err}"))
+}
+
+#[tauri::command]
+fn stop_file_watch(state: tauri::State<WatcherState>) -> Result<(), String> {
+    let mut guard = state.inner.lock().expect("watcher state poisoned");
+    if guard.is_some() {
+        log_watch_event("Stopping active watcher");
+    }
+    guard.take();
+    Ok(())
+}
+
+/// Filesystem types whose change notifications are missing or unreliable for native watchers.
+const UNRELIABLE_WATCH_FILESYSTEMS: &[&str] = &[
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb3",
+    "smbfs",
+    "9p",
+    "drvfs",
+    "fuse.sshfs",
+    "fuse.rclone",
+    "davfs",
+];
+
+#[derive(Serialize)]
+struct WatchCapabilities {
+    /// The notify backend native watches use on this platform, e.g. "inotify".
+    backend: &'static str,
+    /// "native" or "polling".
+    recommendation: &'static str,
+    /// Why polling is recommended; `None` with a "native" recommendation.
+    reason: Option<String>,
+}
+
+/// The filesystem type of the mount holding `path`, by the longest matching mount point in
+/// `mounts` (the `/proc/mounts` format, where spaces in mount points are written as `\040`).
+fn mount_filesystem(path: &Path, mounts: &str) -> Option<String> {
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let mount_point = fields.nth(1)?.replace("\\040", " ");
+            let filesystem = fields.next()?;
+            path.starts_with(&mount_point)
+                .then(|| (mount_point.len(), filesystem.to_string()))
+        })
+        .max_by_key(|(len, _)| *len)
+        .map(|(_, filesystem)| filesystem)
+}
+
+/// Heuristic reasons native change notifications may not arrive for `path`: network shares,
+/// Windows drives seen from WSL and network or FUSE filesystems listed in `/proc/mounts`.
+fn unreliable_watch_reason(path: &Path) -> Option<String> {
+    let normalized = normalize_path(path);
+    if cfg!(windows) && normalized.starts_with("//") {
+        return Some("Network share (UNC path)".into());
+    }
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    let mounts = std::fs::read_to_string("/proc/mounts").unwrap_or_default();
+    if let Some(filesystem) = mount_filesystem(path, &mounts) {
+        if UNRELIABLE_WATCH_FILESYSTEMS.contains(&filesystem.as_str()) {
+            return Some(format!("{filesystem} filesystem"));
+        }
+    }
+    let in_wsl = std::fs::read_to_string("/proc/sys/kernel/osrelease")
+        .is_ok_and(|release| release.to_ascii_lowercase().contains("microsoft"));
+    let on_windows_drive = normalized
+        .strip_prefix("/mnt/")
+        .is_some_and(|rest| rest.len() == 1 || rest.chars().nth(1) == Some('/'));
+    (in_wsl && on_windows_drive).then(|| "Windows drive mounted into WSL".into())
+}
+
+/// Reports the native watch backend and whether `path` is better watched by polling. The check
+/// is a heuristic over the path and mount table; it never opens a watch.
+#[tauri::command]
+fn watch_capabilities(path: String) -> WatchCapabilities {
+    let backend = match RecommendedWatcher::kind() {
+        WatcherKind::Inotify => "inotify",
+        WatcherKind::Fsevent => "fsevent",
+        WatcherKind::Kqueue => "kqueue",
+        WatcherKind::PollWatcher => "poll",
+        WatcherKind::ReadDirectoryChangesWatcher => "read-directory-changes",
+        WatcherKind::NullWatcher => "null",
+        _ => "unknown",
+    };
+    let reason = unreliable_watch_reason(&resolve_absolute_path(&resolve_input_path(&path)));
+    WatchCapabilities {
+        backend,
+        recommendation: if reason.is_some() {
+            "polling"
+        } else {
+            "native"
+        },
+        reason,
+    }
+}
+
+/// Watches `dir` and emits `ntr-latest-file-changed` whenever the newest `.ntr` file changes
+/// identity ("switch") or the current newest file is modified ("modify"). Returns the file that
+/// is newest when the watch starts.
+#[tauri::command]
+fn watch_latest_ntr(
+    app: tauri::AppHandle,
+    state: tauri::State<LatestWatchState>,
+    dir: String,
+) -> Result<Option<String>, String> {
+    log_watch_event(&format!("Starting latest-file watch for {dir}"));
+    let dir_path = resolve_absolute_path(&resolve_input_path(&dir));
+    if !dir_path.is_dir() {
+        return Err("Path is not a directory".into());
+    }
+
+    {
+        let mut guard = state.inner.lock().expect("latest watch state poisoned");
+        guard.take();
+    }
+
+    let initial =
+        find_latest_ntr(&dir_path)?.map(|latest| normalize_path(&resolve_absolute_path(&latest)));
+    let current = Arc::new(Mutex::new(initial.clone()));
+    let current_for_watch = current.clone();
+    let dir_for_watch = dir_path.clone();
+    let app_handle = app.clone();
 
-    let file_path_for_match = normalized_path.clone();
-    let mut watcher = notify::recommended_watcher(move |res: Result<Event, notify::Error>| {
-        match res {
+    let mut watcher =
+        notify::recommended_watcher(move |res: Result<Event, notify::Error>| match res {
             Ok(event) => {
-                if should_emit_event(&event.kind) && paths_match(&event.paths, &file_path_for_match)
+                if !should_emit_event(&event.kind, DEFAULT_IGNORED_KINDS)
+                    || !(event.paths.is_empty() || event.paths.iter().any(|path| is_ntr_file(path)))
                 {
-                    #[cfg(debug_assertions)]
-                    {
-                        let paths: Vec<String> = event
-                            .paths
-                            .iter()
-                            .map(|path| normalize_path(path))
-                            .collect();
-                        log_watch_event(&format!(
-                            "Event {:?} for paths {:?}",
-                            event.kind, paths
-                        ));
+                    return;
+                }
+                let latest = match find_latest_ntr(&dir_for_watch) {
+                    Ok(latest) => {
+                        latest.map(|latest| normalize_path(&resolve_absolute_path(&latest)))
                     }
-
-                    let payload = FileChangePayload {
-                        path: emit_path_for_watch.as_ref().clone(),
-                        kind: format_event_kind(&event.kind),
-                    };
-                    if let Err(err) = app_handle.emit("ntr-file-changed", payload) {
-                        eprintln!("Failed to emit file change event: {err}");
+                    Err(err) => {
+                        log_watch_event(&format!("Latest-file scan failed: {err}"));
+                        return;
                     }
+                };
+
+                let mut current = current_for_watch.lock().expect("latest target poisoned");
+                let Some(latest) = latest else {
+                    current.take();
+                    return;
+                };
+                let kind = if current.as_deref() != Some(latest.as_str()) {
+                    "switch"
+                } else if paths_match(
+                    &event.paths,
+                    &latest,
+                    cfg!(windows),
+                    matches!(event.kind, EventKind::Modify(ModifyKind::Name(_))),
+                ) {
+                    "modify"
+                } else {
+                    return;
+                };
+                log_watch_event(&format!("Latest file {kind}: {latest}"));
+                *current = Some(latest.clone());
+                let payload = FileChangePayload {
+                    path: latest,
+                    kind: kind.into(),
+                    contents: None,
+                    encoding: None,
+                    tag: None,
+                };
+                if let Err(err) = app_handle.emit("ntr-latest-file-changed", payload) {
+                    log::error!("Failed to emit latest file event: {err}");
                 }
             }
             Err(err) => {
-                eprintln!("File watcher error: {err}");
-                log_watch_event(&format!("Watcher error: {err}"));
+                log::warn!(target: "watch", "Latest-file watcher error: {err}");
+                log_watch_event(&format!("Latest-file watcher error: {err}"));
                 let _ = app_handle.emit(
                     "ntr-file-watch-error",
                     FileChangePayload {
-                        path: emit_path_for_watch.as_ref().clone(),
+                        path: normalize_path(&dir_for_watch),
                         kind: format!("error:{err}"),
+                        contents: None,
+                        encoding: None,
+                        tag: None,
                     },
                 );
             }
-        }
-    })
-    .map_err(|err| err.to_string())?;
-
-    watcher
-        .configure(Config::default())
+        })
         .map_err(|err| err.to_string())?;
-    let watch_target = canonical_path
-        .parent()
-        .map(Path::to_path_buf)
-        .unwrap_or_else(|| canonical_path.clone());
+
     watcher
-        .watch(&watch_target, RecursiveMode::NonRecursive)
+        .watch(&dir_path, RecursiveMode::NonRecursive)
         .map_err(|err| err.to_string())?;
 
-    let mut guard = state.inner.lock().expect("watcher state poisoned");
-    *guard = Some(ActiveWatcher {
+    let mut guard = state.inner.lock().expect("latest watch state poisoned");
+    *guard = Some(LatestWatcher {
         _watcher: watcher,
-        _file_path: canonical_path,
+        _current: current,
     });
-    Ok(())
+    Ok(initial)
 }
 
 #[tauri::command]
-fn stop_file_watch(state: tauri::State<WatcherState>) -> Result<(), String> {
-    let mut guard = state.inner.lock().expect("watcher state poisoned");
-    #[cfg(debug_assertions)]
-    {
-        if guard.is_some() {
-            log_watch_event("Stopping active watcher");
-        }
+fn stop_latest_watch(state: tauri::State<LatestWatchState>) {
+    let mut guard = state.inner.lock().expect("latest watch state poisoned");
+    if guard.take().is_some() {
+        log_watch_event("Stopping latest-file watcher");
     }
-    guard.take();
-    Ok(())
 }
 
-fn should_emit_event(kind: &EventKind) -> bool {
-    !matches!(kind, EventKind::Access(_))
+/// Kind names accepted by `start_file_watch`'s `ignore_kinds`. "metadata" covers only
+/// metadata modifications, whereas "modify" covers every modification.
+const WATCH_EVENT_KINDS: &[&str] = &[
+    "access", "create", "modify", "metadata", "remove", "any", "other",
+];
+const DEFAULT_IGNORED_KINDS: &[&str] = &["access"];
+
+fn parse_ignored_kinds(names: Option<Vec<String>>) -> Result<Vec<&'static str>, String> {
+    let Some(names) = names else {
+        return Ok(DEFAULT_IGNORED_KINDS.to_vec());
+    };
+    names
+        .iter()
+        .map(|name| {
+            let name = name.trim().to_ascii_lowercase();
+            WATCH_EVENT_KINDS
+                .iter()
+                .find(|&&known| known == name)
+                .copied()
+                .ok_or_else(|| {
+                    format!(
+                        "Unknown event kind: {name} (expected one of {})",
+                        WATCH_EVENT_KINDS.join(", ")
+                    )
+                })
+        })
+        .collect()
+}
+
+fn should_emit_event(kind: &EventKind, ignored: &[&str]) -> bool {
+    let is_metadata = matches!(kind, EventKind::Modify(ModifyKind::Metadata(_)));
+    !(ignored.contains(&format_event_kind(kind).as_str())
+        || (is_metadata && ignored.contains(&"metadata")))
 }
 
 fn format_event_kind(kind: &EventKind) -> String {
@@ -203,23 +2635,111 @@ fn format_event_kind(kind: &EventKind) -> String {
     }
 }
 
-fn paths_match(event_paths: &[PathBuf], target: &str) -> bool {
+/// `target` must have been normalized with the same `case_insensitive` setting. Paths that
+/// are spelled differently are compared by file identity only when the file names agree up to
+/// case or the event is a rename, so unrelated files in the directory cost no extra opens.
+fn paths_match(
+    event_paths: &[PathBuf],
+    target: &str,
+    case_insensitive: bool,
+    renamed: bool,
+) -> bool {
     if event_paths.is_empty() {
         log_watch_event("Event without explicit path list; assuming match");
         return true;
     }
     for path in event_paths {
-        let candidate = normalize_path(path);
-        log_watch_event(&format!("Comparing event path {} to target {}", candidate, target));
+        let candidate = normalize_path_cased(path, case_insensitive);
+        log_watch_event(&format!(
+            "Comparing event path {} to target {}",
+            candidate, target
+        ));
         if candidate == target {
             log_watch_event("Path match confirmed");
             return true;
         }
     }
+    let target_path = Path::new(target);
+    let target_name = target_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase());
+    let same_name = |path: &PathBuf| {
+        path.file_name()
+            .is_some_and(|name| Some(name.to_string_lossy().to_lowercase()) == target_name)
+    };
+    if event_paths
+        .iter()
+        .filter(|path| renamed || same_name(path))
+        .any(|path| refers_to_same_file(path, target_path))
+    {
+        log_watch_event("Path match confirmed by file identity");
+        return true;
+    }
     false
 }
 
+/// Compares file identity (device and inode on Unix, volume serial and file index on Windows)
+/// rather than path spelling, so symlinks, case differences and `\\?\` prefixes still match.
+/// Paths that cannot be opened never match.
+fn refers_to_same_file(a: &Path, b: &Path) -> bool {
+    same_file::is_same_file(a, b).unwrap_or(false)
+}
+
+#[tauri::command]
+fn same_file(a: String, b: String) -> Result<bool, String> {
+    let (a, b) = (resolve_input_path(&a), resolve_input_path(&b));
+    for path in [&a, &b] {
+        if !path.exists() {
+            return Err(format!("File not found: {}", path.display()));
+        }
+    }
+    Ok(refers_to_same_file(&a, &b))
+}
+
+/// Expands a leading `~` to the user's home directory. Relative results are left relative so
+/// they resolve against the process working directory like any other relative path.
+fn resolve_input_path(input: &str) -> PathBuf {
+    let home = || {
+        std::env::var_os("HOME")
+            .or_else(|| std::env::var_os("USERPROFILE"))
+            .map(PathBuf::from)
+    };
+    if input == "~" {
+        if let Some(home) = home() {
+            return home;
+        }
+    } else if let Some(rest) = input
+        .strip_prefix("~/")
+        .or_else(|| input.strip_prefix("~\\"))
+    {
+        if let Some(home) = home() {
+            return home.join(rest);
+        }
+    }
+    PathBuf::from(input)
+}
+
+/// Returns the absolute, normalized path that the load and watch commands would use for
+/// `path`, without touching the file itself.
+#[tauri::command]
+fn resolve_path(path: String) -> String {
+    normalize_path(&resolve_absolute_path(&resolve_input_path(&path)))
+}
+
+/// Canonicalizes `path`, falling back to a lexically absolute form when canonicalization fails
+/// so relative inputs still compare equal to the absolute paths notify reports.
+fn resolve_absolute_path(path: &Path) -> PathBuf {
+    path.canonicalize()
+        .or_else(|_| std::path::absolute(path))
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
 fn normalize_path(path: &Path) -> String {
+    normalize_path_cased(path, cfg!(windows))
+}
+
+/// Like `normalize_path`, but lowercases according to `case_insensitive` instead of the OS.
+fn normalize_path_cased(path: &Path, case_insensitive: bool) -> String {
     let mut normalized = path.to_string_lossy().replace('\\', "/");
     if cfg!(windows) {
         if normalized.starts_with("//?/UNC/") {
@@ -227,53 +2747,1028 @@ fn normalize_path(path: &Path) -> String {
         } else if normalized.starts_with("//?/") {
             normalized = normalized[4..].to_string();
         }
+    }
+    if case_insensitive {
         normalized = normalized.to_lowercase();
     }
     normalized
 }
 
-fn decode_ntr_bytes(bytes: &[u8]) -> Result<String, String> {
+/// Decides whether the volume holding the existing `path` ignores case by looking the path up
+/// again with the case of its last lettered component flipped: the volume is case-insensitive
+/// when the variant resolves to the same file. Falls back to the OS default when no component
+/// has letters to flip.
+fn probe_case_insensitive(path: &Path) -> bool {
+    for ancestor in path.ancestors() {
+        let Some(name) = ancestor.file_name().map(|name| name.to_string_lossy()) else {
+            continue;
+        };
+        let flipped: String = name
+            .chars()
+            .map(|ch| {
+                if ch.is_lowercase() {
+                    ch.to_uppercase().next().unwrap_or(ch)
+                } else {
+                    ch.to_lowercase().next().unwrap_or(ch)
+                }
+            })
+            .collect();
+        if flipped == name {
+            continue;
+        }
+        let variant = ancestor.with_file_name(flipped);
+        let suffix = path.strip_prefix(ancestor).unwrap_or(Path::new(""));
+        return refers_to_same_file(path, &variant.join(suffix));
+    }
+    cfg!(windows)
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct DecodeAttempt {
+    encoding: String,
+    had_errors: bool,
+    error_count: usize,
+    first_error_offset: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct DecodeDiagnostics {
+    message: String,
+    attempts: Vec<DecodeAttempt>,
+    best_candidate: Option<String>,
+    first_error_offset: Option<usize>,
+}
+
+impl DecodeDiagnostics {
+    fn from_attempts(message: String, attempts: Vec<DecodeAttempt>) -> Self {
+        let best = attempts.iter().min_by_key(|attempt| attempt.error_count);
+        let best_candidate = best.map(|attempt| attempt.encoding.clone());
+        let first_error_offset = best.and_then(|attempt| attempt.first_error_offset);
+        DecodeDiagnostics {
+            message,
+            attempts,
+            best_candidate,
+            first_error_offset,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum LoadErrorKind {
+    Io,
+    Decode,
+    Timeout,
+    /// The request itself was rejected, e.g. an unknown encoding label or a zero tab width.
+    Invalid,
+}
+
+#[derive(Serialize)]
+struct LoadError {
+    kind: LoadErrorKind,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diagnostics: Option<DecodeDiagnostics>,
+}
+
+impl LoadError {
+    fn new(kind: LoadErrorKind, message: impl Into<String>) -> Self {
+        LoadError {
+            kind,
+            message: message.into(),
+            diagnostics: None,
+        }
+    }
+
+    fn io(message: impl Into<String>) -> Self {
+        LoadError::new(LoadErrorKind::Io, message)
+    }
+}
+
+impl From<DecodeDiagnostics> for LoadError {
+    fn from(diagnostics: DecodeDiagnostics) -> Self {
+        LoadError {
+            kind: LoadErrorKind::Decode,
+            message: diagnostics.message.clone(),
+            diagnostics: Some(diagnostics),
+        }
+    }
+}
+
+impl From<LoadError> for String {
+    fn from(err: LoadError) -> Self {
+        err.message
+    }
+}
+
+impl From<DecodeDiagnostics> for String {
+    fn from(diagnostics: DecodeDiagnostics) -> Self {
+        diagnostics.message
+    }
+}
+
+/// Error offsets kept per scan; `error_count` keeps counting past this.
+const MAX_REPORTED_DECODE_ERRORS: usize = 10_000;
+
+struct DecodeScan {
+    error_count: usize,
+    /// Start offsets of the first `MAX_REPORTED_DECODE_ERRORS` malformed sequences.
+    error_offsets: Vec<usize>,
+    non_ascii_count: usize,
+}
+
+/// Decodes `bytes` strictly, counting malformed sequences (and where they start) and non-ASCII
+/// characters, without materialising the decoded text.
+fn scan_decode(encoding: &'static Encoding, bytes: &[u8]) -> DecodeScan {
+    scan_decode_reader(encoding, bytes).expect("reading from memory cannot fail")
+}
+
+const SCAN_CHUNK_BYTES: usize = 64 * 1024;
+
+/// `scan_decode` over everything `reader` yields, read in `SCAN_CHUNK_BYTES` chunks so a whole
+/// file can be checked in bounded memory.
+fn scan_decode_reader(
+    encoding: &'static Encoding,
+    mut reader: impl Read,
+) -> std::io::Result<DecodeScan> {
+    let mut decoder = encoding.new_decoder_without_bom_handling();
+    let mut chunk = vec![0; SCAN_CHUNK_BYTES];
+    let mut scratch = String::with_capacity(8192);
+    let mut chunk_start = 0;
+    let mut error_count = 0;
+    let mut error_offsets = Vec::new();
+    let mut non_ascii_count = 0;
+    let count_non_ascii = |text: &str| text.chars().filter(|ch| !ch.is_ascii()).count();
+    loop {
+        let filled = match reader.read(&mut chunk) {
+            Ok(filled) => filled,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        let last = filled == 0;
+        let mut position = 0;
+        loop {
+            let (result, read) = decoder.decode_to_string_without_replacement(
+                &chunk[position..filled],
+                &mut scratch,
+                last,
+            );
+            position += read;
+            match result {
+                DecoderResult::InputEmpty => break,
+                DecoderResult::OutputFull => {
+                    non_ascii_count += count_non_ascii(&scratch);
+                    scratch.clear();
+                }
+                DecoderResult::Malformed(bad, consumed) => {
+                    error_count += 1;
+                    if error_offsets.len() < MAX_REPORTED_DECODE_ERRORS {
+                        // A malformed sequence may have started in the previous chunk.
+                        let end = chunk_start + position;
+                        error_offsets.push(end - usize::from(bad) - usize::from(consumed));
+                    }
+                }
+            }
+        }
+        if last {
+            break;
+        }
+        chunk_start += filled;
+    }
+    non_ascii_count += count_non_ascii(&scratch);
+    Ok(DecodeScan {
+        error_count,
+        error_offsets,
+        non_ascii_count,
+    })
+}
+
+fn scan_decode_errors(encoding: &'static Encoding, bytes: &[u8]) -> DecodeAttempt {
+    let scan = scan_decode(encoding, bytes);
+    DecodeAttempt {
+        encoding: encoding.name().to_string(),
+        had_errors: scan.error_count > 0,
+        error_count: scan.error_count,
+        first_error_offset: scan.error_offsets.first().copied(),
+    }
+}
+
+/// Encodings auto-detection considers for `bytes`, in the order it tries them: a BOM-declared
+/// encoding, UTF-8, then the fallback chain.
+fn detection_candidates(bytes: &[u8], fallbacks: &[&'static Encoding]) -> Vec<&'static Encoding> {
+    let mut candidates = Vec::new();
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        candidates.push(encoding);
+    }
+    for &encoding in std::iter::once(&UTF_8).chain(fallbacks) {
+        if !candidates.contains(&encoding) {
+            candidates.push(encoding);
+        }
+    }
+    candidates
+}
+
+/// The encoding `bytes` of a whole file are read with: the chosen one, else what detection
+/// picks, else UTF-8.
+fn file_encoding(bytes: &[u8], options: &ReadOptions) -> &'static Encoding {
+    options
+        .encoding
+        .or_else(|| detect_encoding(bytes, options.fallback_chain()))
+        .unwrap_or(UTF_8)
+}
+
+/// `detect_encoding` over a whole file, streamed through a buffer rather than mapped, so a
+/// file truncated during the scan reads short instead of faulting.
+fn detect_file_encoding(
+    path: &Path,
+    fallbacks: &[&'static Encoding],
+) -> Result<Option<&'static Encoding>, String> {
+    let open = || File::open(path).map_err(|err| format!("Failed to open file: {err}"));
+    let read_error = |err: std::io::Error| format!("Failed to read file: {err}");
+    let mut leading = Vec::new();
+    // Enough for the longest BOM.
+    open()?
+        .take(3)
+        .read_to_end(&mut leading)
+        .map_err(read_error)?;
+    if let Some((encoding, _)) = Encoding::for_bom(&leading) {
+        return Ok(Some(encoding));
+    }
+    for encoding in detection_candidates(&leading, fallbacks) {
+        let scan = scan_decode_reader(encoding, BufReader::new(open()?)).map_err(read_error)?;
+        if scan.error_count == 0 {
+            return Ok(Some(encoding));
+        }
+    }
+    Ok(None)
+}
+
+/// The encoding `detect_and_decode` would pick for `bytes`, without keeping the decoded text.
+fn detect_encoding(bytes: &[u8], fallbacks: &[&'static Encoding]) -> Option<&'static Encoding> {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return Some(encoding);
+    }
+    detection_candidates(bytes, fallbacks)
+        .into_iter()
+        .find(|&encoding| scan_decode(encoding, bytes).error_count == 0)
+}
+
+#[derive(Serialize)]
+struct DecodeErrorLocation {
+    offset: usize,
+    /// 1-based line containing the malformed sequence.
+    line: usize,
+}
+
+#[derive(Serialize)]
+struct LossyLoadResponse {
+    path: String,
+    contents: String,
+    encoding: String,
+    error_count: usize,
+    /// Where replacement characters were inserted, capped at `MAX_REPORTED_DECODE_ERRORS`.
+    errors: Vec<DecodeErrorLocation>,
+}
+
+/// Decodes with replacement characters instead of failing, using the remembered encoding or
+/// else the detection candidate with the fewest errors, and reports where each error is.
+#[tauri::command]
+fn load_ntr_file_lossy(
+    settings: tauri::State<SettingsState>,
+    path: String,
+) -> Result<LossyLoadResponse, String> {
+    let resolved = resolve_input_path(&path);
+    let bytes =
+        std::fs::read(&resolved).map_err(|err| format!("Failed to read file bytes: {err}"))?;
+    let options = read_options_for(&settings, &resolved);
+    let scan_body = |encoding| scan_decode(encoding, &bytes[bom_len_for(encoding, &bytes)..]);
+
+    let (encoding, scan) = match options.encoding {
+        Some(encoding) => (encoding, scan_body(encoding)),
+        None => detection_candidates(&bytes, options.fallback_chain())
+            .into_iter()
+            .map(|encoding| (encoding, scan_body(encoding)))
+            .min_by_key(|(_, scan)| scan.error_count)
+            .expect("detection always has a UTF-8 candidate"),
+    };
+    let bom_len = bom_len_for(encoding, &bytes);
+    let (contents, _) = encoding.decode_without_bom_handling(&bytes[bom_len..]);
+    let offsets: Vec<usize> = scan
+        .error_offsets
+        .iter()
+        .map(|offset| offset + bom_len)
+        .collect();
+    let errors = offsets
+        .iter()
+        .zip(line_numbers_at(&bytes, &offsets, encoding))
+        .map(|(&offset, line)| DecodeErrorLocation { offset, line })
+        .collect();
+
+    Ok(LossyLoadResponse {
+        path: normalize_path(&resolve_absolute_path(&resolved)),
+        contents: contents.into_owned(),
+        encoding: encoding.name().to_string(),
+        error_count: scan.error_count,
+        errors,
+    })
+}
+
+/// Maps ascending byte offsets to 1-based line numbers by counting newlines before each one.
+/// UTF-16 newlines are matched as aligned code units.
+fn line_numbers_at(bytes: &[u8], offsets: &[usize], encoding: &'static Encoding) -> Vec<usize> {
+    let newline: &[u8] = if encoding == UTF_16LE {
+        &[b'\n', 0]
+    } else if encoding == UTF_16BE {
+        &[0, b'\n']
+    } else {
+        b"\n"
+    };
+    let mut lines = Vec::with_capacity(offsets.len());
+    let mut line = 1;
+    let mut position = 0;
+    for &offset in offsets {
+        while position + newline.len() <= offset.min(bytes.len()) {
+            if bytes[position..].starts_with(newline) {
+                line += 1;
+            }
+            position += newline.len();
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+struct DecodedText {
+    contents: String,
+    encoding: &'static Encoding,
+    had_bom: bool,
+}
+
+impl DecodedText {
+    fn new(contents: String, encoding: &'static Encoding, had_bom: bool) -> Self {
+        DecodedText {
+            contents,
+            encoding,
+            had_bom,
+        }
+    }
+
+    /// Encodes `contents` the way this text was stored: same encoding, BOM restored if present.
+    fn encode_like_source(&self, contents: &str) -> Result<Vec<u8>, String> {
+        let mut bytes = Vec::new();
+        if self.had_bom && self.encoding == UTF_8 {
+            bytes.extend_from_slice(b"\xEF\xBB\xBF");
+        }
+        bytes.extend(encode_contents(contents, self.encoding)?);
+        Ok(bytes)
+    }
+
+    /// Maps an offset into `contents`, counted in UTF-16 code units as the frontend's strings
+    /// are, to the matching byte offset in the encoded file (after the BOM, if one was present).
+    fn byte_offset_of(&self, char_offset: usize) -> Result<u64, String> {
+        let mut units = 0;
+        let mut end = None;
+        for (index, ch) in self.contents.char_indices() {
+            if units >= char_offset {
+                end = Some(index);
+                break;
+            }
+            units += ch.len_utf16();
+        }
+        let end = match end {
+            Some(index) if units == char_offset => index,
+            None if units == char_offset => self.contents.len(),
+            Some(_) => return Err(format!("Offset {char_offset} splits a surrogate pair")),
+            None => return Err(format!("Offset {char_offset} is past the end of the file")),
+        };
+
+        let prefix = &self.contents[..end];
+        let bom_len = match (self.had_bom, self.encoding == UTF_8) {
+            (false, _) => 0,
+            (true, true) => 3,
+            (true, false) => 2,
+        };
+        let encoded_len = if self.encoding == UTF_8 {
+            prefix.len()
+        } else if self.encoding == UTF_16LE || self.encoding == UTF_16BE {
+            prefix.encode_utf16().count() * 2
+        } else {
+            self.encoding.encode(prefix).0.len()
+        };
+        Ok((bom_len + encoded_len) as u64)
+    }
+}
+
+fn decode_bytes(bytes: &[u8], options: &ReadOptions) -> Result<DecodedText, DecodeDiagnostics> {
+    match options.encoding {
+        Some(encoding) => decode_with_encoding(bytes, encoding),
+        None => detect_and_decode(bytes, options.fallback_chain()),
+    }
+}
+
+fn detect_and_decode(
+    bytes: &[u8],
+    fallbacks: &[&'static Encoding],
+) -> Result<DecodedText, DecodeDiagnostics> {
     if bytes.is_empty() {
-        return Ok(String::new());
+        return Ok(DecodedText::new(String::new(), UTF_8, false));
     }
 
     if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
         let (decoded, _, had_errors) = encoding.decode(&bytes[bom_len..]);
         if had_errors {
-            return Err(format!(
-                "File encoding {} contains invalid sequences",
-                encoding.name()
+            let mut attempt = scan_decode_errors(encoding, &bytes[bom_len..]);
+            attempt.first_error_offset = attempt.first_error_offset.map(|offset| offset + bom_len);
+            return Err(DecodeDiagnostics::from_attempts(
+                format!(
+                    "File encoding {} contains invalid sequences",
+                    encoding.name()
+                ),
+                vec![attempt],
             ));
         }
-        return Ok(decoded.into_owned());
+        return Ok(DecodedText::new(decoded.into_owned(), encoding, true));
     }
 
     let (utf8, _, utf8_errors) = UTF_8.decode(bytes);
     if !utf8_errors {
-        return Ok(utf8.into_owned());
+        return Ok(DecodedText::new(utf8.into_owned(), UTF_8, false));
+    }
+
+    for &fallback in fallbacks {
+        let (decoded, had_errors) = fallback.decode_without_bom_handling(bytes);
+        if !had_errors {
+            return Ok(DecodedText::new(decoded.into_owned(), fallback, false));
+        }
     }
 
-    let (fallback, _, fallback_errors) = WINDOWS_1252.decode(bytes);
-    if !fallback_errors {
-        return Ok(fallback.into_owned());
+    let candidates = detection_candidates(bytes, fallbacks);
+    let tried: Vec<&str> = candidates.iter().map(|encoding| encoding.name()).collect();
+    Err(DecodeDiagnostics::from_attempts(
+        format!("Unsupported file encoding; tried {}", tried.join(", ")),
+        candidates
+            .iter()
+            .map(|&encoding| scan_decode_errors(encoding, bytes))
+            .collect(),
+    ))
+}
+
+/// Length of the BOM at the start of `bytes` when it belongs to `encoding`, otherwise 0.
+fn bom_len_for(encoding: &'static Encoding, bytes: &[u8]) -> usize {
+    match Encoding::for_bom(bytes) {
+        Some((bom_encoding, bom_len)) if bom_encoding == encoding => bom_len,
+        _ => 0,
     }
+}
 
-    Err("Unsupported file encoding; expected UTF-8 or Windows-1252".into())
+fn decode_with_encoding(
+    bytes: &[u8],
+    encoding: &'static Encoding,
+) -> Result<DecodedText, DecodeDiagnostics> {
+    let bom_len = bom_len_for(encoding, bytes);
+    let (decoded, had_errors) = encoding.decode_without_bom_handling(&bytes[bom_len..]);
+    if had_errors {
+        let mut attempt = scan_decode_errors(encoding, &bytes[bom_len..]);
+        attempt.first_error_offset = attempt.first_error_offset.map(|offset| offset + bom_len);
+        return Err(DecodeDiagnostics::from_attempts(
+            format!("File is not valid {}", encoding.name()),
+            vec![attempt],
+        ));
+    }
+    Ok(DecodedText::new(
+        decoded.into_owned(),
+        encoding,
+        bom_len > 0,
+    ))
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
+        .setup(|app| {
+            app.manage(SettingsState::load(app.handle()));
+            app.state::<SettingsState>().watch(app.handle());
+            Ok(())
+        })
+        .plugin(logging::plugin())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_opener::init())
         .manage(WatcherState::default())
+        .manage(MappedFileState::default())
+        .manage(SearchState::default())
+        .manage(StreamState::default())
+        .manage(LatestWatchState::default())
+        .manage(clipboard::ClipboardState::default())
+        .manage(multiwatch::MultiWatchState::default())
+        .manage(multiwatch::MergeWatchState::default())
         .invoke_handler(tauri::generate_handler![
             greet,
+            logging::get_log_path,
             open_ntr_file,
             load_ntr_file,
+            open_ntr_as_template,
+            save_ntr_file,
+            save_ntr_file_as,
+            has_unsaved_changes,
+            diff_against_disk,
+            encoding::can_decode,
+            disk_space_for,
+            validate_save_path,
             start_file_watch,
-            stop_file_watch
+            stop_file_watch,
+            watch_capabilities,
+            watch_latest_ntr,
+            stop_latest_watch,
+            refresh_watched_file,
+            watch_debug_info,
+            set_watch_encoding,
+            load_ntr_file_timeout,
+            load_ntr_file_lossy,
+            encoding::suggest_encoding_fix,
+            encoding::repair_utf8,
+            is_file_settled,
+            load_ntr_file_with_encoding,
+            load_ntr_transcoded,
+            forget_file_encoding,
+            open_latest_ntr,
+            list_directory,
+            resolve_path,
+            same_file,
+            encoding::list_supported_encodings,
+            encoding::list_encodings_friendly,
+            encoding::get_fallback_encodings,
+            encoding::set_fallback_encodings,
+            encoding::minimal_encoding_for,
+            encoding::encoding_scorecard,
+            encoding::sniff_ntr_encoding,
+            encoding::detect_mixed_encoding,
+            encoding::per_line_encoding,
+            inspect::read_bom,
+            inspect::load_ntr_file_debug,
+            inspect::file_summary,
+            inspect::guess_file_type,
+            inspect::gzip_uncompressed_size,
+            records::infer_ntr_schema,
+            records::compare_ntr_schemas,
+            records::column_stats,
+            records::load_ntr_records,
+            records::detect_ntr_delimiter,
+            records::detect_ntr_dialect,
+            records::token_frequency,
+            records::scan_formula_cells,
+            records::check_quoting,
+            records::check_last_record,
+            records::preview_ntr_typed,
+            records::align_columns,
+            records::tokenize_line,
+            transform::split_ntr_file,
+            transform::merge_ntr_files,
+            transform::filter_ntr_file,
+            transform::normalize_line_endings,
+            transform::normalized_fingerprint,
+            transform::export_ntr_csv,
+            transform::export_ntr_to_ndjson,
+            transform::export_ntr_to_markdown,
+            transform::export_line_range,
+            transform::find_control_chars,
+            transform::strip_control_chars,
+            transform::trim_fields,
+            transform::batch_convert_encoding,
+            transform::truncate_ntr_to_lines,
+            lines::map_ntr_file,
+            lines::unmap_ntr_file,
+            lines::read_ntr_range,
+            lines::count_ntr_lines,
+            lines::head_and_tail,
+            lines::read_ntr_reversed,
+            lines::longest_line,
+            lines::estimate_record_count,
+            lines::find_delimiter_offset,
+            lines::line_length_histogram,
+            lines::line_hash_index,
+            lines::char_offsets_to_bytes,
+            lines::sample_ntr_lines,
+            lines::measure_range,
+            search::search_ntr_file,
+            search::search_ntr_file_streaming,
+            search::cancel_ntr_search,
+            search::stream_ntr_file,
+            search::cancel_ntr_stream,
+            set_watch_logging,
+            get_watch_log,
+            clipboard::load_ntr_from_string,
+            clipboard::close_clipboard_file,
+            multiwatch::watch_ntr_files,
+            multiwatch::stop_multi_watch,
+            multiwatch::start_merge_watch,
+            multiwatch::stop_merge_watch,
+            snapshots::list_snapshots,
+            snapshots::restore_snapshot
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                app.state::<clipboard::ClipboardState>().cleanup();
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_bom_embedded_after_first_line() {
+        let bytes = b"\xEF\xBB\xBFRO NAME=1\n\xEF\xBB\xBFBOG NAME=2\n";
+        let mut contents = decode_bytes(bytes, &ReadOptions::default())
+            .expect("valid UTF-8")
+            .contents;
+        assert_eq!(contents, "RO NAME=1\n\u{feff}BOG NAME=2\n");
+
+        assert_eq!(strip_bom_chars(&mut contents), 1);
+        assert_eq!(contents, "RO NAME=1\nBOG NAME=2\n");
+    }
+
+    #[test]
+    fn checks_the_final_newline_on_raw_code_units() {
+        assert!(ends_with_newline(b"RO 1\r\n", UTF_8));
+        assert!(!ends_with_newline(b"RO 1", UTF_8));
+        assert!(ends_with_newline(b"R\0\n\0", UTF_16LE));
+        assert!(!ends_with_newline(b"\0\n\n\x01", UTF_16LE));
+        assert!(ends_with_newline(b"\0R\0\n", UTF_16BE));
+    }
+
+    #[test]
+    fn expands_tabs_to_the_next_stop_per_line() {
+        let expanded = expand_tabs("RO\tDN\tL\r\n\tTEE\tx", 4);
+        assert_eq!(expanded, "RO  DN  L\r\n    TEE x");
+    }
+
+    #[test]
+    fn reads_declared_encoding_from_sidecar() {
+        let path = std::env::temp_dir().join(format!("ntr-sidecar-{}.ntr", std::process::id()));
+        assert_eq!(sidecar_encoding(&path), None);
+        std::fs::write(sidecar_path(&path), " latin2\n").expect("write sidecar");
+        assert_eq!(sidecar_encoding(&path), Some(ISO_8859_2));
+        std::fs::write(sidecar_path(&path), "no such encoding").expect("write sidecar");
+        assert_eq!(sidecar_encoding(&path), None);
+        std::fs::remove_file(sidecar_path(&path)).expect("remove sidecar");
+    }
+
+    #[test]
+    fn encoding_sample_ends_on_a_line_boundary() {
+        let path = std::env::temp_dir().join(format!("ntr-sample-{}.ntr", std::process::id()));
+        let line = "RO NAME=\u{E6}\u{E6}\u{E6}\n";
+        let contents = line.repeat(WATCH_ENCODING_SAMPLE_BYTES as usize / line.len() + 1);
+        std::fs::write(&path, &contents).expect("write sample");
+        let sample = read_encoding_sample(&path, None).expect("sample reads");
+        assert!(sample.len() < WATCH_ENCODING_SAMPLE_BYTES as usize);
+        assert_eq!(sample.last(), Some(&b'\n'));
+        assert_eq!(
+            detect_encoding(&sample, DEFAULT_FALLBACK_ENCODINGS),
+            Some(UTF_8)
+        );
+        std::fs::remove_file(&path).expect("remove sample");
+    }
+
+    #[test]
+    fn refuses_writes_larger_than_the_free_space() {
+        let path = std::env::temp_dir().join(format!("ntr-space-{}.ntr", std::process::id()));
+        assert!(ensure_disk_space(&path, 1).is_ok());
+        let Err(message) = ensure_disk_space(&path, u64::MAX) else {
+            panic!("an impossible write is accepted");
+        };
+        assert!(message.starts_with("Not enough disk space"));
+    }
+
+    #[test]
+    fn only_permission_errors_make_a_file_unreadable() {
+        use std::io::{Error, ErrorKind};
+        assert_eq!(readability(File::open(file!())), Some(true));
+        assert_eq!(
+            readability(Err(Error::from(ErrorKind::PermissionDenied))),
+            Some(false)
+        );
+        assert_eq!(readability(Err(Error::from(ErrorKind::NotFound))), None);
+        assert_eq!(readability(Err(Error::other("sharing violation"))), None);
+    }
+
+    #[test]
+    fn decoded_lines_share_the_file_encoding() {
+        let path = std::env::temp_dir().join(format!("ntr-lines-{}.ntr", std::process::id()));
+        std::fs::write(&path, b"\xEF\xBB\xBFRO NAME=1\r\n\xEF\xBB\xBFBOG\n").expect("write sample");
+        let mut lines =
+            DecodedLines::open(&path, &ReadOptions::default(), "Reading").expect("sample opens");
+        let decoded: Result<Vec<String>, String> = lines.by_ref().collect();
+        assert_eq!(
+            decoded,
+            Ok(vec!["RO NAME=1".to_string(), "\u{FEFF}BOG".to_string()])
+        );
+        assert!(lines.had_bom && lines.crlf);
+
+        std::fs::write(&path, b"RO NAME=1\nBOG NAME=\xE6\n").expect("write sample");
+        let lines: Result<Vec<String>, String> =
+            DecodedLines::open(&path, &ReadOptions::default(), "Reading")
+                .expect("sample opens")
+                .collect();
+        assert_eq!(
+            lines,
+            Ok(vec!["RO NAME=1".to_string(), "BOG NAME=\u{E6}".to_string()])
+        );
+
+        let options = ReadOptions {
+            fallbacks: vec![UTF_16LE],
+            ..ReadOptions::default()
+        };
+        let utf16: Vec<u8> = "RO=\u{C6}\n"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        std::fs::write(&path, utf16).expect("write sample");
+        assert!(DecodedLines::open(&path, &options, "Reading").is_err());
+        std::fs::remove_file(&path).expect("remove sample");
+    }
+
+    #[test]
+    fn reported_layout_round_trips_bytes_on_save() {
+        let path = std::env::temp_dir().join(format!("ntr-layout-{}.ntr", std::process::id()));
+        let originals: [&[u8]; 3] = [
+            b"\xEF\xBB\xBFRO NAME=\xC3\xA6\r\nBOG NAME=2\r\n",
+            b"RO NAME=\xE6\nBOG NAME=2",
+            b"RO NAME=1\r\nBOG NAME=2\n",
+        ];
+        for original in originals {
+            std::fs::write(&path, original).expect("write sample");
+            let Ok(loaded) = read_ntr_file(&path, ReadOptions::default()) else {
+                panic!("sample fails to load");
+            };
+            let encoding = parse_encoding_label(&loaded.encoding).expect("known label");
+            let saved = encode_for_save(
+                &loaded.contents,
+                encoding,
+                loaded.had_bom,
+                loaded.line_ending,
+            )
+            .expect("sample encodes");
+            assert_eq!(saved, original);
+        }
+        std::fs::remove_file(&path).expect("remove sample");
+    }
+
+    #[test]
+    fn fallback_order_distinguishes_latin9_from_windows_1252() {
+        let euro_in_latin9 = b"RO WEIGHT=10\xA4\n";
+
+        let latin9 = detect_and_decode(euro_in_latin9, &[ISO_8859_15, WINDOWS_1252])
+            .expect("single-byte fallback decodes");
+        assert_eq!(latin9.encoding, ISO_8859_15);
+        assert_eq!(latin9.contents, "RO WEIGHT=10\u{20ac}\n");
+
+        let windows = detect_and_decode(euro_in_latin9, &[WINDOWS_1252, ISO_8859_15])
+            .expect("single-byte fallback decodes");
+        assert_eq!(windows.encoding, WINDOWS_1252);
+        assert_eq!(windows.contents, "RO WEIGHT=10\u{a4}\n");
+    }
+
+    #[test]
+    fn relative_watch_targets_match_absolute_event_paths() {
+        let cwd = std::env::current_dir().expect("current dir");
+
+        let existing = normalize_path(&resolve_absolute_path(Path::new("Cargo.toml")));
+        let event_path = cwd
+            .join("Cargo.toml")
+            .canonicalize()
+            .expect("manifest exists");
+        assert!(paths_match(&[event_path], &existing, cfg!(windows), false));
+
+        let missing = normalize_path(&resolve_absolute_path(Path::new("exports/Example.ntr")));
+        let event_path = cwd.join("exports").join("Example.ntr");
+        assert!(paths_match(&[event_path], &missing, cfg!(windows), false));
+    }
+
+    #[test]
+    fn compares_identity_only_for_matching_names_or_renames() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("ntr-identity-{}.ntr", std::process::id()));
+        let link = dir.join(format!("ntr-identity-link-{}.ntr", std::process::id()));
+        std::fs::write(&path, "RO 1\n").expect("write sample");
+        let _ = std::fs::remove_file(&link);
+        std::fs::hard_link(&path, &link).expect("link sample");
+
+        let target = normalize_path(&path);
+        assert!(!paths_match(
+            std::slice::from_ref(&link),
+            &target,
+            false,
+            false
+        ));
+        assert!(paths_match(
+            std::slice::from_ref(&link),
+            &target,
+            false,
+            true
+        ));
+        std::fs::remove_file(&link).expect("remove link");
+        std::fs::remove_file(&path).expect("remove sample");
+    }
+
+    #[test]
+    fn finds_the_filesystem_of_the_longest_matching_mount_point() {
+        let mounts = "/dev/sda1 / ext4 rw 0 0\n\
+                      //nas/exports /mnt/nas\\040share cifs rw 0 0\n\
+                      C:\\134 /mnt/c 9p rw 0 0\n";
+        let filesystem = |path: &str| mount_filesystem(Path::new(path), mounts);
+        assert_eq!(filesystem("/mnt/nas share/a.ntr").as_deref(), Some("cifs"));
+        assert_eq!(filesystem("/mnt/c/Users/a.ntr").as_deref(), Some("9p"));
+        assert_eq!(filesystem("/mnt/cd/a.ntr").as_deref(), Some("ext4"));
+    }
+
+    #[test]
+    fn case_sensitivity_is_probed_from_the_watched_volume() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("Case-Probe-{}.ntr", std::process::id()));
+        std::fs::write(&path, "RO 1\n").expect("write probe file");
+        let variant = dir.join(format!("cASE-pROBE-{}.NTR", std::process::id()));
+        let insensitive = probe_case_insensitive(&path);
+        assert_eq!(insensitive, variant.exists());
+
+        let target = normalize_path_cased(&path, insensitive);
+        assert_eq!(
+            paths_match(&[variant], &target, insensitive, false),
+            insensitive
+        );
+        assert!(paths_match(
+            std::slice::from_ref(&path),
+            &target,
+            insensitive,
+            false
+        ));
+        std::fs::remove_file(&path).expect("remove probe file");
+    }
+
+    #[test]
+    fn locates_decode_errors_by_line() {
+        let bytes = b"RO 1\nRO \xff2\n\nBOG \xfe\n";
+        let scan = scan_decode(UTF_8, bytes);
+        assert_eq!(scan.error_offsets, vec![8, 16]);
+        assert_eq!(
+            line_numbers_at(bytes, &scan.error_offsets, UTF_8),
+            vec![2, 4]
+        );
+    }
+
+    #[test]
+    fn maps_character_offsets_to_bytes_in_multibyte_files() {
+        let text = "Ærø ÅÆØ\n日本語 ✓\n𝄞 ü\n";
+        let mut bytes = b"\xEF\xBB\xBF".to_vec();
+        bytes.extend_from_slice(text.as_bytes());
+        let utf8 = decode_bytes(&bytes, &ReadOptions::default()).expect("valid UTF-8");
+        let utf16_bytes: Vec<u8> = [0xFF, 0xFE]
+            .into_iter()
+            .chain(text.encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        let utf16 = decode_bytes(&utf16_bytes, &ReadOptions::default()).expect("valid UTF-16");
+
+        let units: Vec<u16> = text.encode_utf16().collect();
+        let selection = |start: usize, end: usize| String::from_utf16(&units[start..end]).unwrap();
+        for (start, end) in [
+            (0, 3),
+            (4, 7),
+            (8, 11),
+            (14, 16),
+            (14, 18),
+            (0, units.len()),
+        ] {
+            let expected = selection(start, end);
+            for (decoded, raw) in [(&utf8, &bytes), (&utf16, &utf16_bytes)] {
+                let from = decoded.byte_offset_of(start).unwrap() as usize;
+                let to = decoded.byte_offset_of(end).unwrap() as usize;
+                let (slice, _) = decoded.encoding.decode_without_bom_handling(&raw[from..to]);
+                assert_eq!(slice, expected);
+            }
+        }
+        assert!(utf8.byte_offset_of(15).is_err());
+    }
+
+    #[test]
+    fn diffs_the_editor_buffer_against_the_disk_text() {
+        let on_disk = "\u{feff}RO 1\r\nRO 2\r\nRO 3\r\n";
+        let unchanged = disk_diff(on_disk, "RO 1\nRO 2\nRO 3\n");
+        assert!(unchanged.on_disk.is_empty() && unchanged.in_memory.is_empty());
+
+        let diff = disk_diff(on_disk, "RO 1\nRO X\nRO 3\nRO 4\n");
+        let range = |start, count| LineRange { start, count };
+        assert_eq!(diff.on_disk, [range(2, 1)]);
+        assert_eq!(diff.in_memory, [range(2, 1), range(4, 1)]);
+    }
+
+    #[test]
+    fn buffers_appends_below_the_growth_threshold() {
+        let path = std::env::temp_dir().join(format!("ntr-growth-{}.ntr", std::process::id()));
+        std::fs::write(&path, "one\n").unwrap();
+        let append = |text: &str| {
+            let mut file = std::fs::OpenOptions::new()
+                .append(true)
+                .open(&path)
+                .unwrap();
+            std::io::Write::write_all(&mut file, text.as_bytes()).unwrap();
+        };
+
+        let (sender, receiver) = mpsc::channel::<()>();
+        let (emitted, appends) = mpsc::channel::<String>();
+        let worker_path = path.clone();
+        let worker = thread::spawn(move || {
+            let threshold = GrowthThreshold {
+                min_bytes: 10,
+                flush_after: Duration::from_secs(1),
+            };
+            follow_appends(
+                &receiver,
+                &worker_path,
+                4,
+                None,
+                Some(&threshold),
+                ReadOptions::default,
+                |contents| emitted.send(contents).unwrap(),
+            );
+        });
+
+        append("two\n");
+        sender.send(()).unwrap();
+        thread::sleep(DIFF_DEBOUNCE * 2);
+        append("three\nfour\n");
+        sender.send(()).unwrap();
+        let wait = || appends.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(wait(), "two\nthree\nfour\n");
+
+        append("five\n");
+        sender.send(()).unwrap();
+        assert!(appends.recv_timeout(DIFF_DEBOUNCE * 2).is_err());
+        assert_eq!(wait(), "five\n");
+
+        drop(sender);
+        worker.join().unwrap();
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn scans_sequences_split_across_read_chunks() {
+        let mut bytes = vec![b'a'; SCAN_CHUNK_BYTES - 1];
+        bytes.extend_from_slice("\u{E6}\n".as_bytes());
+        bytes.resize(2 * SCAN_CHUNK_BYTES - 1, b'a');
+        bytes.extend_from_slice(b"\xE6b\n");
+        let scan = scan_decode(UTF_8, &bytes);
+        assert_eq!(scan.error_offsets, [2 * SCAN_CHUNK_BYTES - 1]);
+        assert_eq!(scan.non_ascii_count, 1);
+    }
+
+    #[test]
+    fn sorts_names_naturally() {
+        let mut names = ["RO 10.ntr", "ro 2.ntr", "RO 02b.ntr", "BOG.ntr", "RO 1.ntr"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            names,
+            ["BOG.ntr", "RO 1.ntr", "ro 2.ntr", "RO 02b.ntr", "RO 10.ntr"]
+        );
+    }
+
+    #[test]
+    fn transcodes_with_substitution_for_unmappable_characters() {
+        let (bytes, lossy) = transcode_contents("Ø", UTF_16BE).unwrap();
+        assert_eq!((bytes, lossy), (vec![0x00, 0xD8], false));
+        let (bytes, lossy) = transcode_contents("Grüße", WINDOWS_1252).unwrap();
+        assert_eq!((bytes, lossy), (b"Gr\xFC\xDFe".to_vec(), false));
+        let (bytes, lossy) = transcode_contents("Ж", WINDOWS_1252).unwrap();
+        assert_eq!((bytes, lossy), (b"&#1046;".to_vec(), true));
+    }
+
+    #[test]
+    fn reports_reserved_names_and_illegal_characters_in_windows_paths() {
+        assert_eq!(
+            windows_path_problem(Path::new("exports/con.ntr")).as_deref(),
+            Some("reserved name CON")
+        );
+        assert_eq!(
+            windows_path_problem(Path::new("exports/LPT1")).as_deref(),
+            Some("reserved name LPT1")
+        );
+        assert!(windows_path_problem(Path::new("exports/a?b.ntr")).is_some());
+        assert_eq!(windows_path_problem(Path::new("exports/console.ntr")), None);
+    }
+
+    #[test]
+    fn lists_every_save_path_problem() {
+        assert!(save_path_problems(Path::new("/data/export.ntr"), false).is_empty());
+        assert_eq!(
+            save_path_problems(Path::new("/data/aux.ntr."), true),
+            vec![
+                "file name \"aux.ntr.\" ends with a dot or space".to_string(),
+                "reserved name AUX".to_string(),
+            ]
+        );
+        let long = format!("/data/{}.ntr", "x".repeat(300));
+        assert_eq!(save_path_problems(Path::new(&long), false).len(), 1);
+        assert_eq!(save_path_problems(Path::new(&long), true).len(), 2);
+    }
 }
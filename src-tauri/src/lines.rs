@@ -0,0 +1,991 @@
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use memmap2::Mmap;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::{BufRead, BufReader, Read, Seek, SeekFrom},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    bom_len_for, decode_with_encoding, ensure_byte_lines, file_encoding, line_encoding,
+    normalize_path, read_decoded_text, read_encoding_sample, read_options_for,
+    resolve_absolute_path, resolve_input_path, settings::SettingsState, DecodedLines, ReadOptions,
+};
+
+#[derive(Default)]
+pub(crate) struct MappedFileState {
+    pub(crate) inner: Mutex<HashMap<String, MappedFile>>,
+}
+
+pub(crate) struct MappedFile {
+    /// Shared so readers can keep using the map after releasing the state lock.
+    pub(crate) map: Arc<Mmap>,
+    pub(crate) len: u64,
+    /// The fallback chain the whole map was last detected with and the encoding found, so
+    /// paging through a large file detects it once per map instead of once per read.
+    detected: Option<(Vec<&'static Encoding>, &'static Encoding)>,
+}
+
+pub(crate) fn map_file(path: &Path) -> Result<MappedFile, String> {
+    let file = File::open(path).map_err(|err| format!("Failed to open file: {err}"))?;
+    let len = file
+        .metadata()
+        .map_err(|err| format!("Failed to read file metadata: {err}"))?
+        .len();
+    // SAFETY: the map is only ever read, but nothing stops another process from changing the
+    // file while it is mapped. Same-size rewrites just show through the shared mapping. A
+    // shrink is only noticed on the next `current_map`, which compares sizes without any lock
+    // on the file, so a reader still holding the old map (or racing that check) can touch
+    // pages past the new end and fault with SIGBUS. That risk is accepted only for files the
+    // user explicitly maps with `map_ntr_file`; one-off reads of live files stream through a
+    // buffer instead.
+    let map = unsafe { Mmap::map(&file) }.map_err(|err| format!("Failed to map file: {err}"))?;
+    Ok(MappedFile {
+        map: Arc::new(map),
+        len,
+        detected: None,
+    })
+}
+
+pub(crate) fn mapped_file_key(path: &Path) -> String {
+    normalize_path(&resolve_absolute_path(path))
+}
+
+/// The registered map of `path`, if any, replaced first when the file size changed since it
+/// was mapped. The state lock is released before returning.
+fn current_map(state: &MappedFileState, path: &Path) -> Result<Option<Arc<Mmap>>, String> {
+    let mut guard = state.inner.lock().expect("mapped file state poisoned");
+    let Some(mapped) = guard.get_mut(&mapped_file_key(path)) else {
+        return Ok(None);
+    };
+    let current_len = std::fs::metadata(path)
+        .map_err(|err| format!("Failed to read file metadata: {err}"))?
+        .len();
+    if current_len != mapped.len {
+        *mapped = map_file(path)?;
+    }
+    Ok(Some(mapped.map.clone()))
+}
+
+/// The encoding of a mapped file: the chosen one, otherwise the one detected over the whole
+/// `map`, which is cached on the entry. Detection runs without holding the state lock.
+fn mapped_encoding(
+    state: &MappedFileState,
+    path: &Path,
+    map: &Arc<Mmap>,
+    options: &ReadOptions,
+) -> &'static Encoding {
+    if let Some(encoding) = options.encoding {
+        return encoding;
+    }
+    let key = mapped_file_key(path);
+    let fallbacks = options.fallback_chain();
+    let same_map = |mapped: &&mut MappedFile| Arc::ptr_eq(&mapped.map, map);
+    {
+        let mut guard = state.inner.lock().expect("mapped file state poisoned");
+        let cached = guard
+            .get_mut(&key)
+            .filter(same_map)
+            .and_then(|mapped| mapped.detected.as_ref())
+            .filter(|(chain, _)| chain == fallbacks)
+            .map(|&(_, encoding)| encoding);
+        if let Some(encoding) = cached {
+            return encoding;
+        }
+    }
+    let encoding = file_encoding(map, options);
+    let mut guard = state.inner.lock().expect("mapped file state poisoned");
+    if let Some(mapped) = guard.get_mut(&key).filter(same_map) {
+        mapped.detected = Some((fallbacks.to_vec(), encoding));
+    }
+    encoding
+}
+
+/// Runs `f` over the file's bytes, using the memory map when one is registered for `path`
+/// and falling back to a plain read otherwise. The state lock is released before `f` runs.
+pub(crate) fn with_ntr_bytes<T>(
+    state: &MappedFileState,
+    path: &Path,
+    f: impl FnOnce(&[u8]) -> Result<T, String>,
+) -> Result<T, String> {
+    if let Some(map) = current_map(state, path)? {
+        return f(&map);
+    }
+    let bytes = std::fs::read(path).map_err(|err| format!("Failed to read file bytes: {err}"))?;
+    f(&bytes)
+}
+
+/// Bytes read on either side of a range that is not mapped, enough for `align_to_chars` to
+/// reach the boundary of a UTF-8 sequence or a UTF-16 surrogate pair.
+const ALIGN_MARGIN_BYTES: usize = 4;
+
+/// A byte range of a file widened by `align_to_chars`, with the file's encoding.
+struct AlignedRange {
+    /// The range asked for, clamped to the file.
+    requested: (usize, usize),
+    start: usize,
+    end: usize,
+    bytes: Vec<u8>,
+    encoding: &'static Encoding,
+}
+
+/// Reads `start..end` clamped to the file, kept clear of a BOM and widened so it never splits
+/// a character. A mapped file is sliced from its map. Otherwise only the range and a few bytes
+/// around it are read, with the encoding judged from a `read_encoding_sample`.
+fn read_aligned_range(
+    state: &MappedFileState,
+    path: &Path,
+    options: &ReadOptions,
+    start: u64,
+    end: u64,
+) -> Result<AlignedRange, String> {
+    let clamp = |offset: u64, len: usize| usize::try_from(offset).unwrap_or(usize::MAX).min(len);
+    let check = |requested: (usize, usize)| {
+        if requested.0 > requested.1 {
+            return Err("Start offset must not exceed end offset".to_string());
+        }
+        Ok(requested)
+    };
+
+    if let Some(map) = current_map(state, path)? {
+        let encoding = mapped_encoding(state, path, &map, options);
+        let requested = check((clamp(start, map.len()), clamp(end, map.len())))?;
+        let bom_len = bom_len_for(encoding, &map);
+        let (start, end) = align_to_chars(&map, requested.0, requested.1, bom_len, encoding);
+        return Ok(AlignedRange {
+            requested,
+            start,
+            end,
+            bytes: map[start..end].to_vec(),
+            encoding,
+        });
+    }
+
+    let sample = read_encoding_sample(path, options.encoding).ok_or("Failed to read file")?;
+    let encoding = file_encoding(&sample, options);
+    let bom_len = bom_len_for(encoding, &sample);
+    let read_error = |err: std::io::Error| format!("Failed to read file: {err}");
+    let mut file = File::open(path).map_err(|err| format!("Failed to open file: {err}"))?;
+    let len = usize::try_from(file.metadata().map_err(read_error)?.len()).unwrap_or(usize::MAX);
+    let requested = check((clamp(start, len), clamp(end, len)))?;
+
+    let from = requested.0.max(bom_len);
+    let to = requested.1.max(from);
+    let mut window_start = from.saturating_sub(ALIGN_MARGIN_BYTES).max(bom_len);
+    // Keeps UTF-16 code units at the same parity as in the file.
+    window_start -= (window_start - bom_len) % 2;
+    let window_end = to.saturating_add(ALIGN_MARGIN_BYTES).min(len);
+    let mut window = vec![0; window_end - window_start];
+    file.seek(SeekFrom::Start(window_start as u64))
+        .and_then(|_| file.read_exact(&mut window))
+        .map_err(read_error)?;
+
+    let (start, end) = align_to_chars(&window, from - window_start, to - window_start, 0, encoding);
+    Ok(AlignedRange {
+        requested,
+        start: window_start + start,
+        end: window_start + end,
+        bytes: window[start..end].to_vec(),
+        encoding,
+    })
+}
+
+#[tauri::command]
+pub(crate) fn map_ntr_file(
+    state: tauri::State<MappedFileState>,
+    path: String,
+) -> Result<u64, String> {
+    let resolved = &resolve_input_path(&path);
+    if !resolved.is_file() {
+        return Err("Path does not point to a file".into());
+    }
+    let mapped = map_file(resolved)?;
+    let len = mapped.len;
+    let mut guard = state.inner.lock().expect("mapped file state poisoned");
+    guard.insert(mapped_file_key(resolved), mapped);
+    Ok(len)
+}
+
+#[tauri::command]
+pub(crate) fn unmap_ntr_file(state: tauri::State<MappedFileState>, path: String) -> bool {
+    let mut guard = state.inner.lock().expect("mapped file state poisoned");
+    guard
+        .remove(&mapped_file_key(&resolve_input_path(&path)))
+        .is_some()
+}
+
+/// Decodes `length` bytes from `offset` with the encoding of the whole file. The range is
+/// clamped to the file, kept clear of a BOM and widened like in `measure_range` so it never
+/// starts or ends inside a character; see `read_aligned_range` for how much is read.
+#[tauri::command]
+pub(crate) fn read_ntr_range(
+    settings: tauri::State<SettingsState>,
+    state: tauri::State<MappedFileState>,
+    path: String,
+    offset: u64,
+    length: u64,
+) -> Result<String, String> {
+    let resolved = resolve_input_path(&path);
+    let options = read_options_for(&settings, &resolved);
+    let end = offset.saturating_add(length);
+    let range = read_aligned_range(&state, &resolved, &options, offset, end)?;
+    Ok(decode_with_encoding(&range.bytes, range.encoding)?.contents)
+}
+
+/// Converts a frontend selection (`start_char..end_char`, in UTF-16 code units of the decoded
+/// text) into the byte range it occupies on disk.
+#[tauri::command]
+pub(crate) fn char_offsets_to_bytes(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    start_char: usize,
+    end_char: usize,
+) -> Result<(u64, u64), String> {
+    if start_char > end_char {
+        return Err("Start offset must not exceed end offset".into());
+    }
+    let decoded = read_decoded_text(&settings, &resolve_input_path(&path))?;
+    Ok((
+        decoded.byte_offset_of(start_char)?,
+        decoded.byte_offset_of(end_char)?,
+    ))
+}
+
+#[derive(Serialize)]
+pub(crate) struct RangeMeasurement {
+    /// The measured range after alignment to character boundaries.
+    start_byte: u64,
+    end_byte: u64,
+    /// True when the requested range was widened to avoid splitting a character.
+    adjusted: bool,
+    line_count: u64,
+    char_count: usize,
+    byte_count: u64,
+}
+
+/// Measures `start_byte..end_byte` for a selection status display. The range is clamped to the
+/// file, kept clear of a BOM and widened outwards so it never splits a UTF-8 sequence or a
+/// UTF-16 code unit or surrogate pair. Legacy multi-byte encodings are measured as given.
+#[tauri::command]
+pub(crate) fn measure_range(
+    settings: tauri::State<SettingsState>,
+    state: tauri::State<MappedFileState>,
+    path: String,
+    start_byte: u64,
+    end_byte: u64,
+) -> Result<RangeMeasurement, String> {
+    let resolved = resolve_input_path(&path);
+    let options = read_options_for(&settings, &resolved);
+    let range = read_aligned_range(&state, &resolved, &options, start_byte, end_byte)?;
+    let (text, _) = range.encoding.decode_without_bom_handling(&range.bytes);
+    Ok(RangeMeasurement {
+        start_byte: range.start as u64,
+        end_byte: range.end as u64,
+        adjusted: (range.start, range.end) != range.requested,
+        line_count: count_lines(text.as_bytes()),
+        char_count: text.chars().count(),
+        byte_count: range.bytes.len() as u64,
+    })
+}
+
+fn align_to_chars(
+    bytes: &[u8],
+    start: usize,
+    end: usize,
+    bom_len: usize,
+    encoding: &'static Encoding,
+) -> (usize, usize) {
+    let start = start.max(bom_len);
+    let end = end.max(start);
+    if encoding == UTF_8 {
+        let is_continuation = |index: usize| bytes.get(index).is_some_and(|b| b & 0xC0 == 0x80);
+        let mut start = start;
+        while start > bom_len && is_continuation(start) {
+            start -= 1;
+        }
+        let mut end = end.max(start);
+        while end < bytes.len() && is_continuation(end) {
+            end += 1;
+        }
+        (start, end)
+    } else if encoding == UTF_16LE || encoding == UTF_16BE {
+        let unit = |index: usize| {
+            let pair = [*bytes.get(index)?, *bytes.get(index + 1)?];
+            Some(if encoding == UTF_16LE {
+                u16::from_le_bytes(pair)
+            } else {
+                u16::from_be_bytes(pair)
+            })
+        };
+        let is_low_surrogate =
+            |index: usize| unit(index).is_some_and(|unit| (0xDC00..0xE000).contains(&unit));
+        let mut start = start - (start - bom_len) % 2;
+        if start >= bom_len + 2 && is_low_surrogate(start) {
+            start -= 2;
+        }
+        let mut end = end.max(start);
+        end = (end + (end - bom_len) % 2).min(bytes.len());
+        if is_low_surrogate(end) {
+            end = (end + 2).min(bytes.len());
+        }
+        (start, end)
+    } else {
+        (start, end)
+    }
+}
+
+#[tauri::command]
+pub(crate) fn count_ntr_lines(
+    state: tauri::State<MappedFileState>,
+    path: String,
+) -> Result<u64, String> {
+    with_ntr_bytes(&state, &resolve_input_path(&path), |bytes| {
+        Ok(count_lines(bytes))
+    })
+}
+
+const TAIL_READ_CHUNK: u64 = 64 * 1024;
+
+#[derive(Serialize)]
+pub(crate) struct HeadAndTail {
+    head: Vec<String>,
+    tail: Vec<String>,
+    total_lines: u64,
+    /// True when the file is short enough that the head and tail share lines.
+    overlapping: bool,
+}
+
+/// Reads the head in one forward pass (which also counts lines) and the tail by seeking
+/// backwards from the end in chunks. Both are decoded with the `line_encoding` of the whole
+/// file, and line splitting works on raw `\n` bytes, so UTF-16 files are rejected.
+#[tauri::command]
+pub(crate) fn head_and_tail(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    head_lines: usize,
+    tail_lines: usize,
+) -> Result<HeadAndTail, String> {
+    let resolved = resolve_input_path(&path);
+    let encoding = line_encoding(
+        &resolved,
+        &read_options_for(&settings, &resolved),
+        "Reading",
+    )?;
+    let read_error = |err: std::io::Error| format!("Failed to read file: {err}");
+    let mut file = File::open(&resolved).map_err(|err| format!("Failed to open file: {err}"))?;
+    let len = file.metadata().map_err(read_error)?.len();
+
+    let mut head = Vec::new();
+    let mut newlines = 0u64;
+    let mut last_byte = None;
+    {
+        let mut reader = BufReader::new(&file);
+        loop {
+            let chunk = reader.fill_buf().map_err(read_error)?;
+            if chunk.is_empty() {
+                break;
+            }
+            let seen = usize::try_from(newlines).unwrap_or(usize::MAX);
+            let wanted = head_lines.saturating_sub(seen);
+            if wanted > 0 {
+                let take = chunk
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, &byte)| byte == b'\n')
+                    .nth(wanted - 1)
+                    .map_or(chunk.len(), |(index, _)| index + 1);
+                head.extend_from_slice(&chunk[..take]);
+            }
+            newlines += chunk.iter().filter(|&&byte| byte == b'\n').count() as u64;
+            last_byte = chunk.last().copied();
+            let consumed = chunk.len();
+            reader.consume(consumed);
+        }
+    }
+    let total_lines = match last_byte {
+        Some(b'\n') | None => newlines,
+        Some(_) => newlines + 1,
+    };
+
+    let tail = read_tail(&mut file, len, tail_lines).map_err(read_error)?;
+    let decode_lines = |bytes: &[u8]| -> Result<Vec<String>, String> {
+        let decoded = decode_with_encoding(bytes, encoding)?;
+        Ok(decoded.contents.lines().map(str::to_string).collect())
+    };
+    Ok(HeadAndTail {
+        head: decode_lines(&head)?,
+        tail: decode_lines(&tail)?,
+        total_lines,
+        overlapping: (head_lines as u64).saturating_add(tail_lines as u64) > total_lines,
+    })
+}
+
+/// SplitMix64: a tiny, seedable generator that is plenty for picking sample lines.
+struct SampleRng(u64);
+
+impl SampleRng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A value in `0..bound`; the modulo bias is negligible for line counts.
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct SampledLine {
+    /// 1-based line number.
+    line: u64,
+    text: String,
+}
+
+/// Picks `count` lines uniformly at random in one streaming pass (reservoir sampling) and
+/// returns them in file order. The same `seed` always yields the same sample for the same
+/// file. Lines are read as `DecodedLines`, so UTF-16 files are rejected.
+#[tauri::command]
+pub(crate) fn sample_ntr_lines(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    count: usize,
+    seed: Option<u64>,
+) -> Result<Vec<SampledLine>, String> {
+    let resolved = resolve_input_path(&path);
+    let lines = DecodedLines::open(
+        &resolved,
+        &read_options_for(&settings, &resolved),
+        "Sampling",
+    )?;
+    let seed = seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos() as u64)
+            .unwrap_or_default()
+    });
+    let mut rng = SampleRng(seed);
+
+    let mut reservoir: Vec<SampledLine> = Vec::with_capacity(count);
+    let mut seen = 0u64;
+    for text in lines {
+        let text = text?;
+        seen += 1;
+        if reservoir.len() < count {
+            reservoir.push(SampledLine { line: seen, text });
+        } else {
+            let slot = rng.below(seen);
+            if let Some(entry) = usize::try_from(slot)
+                .ok()
+                .and_then(|slot| reservoir.get_mut(slot))
+            {
+                *entry = SampledLine { line: seen, text };
+            }
+        }
+    }
+
+    reservoir.sort_by_key(|sampled| sampled.line);
+    Ok(reservoir)
+}
+
+/// The last `max_lines` lines, newest first, for reverse-chronological log views. Reads
+/// backwards from the end like `head_and_tail`, so only the tail is decoded, with the
+/// `line_encoding` of the whole file; a missing final newline does not produce an empty first
+/// line. UTF-16 files are rejected.
+#[tauri::command]
+pub(crate) fn read_ntr_reversed(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    max_lines: usize,
+) -> Result<Vec<String>, String> {
+    let resolved = resolve_input_path(&path);
+    reversed_lines(
+        &resolved,
+        &read_options_for(&settings, &resolved),
+        max_lines,
+    )
+}
+
+fn reversed_lines(
+    path: &Path,
+    options: &ReadOptions,
+    max_lines: usize,
+) -> Result<Vec<String>, String> {
+    let encoding = line_encoding(path, options, "Reading")?;
+    let read_error = |err: std::io::Error| format!("Failed to read file: {err}");
+    let mut file = File::open(path).map_err(|err| format!("Failed to open file: {err}"))?;
+    let len = file.metadata().map_err(read_error)?.len();
+
+    let tail = read_tail(&mut file, len, max_lines).map_err(read_error)?;
+    let decoded = decode_with_encoding(&tail, encoding)?;
+    Ok(decoded.contents.lines().rev().map(str::to_string).collect())
+}
+
+/// Returns the bytes of the last `lines` lines of `file`, reading backwards from `len`.
+fn read_tail(file: &mut File, len: u64, lines: usize) -> std::io::Result<Vec<u8>> {
+    if lines == 0 {
+        return Ok(Vec::new());
+    }
+    let line_start = |bytes: &[u8]| {
+        let body = bytes.strip_suffix(b"\n").unwrap_or(bytes);
+        body.iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, &byte)| byte == b'\n')
+            .nth(lines - 1)
+            .map(|(index, _)| index + 1)
+    };
+
+    let mut start = len;
+    let mut buffer = Vec::new();
+    while start > 0 {
+        if let Some(cut) = line_start(&buffer) {
+            return Ok(buffer.split_off(cut));
+        }
+        let next = start.saturating_sub(TAIL_READ_CHUNK);
+        let mut chunk = vec![0; (start - next) as usize];
+        file.seek(SeekFrom::Start(next))?;
+        file.read_exact(&mut chunk)?;
+        chunk.extend_from_slice(&buffer);
+        buffer = chunk;
+        start = next;
+    }
+    let cut = line_start(&buffer).unwrap_or(0);
+    Ok(buffer.split_off(cut))
+}
+
+pub(crate) fn count_lines(bytes: &[u8]) -> u64 {
+    let newlines = bytes.iter().filter(|&&byte| byte == b'\n').count() as u64;
+    match bytes.last() {
+        Some(b'\n') | None => newlines,
+        Some(_) => newlines + 1,
+    }
+}
+
+/// Leading bytes `estimate_record_count` reads; files up to this size are counted exactly.
+const RECORD_SAMPLE_BYTES: u64 = 1024 * 1024;
+
+/// Non-overlapping occurrences of `separator` in `bytes`, and the offset just past the last.
+fn scan_separators(bytes: &[u8], separator: &[u8]) -> (u64, usize) {
+    let (mut count, mut end, mut index) = (0, 0, 0);
+    while index + separator.len() <= bytes.len() {
+        if bytes[index..].starts_with(separator) {
+            count += 1;
+            index += separator.len();
+            end = index;
+        } else {
+            index += 1;
+        }
+    }
+    (count, end)
+}
+
+#[derive(Serialize)]
+pub(crate) struct RecordCountEstimate {
+    records: u64,
+    exact: bool,
+    file_size: u64,
+    /// Says how `records` was extrapolated; `None` for an exact count.
+    note: Option<String>,
+}
+
+/// Counts records in files up to `RECORD_SAMPLE_BYTES` and extrapolates for larger ones from
+/// the average record length in that leading sample, so the UI gets a size figure for a huge
+/// file without a full pass. Records end at `delimiter`, a newline by default; a final record
+/// without one still counts. Raw bytes are scanned, so UTF-16 files are rejected.
+#[tauri::command(async)]
+pub(crate) fn estimate_record_count(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    delimiter: Option<String>,
+) -> Result<RecordCountEstimate, String> {
+    let separator = delimiter.unwrap_or_else(|| "\n".into());
+    if separator.is_empty() {
+        return Err("Record delimiter must not be empty".into());
+    }
+    let resolved = resolve_input_path(&path);
+    let file = File::open(&resolved).map_err(|err| format!("Failed to open file: {err}"))?;
+    let file_size = file
+        .metadata()
+        .map_err(|err| format!("Failed to read file metadata: {err}"))?
+        .len();
+    let mut sample = Vec::new();
+    file.take(RECORD_SAMPLE_BYTES)
+        .read_to_end(&mut sample)
+        .map_err(|err| format!("Failed to read file: {err}"))?;
+    ensure_byte_lines(
+        &read_options_for(&settings, &resolved),
+        &sample,
+        "Estimating records in",
+    )?;
+
+    let (count, end) = scan_separators(&sample, separator.as_bytes());
+    if file_size <= RECORD_SAMPLE_BYTES {
+        return Ok(RecordCountEstimate {
+            records: count + u64::from(end < sample.len()),
+            exact: true,
+            file_size,
+            note: None,
+        });
+    }
+    let records = if count == 0 {
+        1
+    } else {
+        (file_size as f64 / (end as f64 / count as f64)).round() as u64
+    };
+    Ok(RecordCountEstimate {
+        records,
+        exact: false,
+        file_size,
+        note: Some(format!(
+            "Approximate: extrapolated from the average record length in the first {} KiB",
+            sample.len() / 1024
+        )),
+    })
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+/// Line hashes keep 53 bits so they survive the trip into JavaScript numbers exactly.
+const LINE_HASH_MASK: u64 = (1 << 53) - 1;
+
+/// 64-bit FNV-1a of the line's UTF-8 text (without its line ending), truncated to 53 bits.
+/// FNV-1a is fixed by specification, so hashes stay comparable across runs and builds, unlike
+/// the randomly seeded std hasher.
+fn hash_line(line: &str) -> u64 {
+    let hash = line.bytes().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    });
+    hash & LINE_HASH_MASK
+}
+
+pub(crate) fn line_hashes(contents: &str) -> Vec<u64> {
+    contents.lines().map(hash_line).collect()
+}
+
+/// Streams the file and returns `hash_line` of every decoded line, so two versions can be
+/// compared line by line without holding either text. Diff-mode watches keep the same index
+/// as their snapshot.
+#[tauri::command]
+pub(crate) fn line_hash_index(
+    settings: tauri::State<SettingsState>,
+    path: String,
+) -> Result<Vec<u64>, String> {
+    let resolved = resolve_input_path(&path);
+    let options = read_options_for(&settings, &resolved);
+    DecodedLines::open(&resolved, &options, "Hashing")?
+        .map(|line| line.map(|line| hash_line(&line)))
+        .collect()
+}
+
+/// Byte offset of the `occurrence`-th (1-based) `byte` in `reader`, or `None` when there are
+/// fewer occurrences.
+fn nth_byte_offset(
+    mut reader: impl BufRead,
+    byte: u8,
+    occurrence: u64,
+) -> std::io::Result<Option<u64>> {
+    let mut remaining = occurrence;
+    let mut offset = 0u64;
+    loop {
+        let chunk = reader.fill_buf()?;
+        if chunk.is_empty() {
+            return Ok(None);
+        }
+        let mut matches = chunk
+            .iter()
+            .enumerate()
+            .filter(|(_, &candidate)| candidate == byte);
+        let found = usize::try_from(remaining - 1)
+            .ok()
+            .and_then(|skip| matches.nth(skip))
+            .map(|(index, _)| index);
+        if let Some(index) = found {
+            return Ok(Some(offset + index as u64));
+        }
+        remaining -= chunk.iter().filter(|&&candidate| candidate == byte).count() as u64;
+        let consumed = chunk.len();
+        offset += consumed as u64;
+        reader.consume(consumed);
+    }
+}
+
+/// Streams the file's raw bytes for the `occurrence`-th (1-based) `delimiter_byte`, so a range
+/// can be cut out of a huge file without parsing it. No decoding happens: the byte is matched
+/// as-is, which only makes sense for single-byte delimiters in ASCII-compatible encodings.
+#[tauri::command(async)]
+pub(crate) fn find_delimiter_offset(
+    path: String,
+    delimiter_byte: u8,
+    occurrence: u64,
+) -> Result<u64, String> {
+    if occurrence == 0 {
+        return Err("Occurrence must be at least 1".into());
+    }
+    let file = File::open(resolve_input_path(&path))
+        .map_err(|err| format!("Failed to open file: {err}"))?;
+    nth_byte_offset(BufReader::new(file), delimiter_byte, occurrence)
+        .map_err(|err| format!("Failed to read file: {err}"))?
+        .ok_or_else(|| format!("File has fewer than {occurrence} occurrences of the delimiter"))
+}
+
+/// Characters of the longest line returned by `longest_line`.
+const LONGEST_LINE_PREVIEW_CHARS: usize = 500;
+
+#[derive(Serialize)]
+pub(crate) struct LongestLine {
+    /// 1-based line number; the first of several equally long lines.
+    line: usize,
+    /// Length in characters, without the line ending.
+    length: usize,
+    /// The line cut to `LONGEST_LINE_PREVIEW_CHARS` characters.
+    text: String,
+    truncated: bool,
+}
+
+/// Streams the file and decodes each line separately so lengths count characters, not bytes.
+/// Returns `None` for an empty file.
+#[tauri::command]
+pub(crate) fn longest_line(
+    settings: tauri::State<SettingsState>,
+    path: String,
+) -> Result<Option<LongestLine>, String> {
+    let resolved = resolve_input_path(&path);
+    find_longest_line(&resolved, &read_options_for(&settings, &resolved))
+}
+
+fn find_longest_line(path: &Path, options: &ReadOptions) -> Result<Option<LongestLine>, String> {
+    let mut longest: Option<LongestLine> = None;
+    for (index, line) in DecodedLines::open(path, options, "Reading")?.enumerate() {
+        let line = line?;
+        let number = index + 1;
+        let length = line.chars().count();
+        if longest
+            .as_ref()
+            .is_none_or(|longest| length > longest.length)
+        {
+            longest = Some(LongestLine {
+                line: number,
+                length,
+                text: line.chars().take(LONGEST_LINE_PREVIEW_CHARS).collect(),
+                truncated: length > LONGEST_LINE_PREVIEW_CHARS,
+            });
+        }
+    }
+    Ok(longest)
+}
+
+#[derive(Serialize)]
+struct LengthBucket {
+    /// Shortest length in the bucket, in characters.
+    start: usize,
+    /// One past the longest length in the bucket.
+    end: usize,
+    count: usize,
+}
+
+#[derive(Serialize)]
+pub(crate) struct LineLengthHistogram {
+    /// Non-empty buckets only, shortest first, so one extreme outlier does not produce a long
+    /// run of empty buckets.
+    buckets: Vec<LengthBucket>,
+    lines: usize,
+    min: Option<usize>,
+    max: Option<usize>,
+    /// The lower median for an even number of lines, so it is always a length that occurs.
+    median: Option<usize>,
+}
+
+/// Groups line lengths (characters, without the line ending) into buckets of `bucket_size`.
+/// Exact lengths are tallied first, so memory grows with the number of distinct lengths, not
+/// lines. Lines are read and decoded one at a time like in `longest_line`.
+#[tauri::command]
+pub(crate) fn line_length_histogram(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    bucket_size: usize,
+) -> Result<LineLengthHistogram, String> {
+    if bucket_size == 0 {
+        return Err("Bucket size must be greater than zero".into());
+    }
+    let resolved = resolve_input_path(&path);
+    let options = read_options_for(&settings, &resolved);
+
+    let mut lengths: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut lines = 0;
+    for line in DecodedLines::open(&resolved, &options, "Measuring")? {
+        let line = line?;
+        lines += 1;
+        *lengths.entry(line.chars().count()).or_default() += 1;
+    }
+
+    let mut buckets: Vec<LengthBucket> = Vec::new();
+    for (&length, &count) in &lengths {
+        let start = length / bucket_size * bucket_size;
+        match buckets.last_mut() {
+            Some(bucket) if bucket.start == start => bucket.count += count,
+            _ => buckets.push(LengthBucket {
+                start,
+                end: start + bucket_size,
+                count,
+            }),
+        }
+    }
+    let mut seen = 0;
+    let median = lengths.iter().find_map(|(&length, &count)| {
+        seen += count;
+        (seen * 2 >= lines).then_some(length)
+    });
+    Ok(LineLengthHistogram {
+        buckets,
+        lines,
+        min: lengths.keys().next().copied(),
+        max: lengths.keys().next_back().copied(),
+        median,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_hashes_are_stable_and_ignore_line_endings() {
+        assert_eq!(hash_line(""), FNV_OFFSET_BASIS & LINE_HASH_MASK);
+        assert_eq!(hash_line("a"), 0xaf63dc4c8601ec8c & LINE_HASH_MASK);
+        assert_eq!(line_hashes("RO 1\r\nRO 2\n"), line_hashes("RO 1\nRO 2"));
+    }
+
+    #[test]
+    fn measures_the_longest_line_in_characters() {
+        let path = std::env::temp_dir().join(format!("ntr-longest-{}.ntr", std::process::id()));
+        let mega = "\u{E6}".repeat(LONGEST_LINE_PREVIEW_CHARS + 1);
+        std::fs::write(&path, format!("RO 1234567\n{mega}\nRO {mega}")).expect("write sample");
+        let longest = find_longest_line(&path, &ReadOptions::default()).expect("scan");
+        std::fs::write(&path, "").expect("truncate sample");
+        let empty = find_longest_line(&path, &ReadOptions::default()).expect("scan");
+        std::fs::remove_file(&path).expect("remove sample");
+
+        let longest = longest.expect("longest line");
+        assert_eq!(longest.line, 3);
+        assert_eq!(longest.length, LONGEST_LINE_PREVIEW_CHARS + 4);
+        assert_eq!(longest.text.chars().count(), LONGEST_LINE_PREVIEW_CHARS);
+        assert!(longest.truncated);
+        assert!(empty.is_none());
+    }
+
+    #[test]
+    fn reads_the_last_lines_newest_first() {
+        let path = std::env::temp_dir().join(format!("ntr-reversed-{}.ntr", std::process::id()));
+        std::fs::write(&path, b"RO 1\nRO 2\nRO \xE63").expect("write sample");
+        let reversed = reversed_lines(&path, &ReadOptions::default(), 2).expect("read");
+        let everything = reversed_lines(&path, &ReadOptions::default(), 10).expect("read");
+        std::fs::remove_file(&path).expect("remove sample");
+
+        assert_eq!(reversed, ["RO \u{E6}3", "RO 2"]);
+        assert_eq!(everything, ["RO \u{E6}3", "RO 2", "RO 1"]);
+    }
+
+    #[test]
+    fn remaps_files_whose_size_changed() {
+        let path = std::env::temp_dir().join(format!("ntr-remap-{}.ntr", std::process::id()));
+        std::fs::write(&path, "RO \u{E6}1\n").unwrap();
+        let state = MappedFileState::default();
+        let key = mapped_file_key(&path);
+        state
+            .inner
+            .lock()
+            .unwrap()
+            .insert(key.clone(), map_file(&path).unwrap());
+        let options = ReadOptions::default();
+        let first = read_aligned_range(&state, &path, &options, 0, 2).unwrap();
+        assert_eq!(first.encoding, UTF_8);
+        assert!(state.inner.lock().unwrap()[&key].detected.is_some());
+
+        std::fs::write(&path, b"RO \xE61\nRO 2\n").unwrap();
+        let before = state.inner.lock().unwrap()[&key].map.clone();
+        let map = current_map(&state, &path).unwrap().expect("still mapped");
+        assert!(!Arc::ptr_eq(&before, &map));
+        assert_eq!(map.len(), 11);
+        assert!(state.inner.lock().unwrap()[&key].detected.is_none());
+        let second = read_aligned_range(&state, &path, &options, 3, 4).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(second.encoding.name(), "windows-1252");
+        assert_eq!(second.bytes, b"\xE6");
+    }
+
+    #[test]
+    fn reads_unmapped_ranges_on_character_boundaries() {
+        let path = std::env::temp_dir().join(format!("ntr-range-{}.ntr", std::process::id()));
+        let state = MappedFileState::default();
+        let options = ReadOptions::default();
+        std::fs::write(&path, "\u{FEFF}RO \u{E6}\u{65E5} 1\n".repeat(3)).unwrap();
+        let utf8 = read_aligned_range(&state, &path, &options, 1, 9).unwrap();
+        let utf16: Vec<u8> = [0xFF, 0xFE]
+            .into_iter()
+            .chain("a\u{1D11E}b".encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        std::fs::write(&path, &utf16).unwrap();
+        let utf16 = read_aligned_range(&state, &path, &options, 7, 9).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!((utf8.start, utf8.end), (3, 11));
+        assert_eq!(utf8.requested, (1, 9));
+        assert_eq!(utf8.bytes, "RO \u{E6}\u{65E5}".as_bytes());
+        assert_eq!((utf16.start, utf16.end, utf16.encoding), (4, 10, UTF_16LE));
+    }
+
+    #[test]
+    fn reads_tail_lines_across_chunk_boundaries() {
+        let path = std::env::temp_dir().join(format!("ntr-tail-{}.ntr", std::process::id()));
+        let contents: String = (1..=20_000).map(|line| format!("RO {line}\n")).collect();
+        std::fs::write(&path, &contents).unwrap();
+        let mut file = File::open(&path).unwrap();
+        let len = file.metadata().unwrap().len();
+        let tail = read_tail(&mut file, len, 3).unwrap();
+        let everything = read_tail(&mut file, len, 50_000).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(tail, b"RO 19998\nRO 19999\nRO 20000\n");
+        assert_eq!(everything, contents.as_bytes());
+    }
+
+    #[test]
+    fn aligns_measured_ranges_to_character_boundaries() {
+        let utf8 = "aæ日b".as_bytes();
+        assert_eq!(align_to_chars(utf8, 2, 4, 0, UTF_8), (1, 6));
+        assert_eq!(align_to_chars(utf8, 0, 1, 0, UTF_8), (0, 1));
+
+        let utf16: Vec<u8> = [0xFF, 0xFE]
+            .into_iter()
+            .chain("a𝄞b".encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        assert_eq!(align_to_chars(&utf16, 0, 5, 2, UTF_16LE), (2, 8));
+        assert_eq!(align_to_chars(&utf16, 7, 9, 2, UTF_16LE), (4, 10));
+    }
+
+    #[test]
+    fn finds_the_nth_byte_across_buffer_refills() {
+        let data = b"a;b;;c;";
+        let reader = || BufReader::with_capacity(3, &data[..]);
+        assert_eq!(nth_byte_offset(reader(), b';', 1).unwrap(), Some(1));
+        assert_eq!(nth_byte_offset(reader(), b';', 3).unwrap(), Some(4));
+        assert_eq!(nth_byte_offset(reader(), b';', 4).unwrap(), Some(6));
+        assert_eq!(nth_byte_offset(reader(), b';', 5).unwrap(), None);
+    }
+
+    #[test]
+    fn counts_non_overlapping_separators() {
+        assert_eq!(scan_separators(b"a\nb\nc", b"\n"), (2, 4));
+        assert_eq!(scan_separators(b"a|||b||", b"||"), (2, 7));
+        assert_eq!(scan_separators(b"abc", b"\n"), (0, 0));
+    }
+}
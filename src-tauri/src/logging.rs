@@ -0,0 +1,45 @@
+use std::path::PathBuf;
+use tauri::{plugin::TauriPlugin, Manager, Runtime};
+use tauri_plugin_log::{RotationStrategy, Target, TargetKind};
+
+use crate::normalize_path;
+
+const LOG_FILE_NAME: &str = "ntr-viewer";
+/// The log file is rotated once it reaches this size.
+const LOG_MAX_FILE_SIZE: u128 = 5 * 1024 * 1024;
+/// Rotated log files kept alongside the active one.
+const LOG_ROTATED_FILES: usize = 2;
+
+/// Writes timestamped, levelled records to `ntr-viewer.log` in the app log directory and
+/// mirrors them to stdout. Debug builds also record debug-level watch chatter.
+pub(crate) fn plugin<R: Runtime>() -> TauriPlugin<R> {
+    let level = if cfg!(debug_assertions) {
+        log::LevelFilter::Debug
+    } else {
+        log::LevelFilter::Info
+    };
+    tauri_plugin_log::Builder::new()
+        .clear_targets()
+        .targets([
+            Target::new(TargetKind::LogDir {
+                file_name: Some(LOG_FILE_NAME.into()),
+            }),
+            Target::new(TargetKind::Stdout),
+        ])
+        .level(level)
+        .max_file_size(LOG_MAX_FILE_SIZE)
+        .rotation_strategy(RotationStrategy::KeepSome(LOG_ROTATED_FILES))
+        .build()
+}
+
+fn log_path<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<PathBuf, String> {
+    app.path()
+        .app_log_dir()
+        .map(|dir| dir.join(format!("{LOG_FILE_NAME}.log")))
+        .map_err(|err| format!("Failed to resolve log directory: {err}"))
+}
+
+#[tauri::command]
+pub(crate) fn get_log_path(app: tauri::AppHandle) -> Result<String, String> {
+    log_path(&app).map(|path| normalize_path(&path))
+}
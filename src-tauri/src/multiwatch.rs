@@ -0,0 +1,403 @@
+use notify::{Config, Event, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{mpsc, Mutex},
+    thread,
+    time::Duration,
+};
+use tauri::Emitter;
+
+use crate::{
+    append_watch_start, format_event_kind, log_watch_event, normalize_path, normalize_path_cased,
+    now_ms, probe_case_insensitive, read_appended_lines, resolve_absolute_path, resolve_input_path,
+    should_emit_event, watch_read_options, FileChangePayload, ReadOptions, DEFAULT_IGNORED_KINDS,
+    DIFF_DEBOUNCE,
+};
+
+/// Changes arriving within this window of each other are reported in one `ntr-watch-batch`.
+const WATCH_BATCH_WINDOW: Duration = Duration::from_millis(250);
+
+#[derive(Default)]
+pub(crate) struct MultiWatchState {
+    inner: Mutex<Option<RecommendedWatcher>>,
+}
+
+/// The watcher behind `start_merge_watch`; dropping it also stops the per-file append workers.
+#[derive(Default)]
+pub(crate) struct MergeWatchState {
+    inner: Mutex<Option<RecommendedWatcher>>,
+}
+
+#[derive(Clone, Serialize)]
+struct BatchEntry {
+    path: String,
+    kind: String,
+    /// File size after the change; `None` when the file no longer exists.
+    size: Option<u64>,
+}
+
+/// Gathers `(path, kind)` changes until the window passes without new ones, then emits them as
+/// a single `ntr-watch-batch`, one entry per path carrying its most recent kind.
+fn spawn_batch_worker(app: tauri::AppHandle) -> mpsc::Sender<(String, String)> {
+    let (sender, receiver) = mpsc::channel::<(String, String)>();
+    thread::spawn(move || {
+        while let Ok(first) = receiver.recv() {
+            let mut changes: Vec<(String, String)> = vec![first];
+            loop {
+                match receiver.recv_timeout(WATCH_BATCH_WINDOW) {
+                    Ok((path, kind)) => match changes.iter_mut().find(|(seen, _)| *seen == path) {
+                        Some(entry) => entry.1 = kind,
+                        None => changes.push((path, kind)),
+                    },
+                    Err(mpsc::RecvTimeoutError::Timeout) => break,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => return,
+                }
+            }
+
+            let batch: Vec<BatchEntry> = changes
+                .into_iter()
+                .map(|(path, kind)| BatchEntry {
+                    size: std::fs::metadata(&path).ok().map(|metadata| metadata.len()),
+                    path,
+                    kind,
+                })
+                .collect();
+            log_watch_event(&format!("Emitting watch batch of {} files", batch.len()));
+            if let Err(err) = app.emit("ntr-watch-batch", batch) {
+                log::error!("Failed to emit watch batch event: {err}");
+            }
+        }
+    });
+    sender
+}
+
+/// Watches several files at once, replacing any previous multi-file watch. Changes are always
+/// reported through `ntr-watch-batch`; with `per_file_events` each change is additionally
+/// emitted as `ntr-file-changed` straight away. Returns the normalized paths being watched.
+#[tauri::command]
+pub(crate) fn watch_ntr_files(
+    app: tauri::AppHandle,
+    state: tauri::State<MultiWatchState>,
+    paths: Vec<String>,
+    per_file_events: Option<bool>,
+) -> Result<Vec<String>, String> {
+    let per_file_events = per_file_events.unwrap_or(false);
+    let mut targets: HashMap<String, PathBuf> = HashMap::new();
+    for path in &paths {
+        let input = resolve_input_path(path);
+        if !input.is_file() {
+            return Err(format!("Path does not point to a file: {path}"));
+        }
+        let absolute = resolve_absolute_path(&input);
+        targets.insert(normalize_path(&absolute), absolute);
+    }
+
+    {
+        let mut guard = state.inner.lock().expect("multi watch state poisoned");
+        guard.take();
+    }
+
+    let batch_sender = spawn_batch_worker(app.clone());
+    let watched: Vec<String> = targets.keys().cloned().collect();
+    let mut parents: Vec<PathBuf> = targets
+        .values()
+        .filter_map(|path| path.parent().map(Path::to_path_buf))
+        .collect();
+    parents.sort();
+    parents.dedup();
+
+    let app_handle = app.clone();
+    let mut watcher = notify::recommended_watcher(move |res: Result<Event, notify::Error>| {
+        let event = match res {
+            Ok(event) => event,
+            Err(err) => {
+                log::warn!(target: "watch", "Multi-file watcher error: {err}");
+                return;
+            }
+        };
+        if !should_emit_event(&event.kind, DEFAULT_IGNORED_KINDS) {
+            return;
+        }
+        let kind = format_event_kind(&event.kind);
+        for path in &event.paths {
+            let normalized = normalize_path(path);
+            if !targets.contains_key(&normalized) {
+                continue;
+            }
+            if per_file_events {
+                let payload = FileChangePayload {
+                    path: normalized.clone(),
+                    kind: kind.clone(),
+                    contents: None,
+                    encoding: None,
+                    tag: None,
+                };
+                if let Err(err) = app_handle.emit("ntr-file-changed", payload) {
+                    log::error!("Failed to emit file change event: {err}");
+                }
+            }
+            let _ = batch_sender.send((normalized, kind.clone()));
+        }
+    })
+    .map_err(|err| err.to_string())?;
+
+    watcher
+        .configure(Config::default())
+        .map_err(|err| err.to_string())?;
+    for parent in &parents {
+        watcher
+            .watch(parent, RecursiveMode::NonRecursive)
+            .map_err(|err| format!("Failed to watch {}: {err}", parent.display()))?;
+    }
+    log_watch_event(&format!("Watching {} files", watched.len()));
+
+    let mut guard = state.inner.lock().expect("multi watch state poisoned");
+    *guard = Some(watcher);
+    Ok(watched)
+}
+
+#[tauri::command]
+pub(crate) fn stop_multi_watch(state: tauri::State<MultiWatchState>) {
+    let mut guard = state.inner.lock().expect("multi watch state poisoned");
+    if guard.take().is_some() {
+        log_watch_event("Stopping multi-file watch");
+    }
+}
+
+#[derive(Clone, Serialize)]
+struct MergedAppendPayload {
+    path: String,
+    /// Milliseconds since the Unix epoch when the first change of this append was observed.
+    observed_ms: u64,
+    contents: String,
+}
+
+/// Follows appends to one file of a merge watch. Each message carries the time its change was
+/// observed; a burst is read once it has been quiet for `DIFF_DEBOUNCE` and stamped with its
+/// first observation. A file that shrank was rewritten, so only its offset is reset and the
+/// other files of the watch carry on unaffected.
+fn spawn_merge_worker(
+    app: tauri::AppHandle,
+    file_path: PathBuf,
+    emit_path: String,
+    offset: u64,
+) -> mpsc::Sender<u64> {
+    let (sender, receiver) = mpsc::channel::<u64>();
+    thread::spawn(move || {
+        let read_options = || watch_read_options(&app, &file_path);
+        follow_merged_appends(
+            &receiver,
+            &file_path,
+            &emit_path,
+            offset,
+            read_options,
+            |payload| {
+                if let Err(err) = app.emit("ntr-merged-append", payload) {
+                    log::error!("Failed to emit merged append event: {err}");
+                }
+            },
+        );
+    });
+    sender
+}
+
+/// The loop behind `spawn_merge_worker`; returns once the sender is dropped.
+fn follow_merged_appends(
+    receiver: &mpsc::Receiver<u64>,
+    file_path: &Path,
+    emit_path: &str,
+    mut offset: u64,
+    read_options: impl Fn() -> ReadOptions,
+    mut emit: impl FnMut(MergedAppendPayload),
+) {
+    while let Ok(observed_ms) = receiver.recv() {
+        loop {
+            match receiver.recv_timeout(DIFF_DEBOUNCE) {
+                Ok(_) => continue,
+                Err(mpsc::RecvTimeoutError::Timeout) => break,
+                Err(mpsc::RecvTimeoutError::Disconnected) => return,
+            }
+        }
+        let Ok(len) = std::fs::metadata(file_path).map(|metadata| metadata.len()) else {
+            continue;
+        };
+        if len < offset {
+            log_watch_event(&format!(
+                "{emit_path} shrank; following appends from byte {len}"
+            ));
+            offset = len;
+            continue;
+        }
+        let Some(contents) = read_appended_lines(&read_options(), file_path, &mut offset, len)
+        else {
+            continue;
+        };
+        emit(MergedAppendPayload {
+            path: emit_path.to_string(),
+            observed_ms,
+            contents,
+        });
+    }
+}
+
+struct MergeTarget {
+    path: PathBuf,
+    /// `path` normalized with the case folding of its volume; what events are matched against.
+    match_path: String,
+    case_insensitive: bool,
+}
+
+/// Follows appends to several files at once and reports every block of new complete lines as
+/// `ntr-merged-append`, tagged with its source path and observation time so the frontend can
+/// interleave them into one chronological stream. Replaces any previous merge watch once the
+/// new one is running, so a failed start leaves the old one in place; reading starts at each
+/// file's current end. Returns the normalized paths being watched.
+#[tauri::command]
+pub(crate) fn start_merge_watch(
+    app: tauri::AppHandle,
+    state: tauri::State<MergeWatchState>,
+    paths: Vec<String>,
+) -> Result<Vec<String>, String> {
+    if paths.is_empty() {
+        return Err("No files to watch".into());
+    }
+    let mut targets: HashMap<String, MergeTarget> = HashMap::new();
+    for path in &paths {
+        let input = resolve_input_path(path);
+        if !input.is_file() {
+            return Err(format!("Path does not point to a file: {path}"));
+        }
+        let absolute = resolve_absolute_path(&input);
+        let case_insensitive = probe_case_insensitive(&absolute);
+        let match_path = normalize_path_cased(&absolute, case_insensitive);
+        targets.insert(
+            match_path.clone(),
+            MergeTarget {
+                match_path,
+                path: absolute,
+                case_insensitive,
+            },
+        );
+    }
+
+    let mut workers: Vec<(MergeTarget, mpsc::Sender<u64>)> = Vec::new();
+    for target in targets.into_values() {
+        let start = append_watch_start(&app, &target.path)?;
+        let emit_path = normalize_path(&target.path);
+        let worker = spawn_merge_worker(app.clone(), target.path.clone(), emit_path, start);
+        workers.push((target, worker));
+    }
+    let mut watched: Vec<String> = workers
+        .iter()
+        .map(|(target, _)| normalize_path(&target.path))
+        .collect();
+    watched.sort();
+    let mut parents: Vec<PathBuf> = workers
+        .iter()
+        .filter_map(|(target, _)| target.path.parent().map(Path::to_path_buf))
+        .collect();
+    parents.sort();
+    parents.dedup();
+
+    let mut watcher = notify::recommended_watcher(move |res: Result<Event, notify::Error>| {
+        let event = match res {
+            Ok(event) => event,
+            Err(err) => {
+                log::warn!(target: "watch", "Merge watcher error: {err}");
+                return;
+            }
+        };
+        if !should_emit_event(&event.kind, DEFAULT_IGNORED_KINDS) {
+            return;
+        }
+        let observed_ms = now_ms();
+        for path in &event.paths {
+            let worker = workers.iter().find(|(target, _)| {
+                normalize_path_cased(path, target.case_insensitive) == target.match_path
+            });
+            if let Some((_, worker)) = worker {
+                let _ = worker.send(observed_ms);
+            }
+        }
+    })
+    .map_err(|err| err.to_string())?;
+
+    watcher
+        .configure(Config::default())
+        .map_err(|err| err.to_string())?;
+    for parent in &parents {
+        watcher
+            .watch(parent, RecursiveMode::NonRecursive)
+            .map_err(|err| format!("Failed to watch {}: {err}", parent.display()))?;
+    }
+    log_watch_event(&format!("Merge-watching {} files", watched.len()));
+
+    let mut guard = state.inner.lock().expect("merge watch state poisoned");
+    *guard = Some(watcher);
+    Ok(watched)
+}
+
+#[tauri::command]
+pub(crate) fn stop_merge_watch(state: tauri::State<MergeWatchState>) {
+    let mut guard = state.inner.lock().expect("merge watch state poisoned");
+    if guard.take().is_some() {
+        log_watch_event("Stopping merge watch");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn merged_appends_keep_the_first_observation_and_reset_on_shrink() {
+        let path = std::env::temp_dir().join(format!("ntr-merge-{}.ntr", std::process::id()));
+        std::fs::write(&path, "one\ntwo\n").unwrap();
+        let append = |text: &str| {
+            let mut file = std::fs::OpenOptions::new()
+                .append(true)
+                .open(&path)
+                .unwrap();
+            file.write_all(text.as_bytes()).unwrap();
+        };
+
+        let (sender, receiver) = mpsc::channel::<u64>();
+        let (emitted, payloads) = mpsc::channel::<MergedAppendPayload>();
+        let worker_path = path.clone();
+        let worker = thread::spawn(move || {
+            follow_merged_appends(
+                &receiver,
+                &worker_path,
+                "merged.ntr",
+                8,
+                ReadOptions::default,
+                |payload| emitted.send(payload).unwrap(),
+            );
+        });
+        let wait = || payloads.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        append("three\n");
+        sender.send(10).unwrap();
+        sender.send(20).unwrap();
+        let payload = wait();
+        assert_eq!(payload.path, "merged.ntr");
+        assert_eq!(payload.observed_ms, 10);
+        assert_eq!(payload.contents, "three\n");
+
+        std::fs::write(&path, "new\n").unwrap();
+        sender.send(30).unwrap();
+        thread::sleep(DIFF_DEBOUNCE * 2);
+        append("four\n");
+        sender.send(40).unwrap();
+        let payload = wait();
+        assert_eq!(payload.observed_ms, 40);
+        assert_eq!(payload.contents, "four\n");
+
+        drop(sender);
+        worker.join().unwrap();
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,1541 @@
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use crate::{
+    detect_line_ending, read_decoded, read_decoded_text, read_options_for, resolve_input_path,
+    settings::SettingsState, DecodedLines,
+};
+
+const DEFAULT_SCHEMA_SAMPLE_ROWS: usize = 1000;
+const DEFAULT_MAX_RECORD_ROWS: usize = 100_000;
+const DEFAULT_ALIGN_SAMPLE_ROWS: usize = 5000;
+const DEFAULT_PREVIEW_ROWS: usize = 50;
+/// Cap on the rows `preview_ntr_typed` returns; types are still inferred from
+/// `DEFAULT_SCHEMA_SAMPLE_ROWS`.
+const MAX_PREVIEW_ROWS: usize = 1000;
+/// Lines inspected by `detect_delimiter`.
+const DELIMITER_SAMPLE_LINES: usize = 50;
+/// Records inspected by `classify_dialect`; the header and definitions come first in a file.
+const DIALECT_SAMPLE_RECORDS: usize = 200;
+/// Leading characters that make spreadsheet applications evaluate a cell as a formula.
+const FORMULA_PREFIXES: &[char] = &['=', '+', '-', '@'];
+/// Line that opens and closes a leading metadata block; see `split_metadata`.
+const METADATA_MARKER: &str = "---";
+/// Lines of an opened metadata block a preview reads while looking for the closing marker.
+const MAX_PREVIEW_METADATA_LINES: usize = 1000;
+/// Delimiters `detect_delimiter` considers, multi-character ones first so `||` wins over `|`.
+const DELIMITER_CANDIDATES: &[&str] = &["||", "\t\t", ";", "\t", "|", ","];
+
+/// Splits a record into fields. A delimiter may be several characters long and is matched as
+/// an exact sequence; occurrences inside double-quoted sections do not split, and quotes are
+/// kept in the returned fields. Without a delimiter (or with an empty one) fields are separated
+/// by runs of whitespace, which is how plain NTR records are laid out.
+pub(crate) fn split_fields<'a>(line: &'a str, delimiter: Option<&str>) -> Vec<&'a str> {
+    match delimiter {
+        Some(delimiter) if !delimiter.is_empty() && line.contains('"') => {
+            split_quoted(line, delimiter, '"')
+        }
+        Some(delimiter) if !delimiter.is_empty() => line.split(delimiter).collect(),
+        _ => line.split_whitespace().collect(),
+    }
+}
+
+fn split_quoted<'a>(line: &'a str, delimiter: &str, quote: char) -> Vec<&'a str> {
+    let mut fields = Vec::new();
+    let mut in_quotes = false;
+    let mut start = 0;
+    let mut index = 0;
+    while index < line.len() {
+        let rest = &line[index..];
+        if rest.starts_with(quote) {
+            in_quotes = !in_quotes;
+            index += quote.len_utf8();
+        } else if !in_quotes && rest.starts_with(delimiter) {
+            fields.push(&line[start..index]);
+            index += delimiter.len();
+            start = index;
+        } else {
+            index += rest.chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    fields.push(&line[start..]);
+    fields
+}
+
+/// Picks the first candidate that splits every sampled non-blank line into the same number
+/// (more than one) of fields. Returns `None` for whitespace-separated records.
+pub(crate) fn detect_delimiter(contents: &str) -> Option<&'static str> {
+    let lines: Vec<&str> = contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .take(DELIMITER_SAMPLE_LINES)
+        .collect();
+    DELIMITER_CANDIDATES.iter().copied().find(|&delimiter| {
+        let mut counts = lines
+            .iter()
+            .map(|line| split_fields(line, Some(delimiter)).len());
+        counts
+            .next()
+            .is_some_and(|first| first > 1 && counts.all(|count| count == first))
+    })
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct MetadataEntry {
+    key: String,
+    value: String,
+}
+
+/// Separates a leading preamble from the data: a `#!` first line (reported under the key
+/// "shebang") and a block of `key: value` or `key=value` lines between two `---` marker lines.
+/// Returns the entries and the remaining contents; a block that is never closed is treated as
+/// data, so files without a preamble come back unchanged.
+pub(crate) fn split_metadata(contents: &str) -> (Vec<MetadataEntry>, &str) {
+    let mut entries = Vec::new();
+    let mut rest = contents;
+    if let Some(line) = rest.split_inclusive('\n').next() {
+        if let Some(shebang) = line.strip_prefix("#!") {
+            entries.push(MetadataEntry {
+                key: "shebang".to_string(),
+                value: shebang.trim().to_string(),
+            });
+            rest = &rest[line.len()..];
+        }
+    }
+
+    let mut lines = rest.split_inclusive('\n');
+    let Some(opening) = lines
+        .next()
+        .filter(|line| line.trim_end() == METADATA_MARKER)
+    else {
+        return (entries, rest);
+    };
+    let mut consumed = opening.len();
+    let mut block = Vec::new();
+    for line in lines {
+        consumed += line.len();
+        let line = line.trim();
+        if line == METADATA_MARKER {
+            entries.extend(block);
+            return (entries, &rest[consumed..]);
+        }
+        if line.is_empty() {
+            continue;
+        }
+        let (key, value) = line
+            .find([':', '='])
+            .map_or((line, ""), |index| (&line[..index], &line[index + 1..]));
+        block.push(MetadataEntry {
+            key: key.trim().to_string(),
+            value: value.trim().to_string(),
+        });
+    }
+    (entries, rest)
+}
+
+/// Non-blank lines of `contents`, split into fields.
+pub(crate) fn parse_rows<'a>(
+    contents: &'a str,
+    delimiter: Option<&'a str>,
+) -> impl Iterator<Item = Vec<&'a str>> + 'a {
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(move |line| split_fields(line, delimiter))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum FieldType {
+    Integer,
+    Float,
+    Boolean,
+    Date,
+    String,
+    Empty,
+}
+
+pub(crate) fn classify_value(value: &str) -> FieldType {
+    let value = value.trim();
+    if value.is_empty() {
+        FieldType::Empty
+    } else if value.parse::<i64>().is_ok() {
+        FieldType::Integer
+    } else if value.parse::<f64>().is_ok_and(f64::is_finite) {
+        FieldType::Float
+    } else if ["true", "false"]
+        .iter()
+        .any(|b| value.eq_ignore_ascii_case(b))
+    {
+        FieldType::Boolean
+    } else if is_date(value) {
+        FieldType::Date
+    } else {
+        FieldType::String
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum TokenKind {
+    Number,
+    QuotedString,
+    Boolean,
+    Plain,
+}
+
+/// A highlighted field. Offsets are UTF-16 code units into the line, as the frontend's strings
+/// count them; `end` is exclusive.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct TokenSpan {
+    start: usize,
+    end: usize,
+    kind: TokenKind,
+}
+
+pub(crate) fn tokenize_fields(line: &str, delimiter: Option<&str>) -> Vec<TokenSpan> {
+    let utf16_len = |text: &str| text.chars().map(char::len_utf16).sum::<usize>();
+    split_fields(line, delimiter)
+        .into_iter()
+        .filter_map(|field| {
+            let token = field.trim();
+            if token.is_empty() {
+                return None;
+            }
+            let offset = token.as_ptr() as usize - line.as_ptr() as usize;
+            let kind = if token.len() >= 2 && token.starts_with('"') && token.ends_with('"') {
+                TokenKind::QuotedString
+            } else {
+                match classify_value(token) {
+                    FieldType::Integer | FieldType::Float => TokenKind::Number,
+                    FieldType::Boolean => TokenKind::Boolean,
+                    _ => TokenKind::Plain,
+                }
+            };
+            let start = utf16_len(&line[..offset]);
+            Some(TokenSpan {
+                start,
+                end: start + utf16_len(token),
+                kind,
+            })
+        })
+        .collect()
+}
+
+/// Classifies each field of one line for highlighting. Stateless, so the UI can tokenize just
+/// the visible lines; a quoted field that spans lines is not recognised.
+#[tauri::command]
+pub(crate) fn tokenize_line(line: String, delimiter: Option<String>) -> Vec<TokenSpan> {
+    tokenize_fields(&line, delimiter.as_deref())
+}
+
+/// Accepts `YYYY-MM-DD` and `DD.MM.YYYY`, the two layouts our exports use.
+fn is_date(value: &str) -> bool {
+    let parts: Vec<&str> = value.split(['-', '.']).collect();
+    let [a, b, c] = parts.as_slice() else {
+        return false;
+    };
+    let digits =
+        |part: &str, len: usize| part.len() == len && part.bytes().all(|b| b.is_ascii_digit());
+    let iso = value.contains('-') && digits(a, 4) && digits(b, 2) && digits(c, 2);
+    let dotted = value.contains('.') && digits(a, 2) && digits(b, 2) && digits(c, 4);
+    let (month, day) = match (iso, dotted) {
+        (true, _) => (*b, *c),
+        (_, true) => (*b, *a),
+        _ => return false,
+    };
+    let month: u32 = month.parse().unwrap_or(0);
+    let day: u32 = day.parse().unwrap_or(0);
+    (1..=12).contains(&month) && (1..=31).contains(&day)
+}
+
+#[derive(Default)]
+struct TypeTally {
+    integer: usize,
+    float: usize,
+    boolean: usize,
+    date: usize,
+    string: usize,
+    empty: usize,
+}
+
+impl TypeTally {
+    fn add(&mut self, field_type: FieldType) {
+        match field_type {
+            FieldType::Integer => self.integer += 1,
+            FieldType::Float => self.float += 1,
+            FieldType::Boolean => self.boolean += 1,
+            FieldType::Date => self.date += 1,
+            FieldType::String => self.string += 1,
+            FieldType::Empty => self.empty += 1,
+        }
+    }
+
+    fn non_empty(&self) -> usize {
+        self.integer + self.float + self.boolean + self.date + self.string
+    }
+
+    /// The type matching the most non-empty values; integers count towards a float column.
+    fn dominant(&self) -> FieldType {
+        let numeric = if self.float > 0 {
+            (FieldType::Float, self.integer + self.float)
+        } else {
+            (FieldType::Integer, self.integer)
+        };
+        let candidates = [
+            numeric,
+            (FieldType::Boolean, self.boolean),
+            (FieldType::Date, self.date),
+            (FieldType::String, self.string),
+        ];
+        // `max_by_key` keeps the last maximum, so iterate in reverse to break ties towards the
+        // more specific type.
+        candidates
+            .into_iter()
+            .rev()
+            .filter(|(_, count)| *count > 0)
+            .max_by_key(|(_, count)| *count)
+            .map(|(field_type, _)| field_type)
+            .unwrap_or(FieldType::Empty)
+    }
+
+    fn matching(&self, field_type: FieldType) -> usize {
+        match field_type {
+            FieldType::Integer => self.integer,
+            FieldType::Float => self.integer + self.float,
+            FieldType::Boolean => self.boolean,
+            FieldType::Date => self.date,
+            FieldType::String => self.non_empty(),
+            FieldType::Empty => 0,
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub(crate) struct ColumnSchema {
+    pub index: usize,
+    pub name: String,
+    pub field_type: FieldType,
+    pub mismatched: usize,
+    pub empty: usize,
+}
+
+/// The tabular part of a file; see `read_data`.
+pub(crate) struct DataText {
+    pub(crate) contents: String,
+    /// Lines of the dropped preamble, to add to line numbers counted within `contents`.
+    pub(crate) skipped_lines: usize,
+}
+
+/// Reads `path` for a record analysis or export: decoded like `read_decoded`, with a leading
+/// metadata block (see `split_metadata`) dropped unless `include_metadata` is set. Every such
+/// command reads through here so they all see the same rows, and `include_metadata` is their
+/// one opt-out.
+pub(crate) fn read_data(
+    settings: &SettingsState,
+    path: &Path,
+    include_metadata: Option<bool>,
+) -> Result<DataText, String> {
+    let mut contents = read_decoded(settings, path)?;
+    let mut skipped_lines = 0;
+    if !include_metadata.unwrap_or(false) {
+        let preamble = contents.len() - split_metadata(&contents).1.len();
+        skipped_lines = contents[..preamble].lines().count();
+        contents.drain(..preamble);
+    }
+    Ok(DataText {
+        contents,
+        skipped_lines,
+    })
+}
+
+/// The start of what `read_data` returns, read line by line until it holds `rows` non-blank
+/// rows, so a preview of a large file stops early. A metadata block is read through to its
+/// closing marker before rows are counted; a block still open at the end of the file or after
+/// `MAX_PREVIEW_METADATA_LINES` is taken as data, and the first `rows` non-blank lines are
+/// returned. Unlike `read_data`, UTF-16 files are rejected.
+fn read_data_prefix(
+    settings: &SettingsState,
+    path: &Path,
+    include_metadata: Option<bool>,
+    rows: usize,
+) -> Result<String, String> {
+    let strip = !include_metadata.unwrap_or(false);
+    let options = read_options_for(settings, path);
+    let mut contents = String::new();
+    let mut non_blank = 0;
+    let mut target = rows;
+    let mut open_block = false;
+    let mut block_lines = 0;
+    for line in DecodedLines::open(path, &options, "Previewing")? {
+        let line = line?;
+        non_blank += usize::from(!line.trim().is_empty());
+        contents.push_str(&line);
+        contents.push('\n');
+        if open_block {
+            if line.trim() != METADATA_MARKER {
+                block_lines += 1;
+                if block_lines > MAX_PREVIEW_METADATA_LINES {
+                    break;
+                }
+                continue;
+            }
+            open_block = false;
+        }
+        if non_blank < target {
+            continue;
+        }
+        let data = if strip {
+            split_metadata(&contents).1
+        } else {
+            &contents
+        };
+        if strip
+            && data
+                .lines()
+                .next()
+                .is_some_and(|line| line.trim_end() == METADATA_MARKER)
+        {
+            open_block = true;
+            continue;
+        }
+        let data_rows = data.lines().filter(|line| !line.trim().is_empty()).count();
+        if data_rows >= rows {
+            break;
+        }
+        target = non_blank + rows - data_rows;
+    }
+    if open_block {
+        let mut kept = 0;
+        let end = contents
+            .split_inclusive('\n')
+            .take_while(|line| {
+                let take = kept < rows;
+                kept += usize::from(!line.trim().is_empty());
+                take
+            })
+            .map(str::len)
+            .sum();
+        contents.truncate(end);
+        return Ok(contents);
+    }
+    if strip {
+        let preamble = contents.len() - split_metadata(&contents).1.len();
+        contents.drain(..preamble);
+    }
+    Ok(contents)
+}
+
+#[derive(Serialize)]
+pub(crate) struct NtrSchema {
+    columns: Vec<ColumnSchema>,
+    sampled_rows: usize,
+}
+
+pub(crate) fn header_names(header: Option<&[&str]>, column_count: usize) -> Vec<String> {
+    (0..column_count)
+        .map(|index| {
+            header
+                .and_then(|header| header.get(index))
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| format!("column {}", index + 1))
+        })
+        .collect()
+}
+
+pub(crate) fn infer_schema(header: Option<&[&str]>, rows: &[Vec<&str>]) -> Vec<ColumnSchema> {
+    let column_count = rows
+        .iter()
+        .map(Vec::len)
+        .chain(header.map(<[&str]>::len))
+        .max()
+        .unwrap_or(0);
+    let mut tallies: Vec<TypeTally> = (0..column_count).map(|_| TypeTally::default()).collect();
+    for row in rows {
+        for (index, tally) in tallies.iter_mut().enumerate() {
+            tally.add(
+                row.get(index)
+                    .map_or(FieldType::Empty, |value| classify_value(value)),
+            );
+        }
+    }
+
+    header_names(header, column_count)
+        .into_iter()
+        .zip(tallies)
+        .enumerate()
+        .map(|(index, (name, tally))| {
+            let field_type = tally.dominant();
+            ColumnSchema {
+                index,
+                name,
+                field_type,
+                mismatched: tally.non_empty() - tally.matching(field_type),
+                empty: tally.empty,
+            }
+        })
+        .collect()
+}
+
+#[tauri::command]
+pub(crate) fn infer_ntr_schema(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    delimiter: Option<String>,
+    has_header: Option<bool>,
+    sample_rows: Option<usize>,
+    include_metadata: Option<bool>,
+) -> Result<NtrSchema, String> {
+    let contents = read_data(&settings, &resolve_input_path(&path), include_metadata)?.contents;
+    let mut rows = parse_rows(&contents, delimiter.as_deref());
+    let header = if has_header.unwrap_or(true) {
+        rows.next()
+    } else {
+        None
+    };
+    let sample: Vec<Vec<&str>> = rows
+        .take(sample_rows.unwrap_or(DEFAULT_SCHEMA_SAMPLE_ROWS))
+        .collect();
+    Ok(NtrSchema {
+        columns: infer_schema(header.as_deref(), &sample),
+        sampled_rows: sample.len(),
+    })
+}
+
+/// Whether `value` fits a column inferred as `field_type`, judged the same way as the column's
+/// `mismatched` count. Empty cells always fit.
+pub(crate) fn value_matches(value: &str, field_type: FieldType) -> bool {
+    let mut tally = TypeTally::default();
+    tally.add(classify_value(value));
+    tally.empty == 1 || tally.matching(field_type) == 1
+}
+
+#[derive(Serialize)]
+pub(crate) struct TypedCell {
+    value: String,
+    matches: bool,
+}
+
+#[derive(Serialize)]
+pub(crate) struct TypedPreview {
+    /// Header names with the type inferred for each column.
+    columns: Vec<ColumnSchema>,
+    rows: Vec<Vec<TypedCell>>,
+    /// True when the file has more records than `rows`.
+    truncated: bool,
+}
+
+/// The header, inferred column types and the first `rows` records (default
+/// `DEFAULT_PREVIEW_ROWS`, at most `MAX_PREVIEW_ROWS`) with every cell flagged by whether it
+/// fits its column, so a preview can highlight anomalies without a second call. Types come
+/// from the same sample as `infer_ntr_schema`; only that much of the file is read, through
+/// `read_data_prefix`.
+#[tauri::command]
+pub(crate) fn preview_ntr_typed(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    delimiter: Option<String>,
+    rows: Option<usize>,
+    include_metadata: Option<bool>,
+) -> Result<TypedPreview, String> {
+    let contents = read_data_prefix(
+        &settings,
+        &resolve_input_path(&path),
+        include_metadata,
+        DEFAULT_SCHEMA_SAMPLE_ROWS + 2,
+    )?;
+    let mut records = parse_rows(&contents, delimiter.as_deref());
+    let header = records.next();
+    let sample: Vec<Vec<&str>> = records.take(DEFAULT_SCHEMA_SAMPLE_ROWS + 1).collect();
+    let columns = infer_schema(
+        header.as_deref(),
+        &sample[..sample.len().min(DEFAULT_SCHEMA_SAMPLE_ROWS)],
+    );
+
+    let limit = rows.unwrap_or(DEFAULT_PREVIEW_ROWS).min(MAX_PREVIEW_ROWS);
+    let typed = sample
+        .iter()
+        .take(limit)
+        .map(|row| {
+            row.iter()
+                .enumerate()
+                .map(|(index, value)| TypedCell {
+                    value: value.to_string(),
+                    matches: columns
+                        .get(index)
+                        .is_some_and(|column| value_matches(value, column.field_type)),
+                })
+                .collect()
+        })
+        .collect();
+    Ok(TypedPreview {
+        columns,
+        rows: typed,
+        truncated: sample.len() > limit,
+    })
+}
+
+#[derive(Serialize)]
+pub(crate) struct DelimiterReport {
+    delimiter: Option<String>,
+    multi_character: bool,
+    fields: usize,
+}
+
+#[tauri::command]
+pub(crate) fn detect_ntr_delimiter(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    include_metadata: Option<bool>,
+) -> Result<DelimiterReport, String> {
+    let contents = read_data(&settings, &resolve_input_path(&path), include_metadata)?.contents;
+    let delimiter = detect_delimiter(&contents);
+    let fields = parse_rows(&contents, delimiter)
+        .next()
+        .map_or(0, |row| row.len());
+    Ok(DelimiterReport {
+        delimiter: delimiter.map(str::to_string),
+        multi_character: delimiter.is_some_and(|delimiter| delimiter.chars().count() > 1),
+        fields,
+    })
+}
+
+/// A record code, optionally with a `KEY=` field it must carry, that hints at a dialect.
+struct DialectMarker {
+    code: &'static str,
+    key: Option<&'static str>,
+}
+
+const fn marker(code: &'static str, key: Option<&'static str>) -> DialectMarker {
+    DialectMarker { code, key }
+}
+
+struct NtrDialect {
+    name: &'static str,
+    markers: &'static [DialectMarker],
+    /// Record codes whose presence rules the dialect out.
+    excludes: &'static [&'static str],
+}
+
+/// Known NTR dialects, most specific first; on equal confidence the earlier entry wins. A new
+/// dialect only needs a row here.
+const NTR_DIALECTS: &[NtrDialect] = &[
+    NtrDialect {
+        name: "ntr-insulated",
+        markers: &[
+            marker("GEN", Some("UNITKT")),
+            marker("GEN", Some("CODE")),
+            marker("IS", Some("NAME")),
+            marker("DN", Some("ISOTYP")),
+            marker("DN", Some("ISODICKE")),
+        ],
+        excludes: &[],
+    },
+    NtrDialect {
+        name: "ntr",
+        markers: &[
+            marker("GEN", None),
+            marker("DN", Some("NAME")),
+            marker("DN", Some("DA")),
+            marker("RO", Some("P1")),
+        ],
+        excludes: &[],
+    },
+    NtrDialect {
+        name: "ntr-headerless",
+        markers: &[marker("DN", Some("NAME")), marker("RO", Some("P1"))],
+        excludes: &["GEN"],
+    },
+];
+
+/// Scores the leading records of `contents` against `NTR_DIALECTS` and returns the best
+/// dialect with the share of its markers found, or `None` when no marker matched.
+pub(crate) fn classify_dialect(contents: &str) -> Option<(&'static str, f64)> {
+    let mut fields: HashMap<String, HashSet<&str>> = HashMap::new();
+    let records = contents
+        .lines()
+        .filter_map(|line| {
+            let mut tokens = line.split_whitespace();
+            tokens
+                .next()
+                .map(|code| (code.to_ascii_uppercase(), tokens))
+        })
+        .filter(|(code, _)| code != "C")
+        .take(DIALECT_SAMPLE_RECORDS);
+    for (code, tokens) in records {
+        let keys = tokens
+            .filter_map(|token| token.split_once('=').map(|(key, _)| key))
+            .filter(|key| {
+                !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            });
+        fields.entry(code).or_default().extend(keys);
+    }
+
+    let mut best: Option<(&'static str, f64)> = None;
+    for dialect in NTR_DIALECTS {
+        if dialect
+            .excludes
+            .iter()
+            .any(|code| fields.contains_key(*code))
+        {
+            continue;
+        }
+        let matched = dialect
+            .markers
+            .iter()
+            .filter(|marker| {
+                fields.get(marker.code).is_some_and(|keys| {
+                    marker
+                        .key
+                        .is_none_or(|key| keys.iter().any(|found| found.eq_ignore_ascii_case(key)))
+                })
+            })
+            .count();
+        let confidence = matched as f64 / dialect.markers.len() as f64;
+        if matched > 0 && best.is_none_or(|(_, current)| confidence > current) {
+            best = Some((dialect.name, confidence));
+        }
+    }
+    best
+}
+
+#[derive(Serialize)]
+pub(crate) struct DialectReport {
+    /// Name from `NTR_DIALECTS`, or `None` when the file matches none of them.
+    dialect: Option<String>,
+    /// Share of the dialect's markers found in the file, from 0 to 1.
+    confidence: f64,
+}
+
+/// Classifies the file into one of `NTR_DIALECTS` from its leading records so the viewer can
+/// pick matching parsing rules.
+#[tauri::command]
+pub(crate) fn detect_ntr_dialect(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    include_metadata: Option<bool>,
+) -> Result<DialectReport, String> {
+    let contents = read_data(&settings, &resolve_input_path(&path), include_metadata)?.contents;
+    let (dialect, confidence) = classify_dialect(&contents)
+        .map_or((None, 0.0), |(name, confidence)| {
+            (Some(name.to_string()), confidence)
+        });
+    Ok(DialectReport {
+        dialect,
+        confidence,
+    })
+}
+
+#[derive(Serialize)]
+pub(crate) struct NtrRecords {
+    /// Entries of the leading metadata block, which is not part of `header` or `rows`.
+    metadata: Vec<MetadataEntry>,
+    header: Option<Vec<String>>,
+    rows: Vec<Vec<String>>,
+    encoding: String,
+    line_count: usize,
+    /// True when rows beyond `max_rows` were left out.
+    truncated: bool,
+}
+
+/// Returns the file pre-split into fields, with at most `max_rows` rows (default
+/// `DEFAULT_MAX_RECORD_ROWS`) so huge files do not have to cross the IPC bridge in one go.
+#[tauri::command]
+pub(crate) fn load_ntr_records(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    delimiter: Option<String>,
+    has_header: Option<bool>,
+    max_rows: Option<usize>,
+) -> Result<NtrRecords, String> {
+    let decoded = read_decoded_text(&settings, &resolve_input_path(&path))?;
+    let (metadata, body) = split_metadata(&decoded.contents);
+    let to_owned = |fields: Vec<&str>| fields.into_iter().map(str::to_string).collect();
+    let mut rows = parse_rows(body, delimiter.as_deref());
+    let header = if has_header.unwrap_or(true) {
+        rows.next().map(to_owned)
+    } else {
+        None
+    };
+    let max_rows = max_rows.unwrap_or(DEFAULT_MAX_RECORD_ROWS);
+    let mut rows = rows.peekable();
+    let records: Vec<Vec<String>> = rows.by_ref().take(max_rows).map(to_owned).collect();
+    Ok(NtrRecords {
+        metadata,
+        header,
+        rows: records,
+        encoding: decoded.encoding.name().to_string(),
+        line_count: decoded.contents.lines().count(),
+        truncated: rows.peek().is_some(),
+    })
+}
+
+#[derive(Serialize)]
+pub(crate) struct AlignedText {
+    contents: String,
+    column_widths: Vec<usize>,
+    /// True when the widths came from the first `sample_rows` rows only; longer fields further
+    /// down then push their line out of alignment.
+    sampled: bool,
+}
+
+/// Re-renders `contents` with every field padded to its column's widest value (in characters)
+/// among the first `sample_rows` non-blank rows. Columns are joined by the delimiter between
+/// single spaces, or by two spaces for whitespace-separated and tab-delimited records. Blank
+/// lines are kept and the last field of a row is never padded.
+pub(crate) fn align_rows(
+    contents: &str,
+    delimiter: Option<&str>,
+    sample_rows: usize,
+) -> AlignedText {
+    let mut column_widths: Vec<usize> = Vec::new();
+    let mut rows = parse_rows(contents, delimiter);
+    for row in rows.by_ref().take(sample_rows) {
+        for (index, field) in row.iter().enumerate() {
+            let width = field.trim().chars().count();
+            match column_widths.get_mut(index) {
+                Some(max) => *max = (*max).max(width),
+                None => column_widths.push(width),
+            }
+        }
+    }
+    let sampled = rows.next().is_some();
+
+    let separator = match delimiter.map(str::trim) {
+        Some(delimiter) if !delimiter.is_empty() => format!(" {delimiter} "),
+        _ => "  ".to_string(),
+    };
+    let line_ending = detect_line_ending(contents);
+    let mut aligned = String::with_capacity(contents.len());
+    for line in contents.lines() {
+        if !line.trim().is_empty() {
+            let fields = split_fields(line, delimiter);
+            for (index, field) in fields.iter().enumerate() {
+                let field = field.trim();
+                aligned.push_str(field);
+                if index + 1 < fields.len() {
+                    let width = column_widths.get(index).copied().unwrap_or(0);
+                    let padding = width.saturating_sub(field.chars().count());
+                    aligned.extend(std::iter::repeat_n(' ', padding));
+                    aligned.push_str(&separator);
+                }
+            }
+        }
+        aligned.push_str(line_ending);
+    }
+    AlignedText {
+        contents: aligned,
+        column_widths,
+        sampled,
+    }
+}
+
+/// Read-only pretty print of a delimited file for the text view; see `align_rows`. Widths are
+/// computed from at most `sample_rows` rows (default `DEFAULT_ALIGN_SAMPLE_ROWS`).
+#[tauri::command]
+pub(crate) fn align_columns(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    delimiter: Option<String>,
+    sample_rows: Option<usize>,
+    include_metadata: Option<bool>,
+) -> Result<AlignedText, String> {
+    let contents = read_data(&settings, &resolve_input_path(&path), include_metadata)?.contents;
+    let sample_rows = sample_rows.unwrap_or(DEFAULT_ALIGN_SAMPLE_ROWS);
+    Ok(align_rows(&contents, delimiter.as_deref(), sample_rows))
+}
+
+#[derive(Serialize)]
+pub(crate) struct TokenCount {
+    token: String,
+    count: usize,
+}
+
+/// Streams the file line by line, decoding each line on its own, and returns the `top_n` most
+/// frequent fields (split like `split_fields`), most frequent first and ties alphabetically.
+/// With `case_insensitive`, tokens are tallied and reported in lowercase.
+#[tauri::command]
+pub(crate) fn token_frequency(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    delimiter: Option<String>,
+    top_n: usize,
+    case_insensitive: Option<bool>,
+) -> Result<Vec<TokenCount>, String> {
+    let resolved = resolve_input_path(&path);
+    let mut tokens = count_tokens(
+        &settings,
+        &resolved,
+        delimiter.as_deref(),
+        case_insensitive.unwrap_or(false),
+    )?;
+    tokens.truncate(top_n);
+    Ok(tokens)
+}
+
+/// Every token of the file with its count, in `token_frequency` order.
+fn count_tokens(
+    settings: &SettingsState,
+    path: &Path,
+    delimiter: Option<&str>,
+    case_insensitive: bool,
+) -> Result<Vec<TokenCount>, String> {
+    let options = read_options_for(settings, path);
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for line in DecodedLines::open(path, &options, "Counting tokens of")? {
+        let line = line?;
+        for field in split_fields(line.trim_end(), delimiter) {
+            let token = field.trim();
+            if token.is_empty() {
+                continue;
+            }
+            let token = if case_insensitive {
+                token.to_lowercase()
+            } else {
+                token.to_string()
+            };
+            *counts.entry(token).or_default() += 1;
+        }
+    }
+
+    let mut tokens: Vec<TokenCount> = counts
+        .into_iter()
+        .map(|(token, count)| TokenCount { token, count })
+        .collect();
+    tokens.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.token.cmp(&b.token)));
+    Ok(tokens)
+}
+
+#[derive(Serialize)]
+pub(crate) struct ColumnStats {
+    count: usize,
+    min: Option<f64>,
+    max: Option<f64>,
+    sum: f64,
+    mean: Option<f64>,
+    /// Non-empty values that are not numbers.
+    skipped: usize,
+    /// Rows where the column is blank or missing.
+    empty: usize,
+}
+
+/// Aggregates the values of `column_index` that classify as integers or floats. The first
+/// non-blank line is treated as a header unless `has_header` is false.
+#[tauri::command]
+pub(crate) fn column_stats(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    column_index: usize,
+    delimiter: Option<String>,
+    has_header: Option<bool>,
+    include_metadata: Option<bool>,
+) -> Result<ColumnStats, String> {
+    let contents = read_data(&settings, &resolve_input_path(&path), include_metadata)?.contents;
+    let mut rows = parse_rows(&contents, delimiter.as_deref());
+    if has_header.unwrap_or(true) {
+        rows.next();
+    }
+
+    let mut stats = ColumnStats {
+        count: 0,
+        min: None,
+        max: None,
+        sum: 0.0,
+        mean: None,
+        skipped: 0,
+        empty: 0,
+    };
+    for row in rows {
+        let value = row.get(column_index).map_or("", |value| value.trim());
+        let number = match classify_value(value) {
+            FieldType::Integer | FieldType::Float => value.parse::<f64>().ok(),
+            FieldType::Empty => {
+                stats.empty += 1;
+                continue;
+            }
+            _ => None,
+        };
+        let Some(number) = number else {
+            stats.skipped += 1;
+            continue;
+        };
+        stats.count += 1;
+        stats.sum += number;
+        stats.min = Some(stats.min.map_or(number, |min| min.min(number)));
+        stats.max = Some(stats.max.map_or(number, |max| max.max(number)));
+    }
+    stats.mean = (stats.count > 0).then(|| stats.sum / stats.count as f64);
+    Ok(stats)
+}
+
+#[derive(Serialize)]
+pub(crate) struct ReorderedColumn {
+    name: String,
+    index_a: usize,
+    index_b: usize,
+}
+
+#[derive(Serialize)]
+pub(crate) struct TypeMismatch {
+    name: String,
+    type_a: FieldType,
+    type_b: FieldType,
+}
+
+#[derive(Serialize)]
+pub(crate) struct FieldCounts {
+    header_fields: usize,
+    /// Sampled rows whose field count differs from the header's.
+    mismatched_rows: usize,
+}
+
+#[derive(Serialize)]
+pub(crate) struct SchemaComparison {
+    only_in_a: Vec<String>,
+    only_in_b: Vec<String>,
+    /// Shared columns that appear in a different order relative to the other shared columns.
+    reordered: Vec<ReorderedColumn>,
+    type_mismatches: Vec<TypeMismatch>,
+    field_counts_a: FieldCounts,
+    field_counts_b: FieldCounts,
+    compatible: bool,
+}
+
+/// Whether a spreadsheet would evaluate `field` as a formula: it starts (after whitespace and an
+/// opening quote) with one of `FORMULA_PREFIXES`. Plain numbers such as `-12.5` are evaluated
+/// as numbers, not formulas, so they are not flagged.
+pub(crate) fn is_formula_cell(field: &str) -> bool {
+    let value = field.trim_start().trim_start_matches('"');
+    value.starts_with(FORMULA_PREFIXES)
+        && !matches!(
+            classify_value(value.trim_end_matches('"')),
+            FieldType::Integer | FieldType::Float
+        )
+}
+
+#[derive(Serialize)]
+pub(crate) struct FormulaCell {
+    /// 1-based line number.
+    row: usize,
+    /// 0-based field index, as taken by `column_index` parameters.
+    column: usize,
+}
+
+/// Locates fields that would be interpreted as formulas once the data is opened in a
+/// spreadsheet (CSV injection). Blank lines count towards `row` but hold no cells.
+#[tauri::command]
+pub(crate) fn scan_formula_cells(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    delimiter: Option<String>,
+    include_metadata: Option<bool>,
+) -> Result<Vec<FormulaCell>, String> {
+    let data = read_data(&settings, &resolve_input_path(&path), include_metadata)?;
+    let mut cells = Vec::new();
+    for (index, line) in data.contents.lines().enumerate() {
+        for (column, field) in split_fields(line, delimiter.as_deref()).iter().enumerate() {
+            if is_formula_cell(field) {
+                cells.push(FormulaCell {
+                    row: data.skipped_lines + index + 1,
+                    column,
+                });
+            }
+        }
+    }
+    Ok(cells)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum QuotingProblem {
+    /// The line has an odd number of quote characters.
+    Unbalanced,
+    /// A field contains quotes but is not quoted as a whole, like `ab"c"`.
+    PartialField,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct QuotingIssue {
+    /// 1-based line number.
+    line: usize,
+    /// 0-based field index; `None` for unbalanced lines, which cannot be split reliably.
+    column: Option<usize>,
+    problem: QuotingProblem,
+}
+
+/// Checks each line's quoting the way `split_fields` reads it: quotes must pair up, and a field
+/// that contains a quote must be wrapped in quotes from end to end (doubled quotes inside are
+/// escapes).
+pub(crate) fn quoting_issues(contents: &str, delimiter: &str, quote: char) -> Vec<QuotingIssue> {
+    let mut issues = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        if !line.contains(quote) {
+            continue;
+        }
+        if line.matches(quote).count() % 2 != 0 {
+            issues.push(QuotingIssue {
+                line: index + 1,
+                column: None,
+                problem: QuotingProblem::Unbalanced,
+            });
+            continue;
+        }
+        for (column, field) in split_quoted(line, delimiter, quote).iter().enumerate() {
+            let field = field.trim();
+            let wrapped = field.len() >= 2 * quote.len_utf8()
+                && field.starts_with(quote)
+                && field.ends_with(quote);
+            if field.contains(quote) && !wrapped {
+                issues.push(QuotingIssue {
+                    line: index + 1,
+                    column: Some(column),
+                    problem: QuotingProblem::PartialField,
+                });
+            }
+        }
+    }
+    issues
+}
+
+/// Reports lines whose quoting would make a delimited parse go wrong. `quote_char` defaults to
+/// `"`; whitespace-separated records have no field quoting, so a delimiter is required.
+#[tauri::command]
+pub(crate) fn check_quoting(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    delimiter: String,
+    quote_char: Option<String>,
+    include_metadata: Option<bool>,
+) -> Result<Vec<QuotingIssue>, String> {
+    if delimiter.is_empty() {
+        return Err("A delimiter is required to check quoting".into());
+    }
+    let quote = match quote_char.as_deref() {
+        None => '"',
+        Some(quote) => {
+            let mut chars = quote.chars();
+            match (chars.next(), chars.next()) {
+                (Some(quote), None) => quote,
+                _ => return Err("Quote character must be a single character".into()),
+            }
+        }
+    };
+    let data = read_data(&settings, &resolve_input_path(&path), include_metadata)?;
+    let mut issues = quoting_issues(&data.contents, &delimiter, quote);
+    for issue in &mut issues {
+        issue.line += data.skipped_lines;
+    }
+    Ok(issues)
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct LastRecordReport {
+    /// 1-based line number of the last non-blank line.
+    line: usize,
+    field_count: usize,
+    /// The most common field count of the records before it, or the header's when there are
+    /// no other records.
+    expected_fields: usize,
+    ends_with_newline: bool,
+    probably_truncated: bool,
+}
+
+/// Checks the final record against the rest of the file. A record with fewer fields than
+/// expected is a probable truncation, and so is one with any other count when the file also
+/// lacks its final newline, since complete exports end with one. A leading metadata block is
+/// skipped. Returns `None` when there are no records.
+pub(crate) fn last_record_report(
+    contents: &str,
+    delimiter: Option<&str>,
+) -> Option<LastRecordReport> {
+    let (_, data) = split_metadata(contents);
+    let preamble_lines = contents[..contents.len() - data.len()].lines().count();
+    let records: Vec<(usize, usize)> = data
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            (
+                preamble_lines + index + 1,
+                split_fields(line, delimiter).len(),
+            )
+        })
+        .collect();
+    let (&(line, field_count), rest) = records.split_last()?;
+
+    let mut tally: HashMap<usize, usize> = HashMap::new();
+    for &(_, count) in rest.iter().skip(1) {
+        *tally.entry(count).or_default() += 1;
+    }
+    let expected_fields = tally
+        .into_iter()
+        .max_by_key(|&(count, rows)| (rows, count))
+        .map(|(count, _)| count)
+        .or_else(|| rest.first().map(|&(_, count)| count))
+        .unwrap_or(field_count);
+    let ends_with_newline = contents.ends_with('\n');
+    Some(LastRecordReport {
+        line,
+        field_count,
+        expected_fields,
+        ends_with_newline,
+        probably_truncated: field_count < expected_fields
+            || (field_count != expected_fields && !ends_with_newline),
+    })
+}
+
+/// Flags a final record cut short by an interrupted write; see `last_record_report`.
+#[tauri::command]
+pub(crate) fn check_last_record(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    delimiter: Option<String>,
+) -> Result<Option<LastRecordReport>, String> {
+    let contents = read_decoded(&settings, &resolve_input_path(&path))?;
+    Ok(last_record_report(&contents, delimiter.as_deref()))
+}
+
+fn sample_schema(
+    settings: &SettingsState,
+    path: &str,
+    delimiter: Option<&str>,
+    sample_rows: usize,
+    include_metadata: Option<bool>,
+) -> Result<(Vec<ColumnSchema>, FieldCounts), String> {
+    let contents = read_data(settings, &resolve_input_path(path), include_metadata)?.contents;
+    let mut rows = parse_rows(&contents, delimiter);
+    let header = rows.next();
+    let sample: Vec<Vec<&str>> = rows.take(sample_rows).collect();
+    let header_fields = header.as_ref().map_or(0, Vec::len);
+    let counts = FieldCounts {
+        header_fields,
+        mismatched_rows: sample
+            .iter()
+            .filter(|row| row.len() != header_fields)
+            .count(),
+    };
+    Ok((infer_schema(header.as_deref(), &sample), counts))
+}
+
+/// Compares the header columns of two files (first non-blank line) and the types inferred
+/// from their first `DEFAULT_SCHEMA_SAMPLE_ROWS` rows. Files are compatible when they share
+/// the same columns in the same order with matching types and consistent field counts.
+#[tauri::command]
+pub(crate) fn compare_ntr_schemas(
+    settings: tauri::State<SettingsState>,
+    a: String,
+    b: String,
+    delimiter: Option<String>,
+    include_metadata: Option<bool>,
+) -> Result<SchemaComparison, String> {
+    let delimiter = delimiter.as_deref();
+    let sample = |path: &str| {
+        let rows = DEFAULT_SCHEMA_SAMPLE_ROWS;
+        sample_schema(&settings, path, delimiter, rows, include_metadata)
+    };
+    let (columns_a, field_counts_a) = sample(&a)?;
+    let (columns_b, field_counts_b) = sample(&b)?;
+    let find = |columns: &[ColumnSchema], name: &str| {
+        columns.iter().position(|column| column.name == name)
+    };
+
+    let only_in = |from: &[ColumnSchema], other: &[ColumnSchema]| -> Vec<String> {
+        from.iter()
+            .filter(|column| find(other, &column.name).is_none())
+            .map(|column| column.name.clone())
+            .collect()
+    };
+    let only_in_a = only_in(&columns_a, &columns_b);
+    let only_in_b = only_in(&columns_b, &columns_a);
+
+    let shared_a: Vec<&ColumnSchema> = columns_a
+        .iter()
+        .filter(|column| find(&columns_b, &column.name).is_some())
+        .collect();
+    let shared_b: Vec<&ColumnSchema> = columns_b
+        .iter()
+        .filter(|column| find(&columns_a, &column.name).is_some())
+        .collect();
+    let mut reordered = Vec::new();
+    let mut type_mismatches = Vec::new();
+    for (position, column_a) in shared_a.iter().enumerate() {
+        let Some(column_b) = shared_b.iter().find(|column| column.name == column_a.name) else {
+            continue;
+        };
+        if shared_b.get(position).map(|column| &column.name) != Some(&column_a.name) {
+            reordered.push(ReorderedColumn {
+                name: column_a.name.clone(),
+                index_a: column_a.index,
+                index_b: column_b.index,
+            });
+        }
+        let comparable = |field_type: FieldType| field_type != FieldType::Empty;
+        if comparable(column_a.field_type)
+            && comparable(column_b.field_type)
+            && column_a.field_type != column_b.field_type
+        {
+            type_mismatches.push(TypeMismatch {
+                name: column_a.name.clone(),
+                type_a: column_a.field_type,
+                type_b: column_b.field_type,
+            });
+        }
+    }
+
+    let compatible = only_in_a.is_empty()
+        && only_in_b.is_empty()
+        && reordered.is_empty()
+        && type_mismatches.is_empty()
+        && field_counts_a.header_fields == field_counts_b.header_fields
+        && field_counts_a.mismatched_rows == 0
+        && field_counts_b.mismatched_rows == 0;
+    Ok(SchemaComparison {
+        only_in_a,
+        only_in_b,
+        reordered,
+        type_mismatches,
+        field_counts_a,
+        field_counts_b,
+        compatible,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenizes_fields_with_utf16_offsets() {
+        let span = |start, end, kind| TokenSpan { start, end, kind };
+        assert_eq!(
+            tokenize_fields("Rør; \"DN 200\" ;-1.5;TRUE;;x", Some(";")),
+            vec![
+                span(0, 3, TokenKind::Plain),
+                span(5, 13, TokenKind::QuotedString),
+                span(15, 19, TokenKind::Number),
+                span(20, 24, TokenKind::Boolean),
+                span(26, 27, TokenKind::Plain),
+            ]
+        );
+    }
+
+    #[test]
+    fn pads_fields_to_the_widest_sampled_value() {
+        let aligned = align_rows("NAME;DN\nP100;25\n\nP2;300\nP3000000;4\n", Some(";"), 3);
+        assert_eq!(aligned.column_widths, vec![4, 3]);
+        assert!(aligned.sampled);
+        assert_eq!(
+            aligned.contents,
+            "NAME ; DN\nP100 ; 25\n\nP2   ; 300\nP3000000 ; 4\n"
+        );
+    }
+
+    #[test]
+    fn reports_unbalanced_and_partial_quotes() {
+        let contents = "NAME;TEXT\nP1;\"a;b\"\nP2;ab\"c\"\nP3;\"open\n'x';'y''z'\n";
+        assert_eq!(
+            quoting_issues(contents, ";", '"'),
+            [
+                QuotingIssue {
+                    line: 3,
+                    column: Some(1),
+                    problem: QuotingProblem::PartialField,
+                },
+                QuotingIssue {
+                    line: 4,
+                    column: None,
+                    problem: QuotingProblem::Unbalanced,
+                },
+            ]
+        );
+        assert_eq!(quoting_issues(contents, ";", '\''), []);
+    }
+
+    #[test]
+    fn splits_leading_metadata_block() {
+        let (entries, body) =
+            split_metadata("#!ntr-export\n---\nsource: Plant 3\nREV=2\n---\nRO P1=1\n");
+        let pairs: Vec<(&str, &str)> = entries
+            .iter()
+            .map(|entry| (entry.key.as_str(), entry.value.as_str()))
+            .collect();
+        assert_eq!(
+            pairs,
+            [
+                ("shebang", "ntr-export"),
+                ("source", "Plant 3"),
+                ("REV", "2")
+            ]
+        );
+        assert_eq!(body, "RO P1=1\n");
+
+        let unclosed = "---\nRO P1=1\n";
+        assert_eq!(split_metadata(unclosed), (Vec::new(), unclosed));
+    }
+
+    #[test]
+    fn reads_data_without_the_preamble_by_default() {
+        let path = std::env::temp_dir().join(format!("ntr-data-{}.ntr", std::process::id()));
+        let contents = "---\nsource: Plant 3\n---\nRO P1=1\n";
+        std::fs::write(&path, contents).expect("write sample");
+        let settings = SettingsState::in_memory(Default::default());
+        let data = read_data(&settings, &path, None).expect("sample reads");
+        assert_eq!(
+            (data.contents.as_str(), data.skipped_lines),
+            ("RO P1=1\n", 3)
+        );
+        let data = read_data(&settings, &path, Some(true)).expect("sample reads");
+        assert_eq!((data.contents.as_str(), data.skipped_lines), (contents, 0));
+        std::fs::remove_file(&path).expect("remove sample");
+    }
+
+    #[test]
+    fn reads_a_data_prefix_past_the_preamble() {
+        let path = std::env::temp_dir().join(format!("ntr-prefix-{}.ntr", std::process::id()));
+        let contents = "---\nsource: Plant 3\n\nunit: mm\n---\nA B\n\n1 2\n3 4\n5 6\n";
+        std::fs::write(&path, contents).expect("write sample");
+        let settings = SettingsState::in_memory(Default::default());
+        let prefix = read_data_prefix(&settings, &path, None, 3).expect("sample reads");
+        assert_eq!(prefix, "A B\n\n1 2\n3 4\n");
+        let prefix = read_data_prefix(&settings, &path, Some(true), 3).expect("sample reads");
+        assert_eq!(prefix, "---\nsource: Plant 3\n\nunit: mm\n");
+        let prefix = read_data_prefix(&settings, &path, None, 10).expect("sample reads");
+        assert_eq!(prefix, "A B\n\n1 2\n3 4\n5 6\n");
+
+        std::fs::write(&path, "---\nA B\n1 2\n").expect("write sample");
+        let prefix = read_data_prefix(&settings, &path, None, 2).expect("sample reads");
+        assert_eq!(prefix, "---\nA B\n");
+
+        let endless: String = (0..2 * MAX_PREVIEW_METADATA_LINES)
+            .map(|row| format!("{row} {row}\n"))
+            .collect();
+        std::fs::write(&path, format!("---\n{endless}---\n")).expect("write sample");
+        let prefix = read_data_prefix(&settings, &path, None, 2).expect("sample reads");
+        assert_eq!(prefix, "---\n0 0\n");
+        std::fs::remove_file(&path).expect("remove sample");
+    }
+
+    #[test]
+    fn counts_tokens_most_frequent_first() {
+        let path = std::env::temp_dir().join(format!("ntr-tokens-{}.ntr", std::process::id()));
+        std::fs::write(&path, "RO P1 ro\nBOG P1\n\nro  P2\n").expect("write sample");
+        let settings = SettingsState::in_memory(Default::default());
+        let tally = |case_insensitive| -> Vec<(String, usize)> {
+            count_tokens(&settings, &path, None, case_insensitive)
+                .expect("tokens count")
+                .into_iter()
+                .map(|token| (token.token, token.count))
+                .collect()
+        };
+        let exact = tally(false);
+        let folded = tally(true);
+        std::fs::remove_file(&path).expect("remove sample");
+
+        let expected = |pairs: &[(&str, usize)]| -> Vec<(String, usize)> {
+            pairs
+                .iter()
+                .map(|&(token, count)| (token.to_string(), count))
+                .collect()
+        };
+        assert_eq!(
+            exact,
+            expected(&[("P1", 2), ("ro", 2), ("BOG", 1), ("P2", 1), ("RO", 1)])
+        );
+        assert_eq!(
+            folded,
+            expected(&[("ro", 3), ("p1", 2), ("bog", 1), ("p2", 1)])
+        );
+    }
+
+    #[test]
+    fn classifies_dialect_by_markers() {
+        let insulated = "C General settings\nGEN TMONT=20 UNITKT=MM CODE=EN13480\n\
+            IS NAME=ISOLERING GAM=130\nDN NAME=DN10 DA=17.2 ISOTYP=ISOLERING ISODICKE=120\n";
+        assert_eq!(classify_dialect(insulated), Some(("ntr-insulated", 1.0)));
+
+        let headerless = "DN NAME=DN10 DA=17.2\nRO P1='0, 0, 0' P2='1, 0, 0' DN=DN10\n";
+        assert_eq!(classify_dialect(headerless), Some(("ntr-headerless", 1.0)));
+
+        assert_eq!(classify_dialect("hello world\n"), None);
+    }
+
+    #[test]
+    fn flags_formula_cells_but_not_negative_numbers() {
+        assert!(is_formula_cell("=HYPERLINK(\"x\")"));
+        assert!(is_formula_cell(" \"@SUM(A1)\""));
+        assert!(is_formula_cell("+cmd|' /C calc'!A0"));
+        assert!(is_formula_cell("-2+3"));
+        assert!(!is_formula_cell("-12.5"));
+        assert!(!is_formula_cell("DN-200"));
+    }
+
+    #[test]
+    fn infers_column_types_and_counts_mismatches() {
+        let contents = "NAME;DN;LENGTH;ACTIVE;DATE\nP1;100;1.5;true;2024-01-31\nP2;;2;false;31.01.2024\nP3;x;3;true;\n";
+        let mut rows = parse_rows(contents, Some(";"));
+        let header = rows.next();
+        let sample: Vec<Vec<&str>> = rows.collect();
+        let columns = infer_schema(header.as_deref(), &sample);
+
+        let types: Vec<FieldType> = columns.iter().map(|column| column.field_type).collect();
+        assert_eq!(
+            types,
+            [
+                FieldType::String,
+                FieldType::Integer,
+                FieldType::Float,
+                FieldType::Boolean,
+                FieldType::Date
+            ]
+        );
+        assert_eq!(columns[1].name, "DN");
+        assert_eq!((columns[1].mismatched, columns[1].empty), (1, 1));
+        assert_eq!(columns[4].empty, 1);
+    }
+
+    #[test]
+    fn splits_on_multi_character_delimiters_outside_quotes() {
+        assert_eq!(
+            split_fields("RO||\"a||b\"||3", Some("||")),
+            ["RO", "\"a||b\"", "3"]
+        );
+        assert_eq!(split_fields("RO|x||12", Some("||")), ["RO|x", "12"]);
+        assert_eq!(
+            split_fields("BOG\t\t1\t2\t\t", Some("\t\t")),
+            ["BOG", "1\t2", ""]
+        );
+
+        assert_eq!(detect_delimiter("A||B||C\n1||2||3\n"), Some("||"));
+        assert_eq!(detect_delimiter("A\t\tB\n1\t\t2\n"), Some("\t\t"));
+        assert_eq!(detect_delimiter("RO 1 2\nBOG 3 4\n"), None);
+    }
+
+    #[test]
+    fn flags_short_unterminated_last_record() {
+        let report = last_record_report("a;b;c\n1;2;3\n4;5;6\n7;8", Some(";")).unwrap();
+        assert_eq!(report.line, 4);
+        assert_eq!((report.field_count, report.expected_fields), (2, 3));
+        assert!(!report.ends_with_newline && report.probably_truncated);
+
+        let report = last_record_report("---\nk: v\n---\na;b\n1;2\n", Some(";")).unwrap();
+        assert_eq!(
+            (report.line, report.field_count, report.expected_fields),
+            (5, 2, 2)
+        );
+        assert!(!report.probably_truncated);
+        assert_eq!(last_record_report("\n\n", Some(";")), None);
+    }
+
+    #[test]
+    fn matches_values_against_column_types() {
+        assert!(value_matches("12", FieldType::Float));
+        assert!(value_matches(" ", FieldType::Integer));
+        assert!(value_matches("abc", FieldType::String));
+        assert!(!value_matches("1.5", FieldType::Integer));
+        assert!(!value_matches("abc", FieldType::Date));
+    }
+}
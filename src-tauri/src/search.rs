@@ -0,0 +1,318 @@
+use serde::Serialize;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+use tauri::Emitter;
+
+use crate::{read_options_for, resolve_input_path, settings::SettingsState, DecodedLines};
+
+/// Cancellation token of the one running job of a kind; starting another job cancels it.
+#[derive(Default)]
+struct CancelSlot {
+    current: Mutex<Option<Arc<AtomicBool>>>,
+}
+
+impl CancelSlot {
+    /// Cancels the running job, if any, and registers a fresh token for the new one.
+    fn start(&self) -> Arc<AtomicBool> {
+        let token = Arc::new(AtomicBool::new(false));
+        let previous = self
+            .current
+            .lock()
+            .expect("cancel slot poisoned")
+            .replace(token.clone());
+        if let Some(previous) = previous {
+            previous.store(true, Ordering::Relaxed);
+        }
+        token
+    }
+
+    /// Cancels the running job, if any.
+    fn cancel(&self) {
+        if let Some(token) = self.current.lock().expect("cancel slot poisoned").take() {
+            token.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Clears the slot when `token` is still the registered one, i.e. no newer job started.
+    fn finish(&self, token: &Arc<AtomicBool>) {
+        let mut current = self.current.lock().expect("cancel slot poisoned");
+        if current
+            .as_ref()
+            .is_some_and(|current| Arc::ptr_eq(current, token))
+        {
+            current.take();
+        }
+    }
+}
+
+/// The running streamed search; see `CancelSlot`.
+#[derive(Default)]
+pub(crate) struct SearchState {
+    cancel: CancelSlot,
+}
+
+/// The running `stream_ntr_file`; see `CancelSlot`.
+#[derive(Default)]
+pub(crate) struct StreamState {
+    cancel: CancelSlot,
+}
+
+#[derive(Clone, Serialize)]
+pub(crate) struct SearchMatch {
+    line: usize,
+    column: usize,
+    text: String,
+}
+
+/// Reads `DecodedLines` with the file's remembered, declared or detected encoding, the same
+/// one the viewer shows, and returns every occurrence of `query`.
+#[tauri::command(async)]
+pub(crate) fn search_ntr_file(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    query: String,
+) -> Result<Vec<SearchMatch>, String> {
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+    let resolved = resolve_input_path(&path);
+    let options = read_options_for(&settings, &resolved);
+    let mut matches = Vec::new();
+    for (index, line) in DecodedLines::open(&resolved, &options, "Searching")?.enumerate() {
+        matches.extend(line_matches(index + 1, &line?, &query));
+    }
+    Ok(matches)
+}
+
+/// The occurrences of `query` in `line`, with 1-based character columns.
+fn line_matches<'a>(
+    line_number: usize,
+    line: &'a str,
+    query: &'a str,
+) -> impl Iterator<Item = SearchMatch> + 'a {
+    line.match_indices(query)
+        .map(move |(column, _)| SearchMatch {
+            line: line_number,
+            column: line[..column].chars().count() + 1,
+            text: line.to_string(),
+        })
+}
+
+#[derive(Clone, Serialize)]
+struct SearchMatchPayload {
+    path: String,
+    #[serde(flatten)]
+    found: SearchMatch,
+}
+
+#[derive(Clone, Serialize)]
+struct SearchDonePayload {
+    path: String,
+    matches: usize,
+    lines_scanned: usize,
+    cancelled: bool,
+}
+
+/// Like `search_ntr_file`, but reads `DecodedLines` and emits each hit as an
+/// `ntr-search-match` event, followed by `ntr-search-done` with the totals. `cancel_ntr_search`
+/// or a newer search stops the scan early; the done event then reports `cancelled`.
+#[tauri::command(async)]
+pub(crate) fn search_ntr_file_streaming(
+    app: tauri::AppHandle,
+    settings: tauri::State<SettingsState>,
+    search: tauri::State<SearchState>,
+    path: String,
+    query: String,
+) -> Result<(), String> {
+    let cancel = search.cancel.start();
+    let mut done = SearchDonePayload {
+        path: path.clone(),
+        matches: 0,
+        lines_scanned: 0,
+        cancelled: false,
+    };
+    let result = (|| {
+        if query.is_empty() {
+            return Ok(());
+        }
+        let resolved = resolve_input_path(&path);
+        let options = read_options_for(&settings, &resolved);
+        let lines = DecodedLines::open(&resolved, &options, "Streaming search of")?;
+        for (index, line) in lines.enumerate() {
+            if cancel.load(Ordering::Relaxed) {
+                done.cancelled = true;
+                break;
+            }
+            let line = line?;
+            done.lines_scanned += 1;
+            for found in line_matches(index + 1, &line, &query) {
+                done.matches += 1;
+                let payload = SearchMatchPayload {
+                    path: path.clone(),
+                    found,
+                };
+                if let Err(err) = app.emit("ntr-search-match", payload) {
+                    log::error!("Failed to emit search match: {err}");
+                }
+            }
+        }
+        Ok::<_, String>(())
+    })();
+
+    search.cancel.finish(&cancel);
+    result?;
+    app.emit("ntr-search-done", done)
+        .map_err(|err| format!("Failed to emit search done event: {err}"))
+}
+
+/// Cancels the running streamed search, if any.
+#[tauri::command]
+pub(crate) fn cancel_ntr_search(search: tauri::State<SearchState>) {
+    search.cancel.cancel();
+}
+
+/// Lines per `ntr-file-batch` event when `stream_ntr_file` is not given a batch size.
+const DEFAULT_STREAM_BATCH_LINES: usize = 1000;
+
+#[derive(Clone, Serialize)]
+struct FileBatchPayload {
+    path: String,
+    /// 1-based line number of the first line in `lines`.
+    start_line: usize,
+    lines: Vec<String>,
+}
+
+#[derive(Clone, Serialize)]
+struct FileCompletePayload {
+    path: String,
+    encoding: String,
+    lines: usize,
+    cancelled: bool,
+}
+
+/// Decodes the file line by line and emits it as `ntr-file-batch` events of `batch_lines`
+/// lines, followed by `ntr-file-complete`, so the viewer can render the first screen of a huge
+/// file before the rest is decoded. `cancel_ntr_stream` or a newer stream stops it early; the
+/// complete event then reports `cancelled`. The file is read through its own `DecodedLines`,
+/// so no shared map is locked while batches are emitted.
+#[tauri::command(async)]
+pub(crate) fn stream_ntr_file(
+    app: tauri::AppHandle,
+    settings: tauri::State<SettingsState>,
+    stream: tauri::State<StreamState>,
+    path: String,
+    batch_lines: Option<usize>,
+) -> Result<(), String> {
+    let batch_lines = batch_lines.unwrap_or(DEFAULT_STREAM_BATCH_LINES);
+    if batch_lines == 0 {
+        return Err("Batch size must be greater than zero".into());
+    }
+    let cancel = stream.cancel.start();
+
+    let resolved = resolve_input_path(&path);
+    let options = read_options_for(&settings, &resolved);
+    let result = DecodedLines::open(&resolved, &options, "Streaming").and_then(|lines| {
+        let encoding = lines.encoding.name().to_string();
+        let (lines, cancelled) = stream_batches(lines, batch_lines, &cancel, |start, batch| {
+            emit_file_batch(&app, &path, start, batch);
+        })?;
+        Ok(FileCompletePayload {
+            path: path.clone(),
+            encoding,
+            lines,
+            cancelled,
+        })
+    });
+
+    stream.cancel.finish(&cancel);
+    app.emit("ntr-file-complete", result?)
+        .map_err(|err| format!("Failed to emit file complete event: {err}"))
+}
+
+/// Hands `lines` to `emit` in batches of `batch_lines` along with the 1-based number of each
+/// batch's first line. `cancel` is checked before every full batch; returns the number of lines
+/// emitted and whether the run was cancelled.
+fn stream_batches(
+    lines: impl Iterator<Item = Result<String, String>>,
+    batch_lines: usize,
+    cancel: &AtomicBool,
+    mut emit: impl FnMut(usize, Vec<String>),
+) -> Result<(usize, bool), String> {
+    let mut emitted = 0;
+    let mut batch = Vec::with_capacity(batch_lines);
+    for line in lines {
+        batch.push(line?);
+        if batch.len() == batch_lines {
+            if cancel.load(Ordering::Relaxed) {
+                return Ok((emitted, true));
+            }
+            emit(emitted + 1, std::mem::take(&mut batch));
+            emitted += batch_lines;
+        }
+    }
+    if !batch.is_empty() {
+        let start = emitted + 1;
+        emitted += batch.len();
+        emit(start, batch);
+    }
+    Ok((emitted, false))
+}
+
+fn emit_file_batch(app: &tauri::AppHandle, path: &str, start_line: usize, lines: Vec<String>) {
+    let payload = FileBatchPayload {
+        path: path.to_string(),
+        start_line,
+        lines,
+    };
+    if let Err(err) = app.emit("ntr-file-batch", payload) {
+        log::error!("Failed to emit file batch: {err}");
+    }
+}
+
+/// Cancels the running `stream_ntr_file`, if any.
+#[tauri::command]
+pub(crate) fn cancel_ntr_stream(stream: tauri::State<StreamState>) {
+    stream.cancel.cancel();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn starting_a_job_cancels_the_previous_one() {
+        let slot = CancelSlot::default();
+        let first = slot.start();
+        let second = slot.start();
+        assert!(first.load(Ordering::Relaxed));
+        assert!(!second.load(Ordering::Relaxed));
+        slot.finish(&first);
+        slot.cancel();
+        assert!(second.load(Ordering::Relaxed));
+
+        let third = slot.start();
+        slot.finish(&third);
+        slot.cancel();
+        assert!(!third.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn streams_batches_until_cancelled() {
+        let lines = || (1..=5).map(|number| Ok(number.to_string()));
+        let mut batches = Vec::new();
+        let cancel = AtomicBool::new(false);
+        let streamed = stream_batches(lines(), 2, &cancel, |start, batch| {
+            batches.push((start, batch));
+        });
+        assert_eq!(streamed, Ok((5, false)));
+        let starts: Vec<usize> = batches.iter().map(|(start, _)| *start).collect();
+        assert_eq!(starts, [1, 3, 5]);
+        assert_eq!(batches[2].1, ["5"]);
+
+        cancel.store(true, Ordering::Relaxed);
+        let streamed = stream_batches(lines(), 2, &cancel, |_, _| panic!("emitted after cancel"));
+        assert_eq!(streamed, Ok((0, true)));
+    }
+}
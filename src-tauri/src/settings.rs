@@ -0,0 +1,215 @@
+use encoding_rs::Encoding;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tauri::{Emitter, Manager};
+
+use crate::write_atomic;
+
+const SETTINGS_FILE_NAME: &str = "settings.json";
+
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Settings {
+    /// Normalized file path → encoding label chosen by the user for that file.
+    #[serde(default)]
+    pub file_encodings: HashMap<String, String>,
+    /// Encoding labels tried in order after UTF-8; empty uses the built-in default chain.
+    #[serde(default)]
+    pub fallback_encodings: Vec<String>,
+    /// Directory of the most recent save, used as the starting point of the save dialog.
+    #[serde(default)]
+    pub last_save_dir: Option<String>,
+}
+
+impl Settings {
+    /// Rejects encoding labels `encoding_rs` does not know, which would otherwise be dropped
+    /// silently when the settings are used.
+    fn validate(&self) -> Result<(), String> {
+        let labels = self
+            .file_encodings
+            .values()
+            .chain(self.fallback_encodings.iter());
+        for label in labels {
+            if Encoding::for_label(label.trim().as_bytes()).is_none() {
+                return Err(format!("Unknown encoding label: {label}"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Settings persisted as JSON in the app config directory. When the directory cannot be
+/// resolved the settings still work for the session but are not written to disk.
+pub(crate) struct SettingsState {
+    path: Option<PathBuf>,
+    inner: Mutex<Settings>,
+    /// Watches the settings file for external edits; see `watch`.
+    watcher: Mutex<Option<RecommendedWatcher>>,
+}
+
+impl SettingsState {
+    pub fn load(app: &tauri::AppHandle) -> Self {
+        let path = app
+            .path()
+            .app_config_dir()
+            .ok()
+            .map(|dir| dir.join(SETTINGS_FILE_NAME));
+        let settings = path
+            .as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default();
+        SettingsState {
+            path,
+            inner: Mutex::new(settings),
+            watcher: Mutex::new(None),
+        }
+    }
+
+    /// Watches the settings file so edits made outside the app, or by a sync tool, take effect
+    /// without a restart; each accepted change is emitted as `ntr-settings-changed`.
+    pub fn watch(&self, app: &tauri::AppHandle) {
+        let Some(path) = self.path.clone() else {
+            return;
+        };
+        let Some(dir) = path.parent().map(Path::to_path_buf) else {
+            return;
+        };
+        if let Err(err) = std::fs::create_dir_all(&dir) {
+            log::warn!("Not watching settings, config directory unavailable: {err}");
+            return;
+        }
+        let app_handle = app.clone();
+        let watcher = notify::recommended_watcher(move |res: Result<Event, notify::Error>| {
+            let Ok(event) = res else {
+                return;
+            };
+            if matches!(event.kind, EventKind::Access(_))
+                || !event
+                    .paths
+                    .iter()
+                    .any(|changed| changed.file_name() == path.file_name())
+            {
+                return;
+            }
+            app_handle.state::<SettingsState>().reload(&app_handle);
+        });
+        let watcher = watcher.and_then(|mut watcher| {
+            watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+            Ok(watcher)
+        });
+        match watcher {
+            Ok(watcher) => *self.watcher.lock().expect("settings watcher poisoned") = Some(watcher),
+            Err(err) => log::warn!("Failed to watch settings file: {err}"),
+        }
+    }
+
+    /// Re-reads the settings file after an external change and emits the accepted settings;
+    /// see `reload_from_disk`.
+    fn reload(&self, app: &tauri::AppHandle) {
+        let Some(settings) = self.reload_from_disk() else {
+            return;
+        };
+        if let Err(err) = app.emit("ntr-settings-changed", settings) {
+            log::error!("Failed to emit settings changed event: {err}");
+        }
+    }
+
+    /// Replaces the settings with the file's contents and returns them. Unparsable or invalid
+    /// contents are logged and ignored, keeping the current settings; unchanged contents (such
+    /// as our own writes) return `None`.
+    fn reload_from_disk(&self) -> Option<Settings> {
+        let path = self.path.as_ref()?;
+        let raw = std::fs::read_to_string(path).ok()?;
+        let settings = serde_json::from_str::<Settings>(&raw)
+            .map_err(|err| err.to_string())
+            .and_then(|settings| settings.validate().map(|()| settings));
+        let settings = match settings {
+            Ok(settings) => settings,
+            Err(err) => {
+                log::warn!("Ignoring invalid settings file {}: {err}", path.display());
+                return None;
+            }
+        };
+
+        let mut guard = self.inner.lock().expect("settings state poisoned");
+        if *guard == settings {
+            return None;
+        }
+        *guard = settings.clone();
+        drop(guard);
+        log::info!("Reloaded settings from {}", path.display());
+        Some(settings)
+    }
+
+    /// Settings that are never persisted, for tests.
+    #[cfg(test)]
+    pub fn in_memory(settings: Settings) -> Self {
+        SettingsState {
+            path: None,
+            inner: Mutex::new(settings),
+            watcher: Mutex::new(None),
+        }
+    }
+
+    pub fn read<T>(&self, f: impl FnOnce(&Settings) -> T) -> T {
+        let guard = self.inner.lock().expect("settings state poisoned");
+        f(&guard)
+    }
+
+    pub fn update<T>(&self, f: impl FnOnce(&mut Settings) -> T) -> Result<T, String> {
+        let mut guard = self.inner.lock().expect("settings state poisoned");
+        let result = f(&mut guard);
+        self.persist(&guard)?;
+        Ok(result)
+    }
+
+    fn persist(&self, settings: &Settings) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|err| format!("Failed to create settings directory: {err}"))?;
+        }
+        let serialized = serde_json::to_string_pretty(settings)
+            .map_err(|err| format!("Failed to serialize settings: {err}"))?;
+        // Atomic so the settings watcher never reads a half-written file.
+        write_atomic(path, serialized.as_bytes())
+            .map_err(|err| format!("Failed to write settings: {err}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reloads_only_valid_changed_settings() {
+        let path = std::env::temp_dir().join(format!("ntr-settings-{}.json", std::process::id()));
+        let state = SettingsState {
+            path: Some(path.clone()),
+            inner: Mutex::new(Settings::default()),
+            watcher: Mutex::new(None),
+        };
+
+        std::fs::write(&path, r#"{"fallback_encodings": ["iso-8859-15"]}"#).unwrap();
+        let reloaded = state
+            .reload_from_disk()
+            .map(|settings| settings.fallback_encodings);
+        assert_eq!(reloaded, Some(vec!["iso-8859-15".to_string()]));
+        assert!(state.reload_from_disk().is_none());
+
+        std::fs::write(&path, r#"{"fallback_encodings": ["klingon"]}"#).unwrap();
+        assert!(state.reload_from_disk().is_none());
+        std::fs::write(&path, "{").unwrap();
+        assert!(state.reload_from_disk().is_none());
+        let kept = state.read(|settings| settings.fallback_encodings.clone());
+        assert_eq!(kept, ["iso-8859-15"]);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,275 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc,
+    thread,
+};
+use tauri::{Emitter, Manager};
+
+use crate::{
+    log_watch_event, normalize_path, now_ms, resolve_absolute_path, resolve_input_path,
+    write_atomic, DIFF_DEBOUNCE,
+};
+
+/// Directory under the app data directory that holds one snapshot folder per watched file.
+const SNAPSHOTS_DIR_NAME: &str = "snapshots";
+const DEFAULT_MAX_SNAPSHOTS: usize = 50;
+const SNAPSHOT_EXTENSION: &str = "ntr";
+
+/// Enables snapshot mode on `start_file_watch`: every distinct version of the file is copied
+/// into the app data directory; see `spawn_snapshot_worker`.
+#[derive(Deserialize)]
+pub(crate) struct SnapshotWatchOptions {
+    /// Snapshots kept per file; the oldest are deleted beyond this. Defaults to
+    /// `DEFAULT_MAX_SNAPSHOTS`.
+    max_count: Option<usize>,
+}
+
+#[derive(Clone, Serialize)]
+struct SnapshotPayload {
+    path: String,
+    snapshot_path: String,
+}
+
+#[derive(Serialize)]
+pub(crate) struct SnapshotInfo {
+    path: String,
+    /// Milliseconds since the Unix epoch, taken from the snapshot's file name.
+    created_ms: u64,
+    size: u64,
+}
+
+/// `<app data>/snapshots/<file stem>-<path hash>`; the hash keeps same-named files from
+/// different folders apart.
+fn snapshot_dir(app: &tauri::AppHandle, file_path: &Path) -> Result<PathBuf, String> {
+    let base = app
+        .path()
+        .app_data_dir()
+        .map_err(|err| format!("Failed to resolve app data directory: {err}"))?;
+    let stem = file_path
+        .file_stem()
+        .map_or_else(|| "file".into(), |stem| stem.to_string_lossy());
+    let digest = Sha256::digest(normalize_path(file_path).as_bytes());
+    let key: String = digest[..4]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    Ok(base.join(SNAPSHOTS_DIR_NAME).join(format!("{stem}-{key}")))
+}
+
+/// Snapshots in `dir` as `(timestamp, path)`, oldest first. Files not named
+/// `<timestamp>.ntr` are ignored.
+fn snapshot_files(dir: &Path) -> Vec<(u64, PathBuf)> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<(u64, PathBuf)> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext == SNAPSHOT_EXTENSION)
+        })
+        .filter_map(|path| {
+            let created = path.file_stem()?.to_str()?.parse().ok()?;
+            Some((created, path))
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+fn content_hash(bytes: &[u8]) -> Vec<u8> {
+    Sha256::digest(bytes).to_vec()
+}
+
+/// Copies the file into `dir` unless its content hash equals `last_hash`, then deletes the
+/// oldest snapshots beyond `max_count`. Returns the new snapshot, if one was written.
+fn take_snapshot(
+    dir: &Path,
+    file_path: &Path,
+    last_hash: &mut Option<Vec<u8>>,
+    max_count: usize,
+) -> Result<Option<PathBuf>, String> {
+    let bytes =
+        std::fs::read(file_path).map_err(|err| format!("Failed to read file bytes: {err}"))?;
+    let hash = content_hash(&bytes);
+    if last_hash.as_ref() == Some(&hash) {
+        return Ok(None);
+    }
+    std::fs::create_dir_all(dir)
+        .map_err(|err| format!("Failed to create snapshot directory: {err}"))?;
+    let mut created = now_ms();
+    let mut snapshot = dir.join(format!("{created}.{SNAPSHOT_EXTENSION}"));
+    while snapshot.exists() {
+        created += 1;
+        snapshot = dir.join(format!("{created}.{SNAPSHOT_EXTENSION}"));
+    }
+    write_atomic(&snapshot, &bytes)?;
+    *last_hash = Some(hash);
+
+    let files = snapshot_files(dir);
+    for (_, stale) in files.iter().take(files.len().saturating_sub(max_count)) {
+        if let Err(err) = std::fs::remove_file(stale) {
+            log::warn!("Failed to delete old snapshot {}: {err}", stale.display());
+        }
+    }
+    Ok(Some(snapshot))
+}
+
+/// Snapshots the watched file when the watch starts and again whenever it has been quiet for
+/// `DIFF_DEBOUNCE` after a change, emitting `ntr-file-snapshot` for each copy written. The
+/// worker exits once the watcher (and with it the sender) is dropped.
+pub(crate) fn spawn_snapshot_worker(
+    app: tauri::AppHandle,
+    file_path: PathBuf,
+    emit_path: String,
+    options: SnapshotWatchOptions,
+) -> Result<mpsc::Sender<()>, String> {
+    let max_count = options.max_count.unwrap_or(DEFAULT_MAX_SNAPSHOTS);
+    if max_count == 0 {
+        return Err("Snapshot count must be greater than zero".into());
+    }
+    let dir = snapshot_dir(&app, &file_path)?;
+    let mut last_hash = snapshot_files(&dir)
+        .last()
+        .and_then(|(_, newest)| std::fs::read(newest).ok())
+        .map(|bytes| content_hash(&bytes));
+
+    let (sender, receiver) = mpsc::channel::<()>();
+    thread::spawn(move || {
+        let mut snapshot = || match take_snapshot(&dir, &file_path, &mut last_hash, max_count) {
+            Ok(Some(snapshot)) => {
+                let payload = SnapshotPayload {
+                    path: emit_path.clone(),
+                    snapshot_path: normalize_path(&snapshot),
+                };
+                if let Err(err) = app.emit("ntr-file-snapshot", payload) {
+                    log::error!("Failed to emit file snapshot event: {err}");
+                }
+            }
+            Ok(None) => log_watch_event("Content unchanged; snapshot skipped"),
+            Err(err) => log_watch_event(&format!("Snapshot failed: {err}")),
+        };
+        snapshot();
+        while receiver.recv().is_ok() {
+            loop {
+                match receiver.recv_timeout(DIFF_DEBOUNCE) {
+                    Ok(()) => continue,
+                    Err(mpsc::RecvTimeoutError::Timeout) => break,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => return,
+                }
+            }
+            snapshot();
+        }
+    });
+    Ok(sender)
+}
+
+/// Snapshots taken of `path` by watches in snapshot mode, newest first.
+#[tauri::command]
+pub(crate) fn list_snapshots(
+    app: tauri::AppHandle,
+    path: String,
+) -> Result<Vec<SnapshotInfo>, String> {
+    let file_path = resolve_absolute_path(&resolve_input_path(&path));
+    let dir = snapshot_dir(&app, &file_path)?;
+    Ok(snapshot_files(&dir)
+        .into_iter()
+        .rev()
+        .map(|(created_ms, snapshot)| SnapshotInfo {
+            size: std::fs::metadata(&snapshot).map_or(0, |metadata| metadata.len()),
+            path: normalize_path(&snapshot),
+            created_ms,
+        })
+        .collect())
+}
+
+/// Writes the snapshot's bytes over `target` atomically, so a failed restore leaves the
+/// current file intact. Only files in `target`'s own snapshot folder are accepted, so the
+/// command cannot be used to copy arbitrary files over `target`.
+#[tauri::command]
+pub(crate) fn restore_snapshot(
+    app: tauri::AppHandle,
+    snapshot_path: String,
+    target: String,
+) -> Result<(), String> {
+    let target = resolve_input_path(&target);
+    let dir = snapshot_dir(&app, &resolve_absolute_path(&target))?;
+    let snapshot = resolve_input_path(&snapshot_path)
+        .canonicalize()
+        .ok()
+        .filter(|snapshot| snapshot.is_file())
+        .ok_or("Snapshot not found")?;
+    if !is_snapshot_of(&snapshot, &dir) {
+        return Err("Snapshot does not belong to this file".into());
+    }
+    let bytes =
+        std::fs::read(&snapshot).map_err(|err| format!("Failed to read snapshot: {err}"))?;
+    write_atomic(&target, &bytes)
+}
+
+/// Whether the canonical `snapshot` sits directly inside the snapshot folder `dir`.
+fn is_snapshot_of(snapshot: &Path, dir: &Path) -> bool {
+    dir.canonicalize()
+        .is_ok_and(|dir| snapshot.parent() == Some(dir.as_path()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ntr-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn skips_unchanged_content_and_prunes_old_snapshots() {
+        let root = test_dir("snapshots");
+        let file = root.join("watched.ntr");
+        let dir = root.join("snapshots");
+        let mut last_hash = None;
+
+        std::fs::write(&file, "a\n").unwrap();
+        assert!(take_snapshot(&dir, &file, &mut last_hash, 2)
+            .unwrap()
+            .is_some());
+        assert_eq!(take_snapshot(&dir, &file, &mut last_hash, 2).unwrap(), None);
+
+        for contents in ["b\n", "c\n"] {
+            std::fs::write(&file, contents).unwrap();
+            assert!(take_snapshot(&dir, &file, &mut last_hash, 2)
+                .unwrap()
+                .is_some());
+        }
+        let kept: Vec<String> = snapshot_files(&dir)
+            .iter()
+            .map(|(_, path)| std::fs::read_to_string(path).unwrap())
+            .collect();
+        assert_eq!(kept, ["b\n", "c\n"]);
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn only_files_in_the_snapshot_folder_are_snapshots() {
+        let root = test_dir("snapshot-owner");
+        let dir = root.join("snapshots");
+        std::fs::create_dir_all(&dir).unwrap();
+        let inside = dir.join("1.ntr");
+        let outside = root.join("1.ntr");
+        std::fs::write(&inside, "").unwrap();
+        std::fs::write(&outside, "").unwrap();
+
+        assert!(is_snapshot_of(&inside.canonicalize().unwrap(), &dir));
+        assert!(!is_snapshot_of(&outside.canonicalize().unwrap(), &dir));
+        let escaped = dir.join("..").join("1.ntr").canonicalize().unwrap();
+        assert!(!is_snapshot_of(&escaped, &dir));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,1171 @@
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use regex::Regex;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+};
+use tauri::Emitter;
+
+use crate::{
+    bom_len_for, decode_with_encoding, encode_contents, ensure_byte_encoding, ensure_disk_space,
+    file_encoding, is_ntr_file, line_encoding,
+    lines::{with_ntr_bytes, MappedFileState},
+    normalize_path, parse_encoding_label, read_decoded, read_decoded_text, read_ntr_file,
+    read_options_for,
+    records::{header_names, is_formula_cell, read_data, split_fields},
+    resolve_absolute_path, resolve_input_path,
+    settings::SettingsState,
+    sidecar_path, write_atomic, DecodedLines, DecodedText,
+};
+
+/// Cap on the positions `find_control_chars` reports; the total is always counted.
+const MAX_REPORTED_CONTROL_CHARS: usize = 10_000;
+/// Data rows `export_ntr_to_markdown` renders when no `max_rows` is given.
+const DEFAULT_MARKDOWN_ROWS: usize = 100;
+
+fn open_reader(path: &Path) -> Result<BufReader<File>, String> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|err| format!("Failed to open file: {err}"))
+}
+
+/// Splits on raw `\n` bytes so chunk contents stay byte-identical to the source; that is only
+/// sound for ASCII-compatible encodings, so UTF-16 input is rejected up front, whether it is
+/// marked by a BOM, remembered for the file or picked by detection. Each chunk is held in memory
+/// and written with `write_atomic`, after checking once that the output volume can hold a copy
+/// of the source.
+#[tauri::command]
+pub(crate) fn split_ntr_file(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    lines_per_chunk: usize,
+    out_dir: String,
+    repeat_header: Option<bool>,
+) -> Result<Vec<String>, String> {
+    let resolved = resolve_input_path(&path);
+    let out_dir = resolve_input_path(&out_dir);
+    split_chunks(
+        &settings,
+        &resolved,
+        lines_per_chunk,
+        &out_dir,
+        repeat_header.unwrap_or(false),
+    )
+}
+
+fn split_chunks(
+    settings: &SettingsState,
+    path: &Path,
+    lines_per_chunk: usize,
+    out_dir: &Path,
+    repeat_header: bool,
+) -> Result<Vec<String>, String> {
+    if lines_per_chunk == 0 {
+        return Err("Lines per chunk must be greater than zero".into());
+    }
+    let encoding = line_encoding(path, &read_options_for(settings, path), "Splitting")?;
+    std::fs::create_dir_all(out_dir)
+        .map_err(|err| format!("Failed to create output directory: {err}"))?;
+    let source_len = std::fs::metadata(path)
+        .map_err(|err| format!("Failed to read file metadata: {err}"))?
+        .len();
+    ensure_disk_space(out_dir, source_len)?;
+
+    let mut reader = open_reader(path)?;
+    let mut line = Vec::new();
+    let read_line = |reader: &mut BufReader<File>, line: &mut Vec<u8>| {
+        line.clear();
+        reader
+            .read_until(b'\n', line)
+            .map_err(|err| format!("Failed to read file: {err}"))
+    };
+
+    if read_line(&mut reader, &mut line)? == 0 {
+        return Ok(Vec::new());
+    }
+    let bom: Vec<u8> = line.drain(..bom_len_for(encoding, &line)).collect();
+    let header = if repeat_header {
+        let header = line.clone();
+        read_line(&mut reader, &mut line)?;
+        Some(header)
+    } else {
+        None
+    };
+
+    let mut created = Vec::new();
+    let mut chunk = Vec::new();
+    let mut lines_in_chunk = 0;
+    let mut write_chunk = |chunk: &[u8]| {
+        let part_path = out_dir.join(format!("part-{:04}.ntr", created.len() + 1));
+        write_atomic(&part_path, chunk)?;
+        created.push(normalize_path(&part_path));
+        Ok::<_, String>(())
+    };
+    while !line.is_empty() {
+        if lines_in_chunk == 0 {
+            chunk.extend_from_slice(&bom);
+            chunk.extend_from_slice(header.as_deref().unwrap_or_default());
+        }
+        chunk.extend_from_slice(&line);
+        lines_in_chunk += 1;
+        if lines_in_chunk == lines_per_chunk {
+            write_chunk(&chunk)?;
+            chunk.clear();
+            lines_in_chunk = 0;
+        }
+        read_line(&mut reader, &mut line)?;
+    }
+    if lines_in_chunk > 0 {
+        write_chunk(&chunk)?;
+    }
+    Ok(created)
+}
+
+#[derive(Serialize)]
+pub(crate) struct SkippedFile {
+    path: String,
+    reason: String,
+}
+
+#[derive(Serialize)]
+pub(crate) struct MergeReport {
+    lines_written: usize,
+    skipped: Vec<SkippedFile>,
+}
+
+/// Concatenates decoded inputs with `\n` line endings. With `dedupe_headers`, a later file's
+/// first line is dropped when it matches the first file's header.
+#[tauri::command]
+pub(crate) fn merge_ntr_files(
+    settings: tauri::State<SettingsState>,
+    paths: Vec<String>,
+    out_path: String,
+    dedupe_headers: bool,
+    encoding: Option<String>,
+) -> Result<MergeReport, String> {
+    let target = match encoding.as_deref() {
+        Some(label) => parse_encoding_label(label)?,
+        None => UTF_8,
+    };
+
+    let mut merged = String::new();
+    let mut header: Option<String> = None;
+    let mut lines_written = 0;
+    let mut skipped = Vec::new();
+    for path in paths {
+        let input = &resolve_input_path(&path);
+        let contents = match read_ntr_file(input, read_options_for(&settings, input)) {
+            Ok(response) => response.contents,
+            Err(err) => {
+                skipped.push(SkippedFile {
+                    path,
+                    reason: err.message,
+                });
+                continue;
+            }
+        };
+
+        let mut lines = contents.lines().peekable();
+        if let Some(first) = lines.peek() {
+            match &header {
+                Some(existing) if dedupe_headers && existing.trim() == first.trim() => {
+                    lines.next();
+                }
+                Some(_) => {}
+                None => header = Some(first.to_string()),
+            }
+        }
+        for line in lines {
+            merged.push_str(line);
+            merged.push('\n');
+            lines_written += 1;
+        }
+    }
+
+    write_atomic(
+        &resolve_input_path(&out_path),
+        &encode_contents(&merged, target)?,
+    )?;
+    Ok(MergeReport {
+        lines_written,
+        skipped,
+    })
+}
+
+#[derive(Serialize)]
+pub(crate) struct FilterReport {
+    matched_rows: usize,
+    total_rows: usize,
+}
+
+/// Writes the header plus every row whose `column_index` field contains `pattern` (or matches
+/// it as a regular expression when `regex` is set) to `out_path` in the source encoding. The
+/// file is read a line at a time, so only the kept rows are held; UTF-16 files are rejected.
+#[tauri::command]
+pub(crate) fn filter_ntr_file(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    column_index: usize,
+    pattern: String,
+    regex: bool,
+    out_path: String,
+    delimiter: Option<String>,
+) -> Result<FilterReport, String> {
+    let matcher = if regex {
+        Some(Regex::new(&pattern).map_err(|err| format!("Invalid pattern: {err}"))?)
+    } else {
+        None
+    };
+    let is_match = |field: &str| match &matcher {
+        Some(matcher) => matcher.is_match(field),
+        None => field.contains(pattern.as_str()),
+    };
+
+    let resolved = resolve_input_path(&path);
+    let options = read_options_for(&settings, &resolved);
+    let mut lines = DecodedLines::open(&resolved, &options, "Filtering")?;
+    let mut kept = Vec::new();
+    let mut matched_rows = 0;
+    let mut total_rows = 0;
+    for line in lines.by_ref() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if kept.is_empty() {
+            kept.push(line);
+            continue;
+        }
+        total_rows += 1;
+        let fields = split_fields(&line, delimiter.as_deref());
+        if fields
+            .get(column_index)
+            .is_some_and(|field| is_match(field))
+        {
+            matched_rows += 1;
+            kept.push(line);
+        }
+    }
+
+    let line_ending = if lines.crlf { "\r\n" } else { "\n" };
+    let mut filtered = kept.join(line_ending);
+    if !kept.is_empty() {
+        filtered.push_str(line_ending);
+    }
+    let source = DecodedText {
+        contents: String::new(),
+        encoding: lines.encoding,
+        had_bom: lines.had_bom,
+    };
+    write_atomic(
+        &resolve_input_path(&out_path),
+        &source.encode_like_source(&filtered)?,
+    )?;
+    Ok(FilterReport {
+        matched_rows,
+        total_rows,
+    })
+}
+
+#[derive(Serialize)]
+pub(crate) struct LineRangeReport {
+    lines_written: usize,
+}
+
+/// The end of line 1 and the byte span of lines `start..=end` (1-based), found in one pass
+/// that stops at line `end`. A file with fewer lines yields its line count as the error.
+fn line_span(bytes: &[u8], start: usize, end: usize) -> Result<(usize, usize, usize), usize> {
+    let (mut lines, mut header_end, mut from, mut line_start) = (0, 0, 0, 0);
+    while line_start < bytes.len() {
+        let line_end = bytes[line_start..]
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .map_or(bytes.len(), |newline| line_start + newline + 1);
+        lines += 1;
+        if lines == 1 {
+            header_end = line_end;
+        }
+        if lines == start {
+            from = line_start;
+        }
+        if lines == end {
+            return Ok((header_end, from, line_end));
+        }
+        line_start = line_end;
+    }
+    Err(lines)
+}
+
+/// Writes lines `start_line..=end_line` (1-based, inclusive) of `path` to `out_path` unchanged,
+/// so the export keeps the source encoding, BOM and line endings. With `include_header`, line 1
+/// is written first when the range does not already start there. Like `split_ntr_file`, this
+/// splits on raw `\n` bytes and therefore rejects UTF-16 files.
+#[tauri::command]
+pub(crate) fn export_line_range(
+    settings: tauri::State<SettingsState>,
+    mapped: tauri::State<MappedFileState>,
+    path: String,
+    start_line: usize,
+    end_line: usize,
+    out_path: String,
+    include_header: Option<bool>,
+) -> Result<LineRangeReport, String> {
+    if start_line == 0 || start_line > end_line {
+        return Err(format!("Invalid line range {start_line}..{end_line}"));
+    }
+    let path = resolve_input_path(&path);
+    let options = read_options_for(&settings, &path);
+    with_ntr_bytes(&mapped, &path, |bytes| {
+        let encoding = file_encoding(bytes, &options);
+        ensure_byte_encoding(Some(encoding), "Exporting lines of")?;
+        let (header_end, from, to) = line_span(bytes, start_line, end_line).map_err(|total| {
+            format!("Line {end_line} is past the end of the file ({total} lines)")
+        })?;
+        let bom_len = bom_len_for(encoding, bytes);
+
+        let mut exported = bytes[..bom_len].to_vec();
+        let mut lines_written = end_line - start_line + 1;
+        if include_header.unwrap_or(false) && start_line > 1 {
+            exported.extend_from_slice(&bytes[bom_len..header_end]);
+            lines_written += 1;
+        }
+        let selected = &bytes[from.max(bom_len)..to];
+        decode_with_encoding(selected, encoding)?;
+        exported.extend_from_slice(selected);
+        write_atomic(&resolve_input_path(&out_path), &exported)?;
+        Ok(LineRangeReport { lines_written })
+    })
+}
+
+#[derive(Serialize)]
+pub(crate) struct CsvExportReport {
+    rows: usize,
+    /// Formula-like cells that were prefixed with `'`.
+    neutralized: usize,
+}
+
+/// The trimmed field with surrounding double quotes removed and doubled quotes inside unescaped.
+fn unquote_field(field: &str) -> String {
+    let trimmed = field.trim();
+    match trimmed
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+    {
+        Some(inner) => inner.replace("\"\"", "\""),
+        None => trimmed.to_string(),
+    }
+}
+
+/// Renders one field as a CSV cell: NTR quoting is removed, and the cell is quoted when it
+/// contains a comma, quote or line break. With `neutralize`, formula-like values get a leading
+/// `'` so spreadsheets show them as text; the flag in the result says whether that happened.
+fn csv_cell(field: &str, neutralize: bool) -> (String, bool) {
+    let mut value = unquote_field(field);
+    let neutralized = neutralize && is_formula_cell(&value);
+    if neutralized {
+        value.insert(0, '\'');
+    }
+    if value.contains([',', '"', '\r', '\n']) {
+        value = format!("\"{}\"", value.replace('"', "\"\""));
+    }
+    (value, neutralized)
+}
+
+/// Writes the non-blank rows of `path` as comma-separated UTF-8 with a BOM and CRLF line
+/// endings, the form spreadsheet applications open without an import dialog. The rows are read
+/// through `read_data`, so a leading metadata block is left out unless `include_metadata`.
+#[tauri::command]
+pub(crate) fn export_ntr_csv(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    out_path: String,
+    delimiter: Option<String>,
+    neutralize_formulas: Option<bool>,
+    include_metadata: Option<bool>,
+) -> Result<CsvExportReport, String> {
+    let neutralize = neutralize_formulas.unwrap_or(false);
+    let contents = read_data(&settings, &resolve_input_path(&path), include_metadata)?.contents;
+    let mut csv = String::from("\u{feff}");
+    let mut report = CsvExportReport {
+        rows: 0,
+        neutralized: 0,
+    };
+    for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+        let cells: Vec<String> = split_fields(line, delimiter.as_deref())
+            .into_iter()
+            .map(|field| {
+                let (cell, neutralized) = csv_cell(field, neutralize);
+                report.neutralized += usize::from(neutralized);
+                cell
+            })
+            .collect();
+        csv.push_str(&cells.join(","));
+        csv.push_str("\r\n");
+        report.rows += 1;
+    }
+    write_atomic(&resolve_input_path(&out_path), csv.as_bytes())?;
+    Ok(report)
+}
+
+#[derive(Serialize)]
+pub(crate) struct NdjsonExportReport {
+    records: usize,
+    /// 1-based line numbers of rows left out because their field count differs from the
+    /// header's.
+    malformed_lines: Vec<usize>,
+}
+
+/// Writes one JSON object per data row, keyed by the header (the first non-blank line) with
+/// the same names as `infer_ntr_schema`, made unique by `json_keys`. Keys keep the column order;
+/// values are strings with NTR quoting removed. The rows are read through `read_data`, so a
+/// leading metadata block is skipped unless `include_metadata`; line numbers still count it.
+#[tauri::command]
+pub(crate) fn export_ntr_to_ndjson(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    out_path: String,
+    delimiter: Option<String>,
+    include_metadata: Option<bool>,
+) -> Result<NdjsonExportReport, String> {
+    let data = read_data(&settings, &resolve_input_path(&path), include_metadata)?;
+    let mut lines = data
+        .contents
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + data.skipped_lines, line))
+        .filter(|(_, line)| !line.trim().is_empty());
+    let Some((_, header)) = lines.next() else {
+        return Err("File has no header row".into());
+    };
+    let header = split_fields(header, delimiter.as_deref());
+    let keys = json_keys(header_names(Some(&header), header.len()));
+
+    let mut ndjson = String::new();
+    let mut report = NdjsonExportReport {
+        records: 0,
+        malformed_lines: Vec::new(),
+    };
+    for (index, line) in lines {
+        let fields = split_fields(line, delimiter.as_deref());
+        if fields.len() != keys.len() {
+            report.malformed_lines.push(index + 1);
+            continue;
+        }
+        let row = JsonRow {
+            keys: &keys,
+            values: fields.iter().map(|field| unquote_field(field)).collect(),
+        };
+        let object = serde_json::to_string(&row)
+            .map_err(|err| format!("Failed to serialize line {}: {err}", index + 1))?;
+        ndjson.push_str(&object);
+        ndjson.push('\n');
+        report.records += 1;
+    }
+    write_atomic(&resolve_input_path(&out_path), ndjson.as_bytes())?;
+    Ok(report)
+}
+
+/// Header names as NDJSON keys: NTR quoting is removed and a repeated name gets the first free
+/// `_2`, `_3`, ... suffix, so no column overwrites another.
+fn json_keys(names: Vec<String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    names
+        .iter()
+        .map(|name| {
+            let name = unquote_field(name);
+            let mut key = name.clone();
+            let mut suffix = 2;
+            while !seen.insert(key.clone()) {
+                key = format!("{name}_{suffix}");
+                suffix += 1;
+            }
+            key
+        })
+        .collect()
+}
+
+/// One NDJSON object; serialized as a map so the keys keep the column order.
+struct JsonRow<'a> {
+    keys: &'a [String],
+    values: Vec<String>,
+}
+
+impl Serialize for JsonRow<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+        let mut map = serializer.serialize_map(Some(self.keys.len()))?;
+        for (key, value) in self.keys.iter().zip(&self.values) {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+/// Renders the first non-blank line as the header and up to `max_rows` further non-blank lines
+/// as a GitHub-flavored Markdown table. Short rows are padded with empty cells, pipes in cells
+/// are escaped, and a note under the table tells how many rows were left out.
+fn markdown_table(contents: &str, delimiter: Option<&str>, max_rows: usize) -> String {
+    let cell = |field: &str| unquote_field(field).replace('|', "\\|");
+    let mut rows = contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| split_fields(line, delimiter));
+    let Some(header) = rows.next() else {
+        return String::new();
+    };
+    let body: Vec<Vec<&str>> = rows.by_ref().take(max_rows).collect();
+    let omitted = rows.count();
+    let columns = body
+        .iter()
+        .map(Vec::len)
+        .chain([header.len()])
+        .max()
+        .unwrap_or(0);
+
+    let render = |fields: &[&str]| {
+        let cells: Vec<String> = (0..columns)
+            .map(|index| {
+                fields
+                    .get(index)
+                    .map_or_else(String::new, |field| cell(field))
+            })
+            .collect();
+        format!("| {} |\n", cells.join(" | "))
+    };
+    let mut table = render(&header);
+    table.push_str(&format!("|{}\n", " --- |".repeat(columns)));
+    for row in &body {
+        table.push_str(&render(row));
+    }
+    if omitted > 0 {
+        table.push_str(&format!(
+            "\n_Showing {} of {} rows; {omitted} omitted._\n",
+            body.len(),
+            body.len() + omitted
+        ));
+    }
+    table
+}
+
+/// Returns the file as a Markdown table for pasting into docs and issues; see
+/// `markdown_table`. `max_rows` defaults to `DEFAULT_MARKDOWN_ROWS`; the rows are read through
+/// `read_data`.
+#[tauri::command]
+pub(crate) fn export_ntr_to_markdown(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    delimiter: Option<String>,
+    max_rows: Option<usize>,
+    include_metadata: Option<bool>,
+) -> Result<String, String> {
+    let contents = read_data(&settings, &resolve_input_path(&path), include_metadata)?.contents;
+    let max_rows = max_rows.unwrap_or(DEFAULT_MARKDOWN_ROWS);
+    Ok(markdown_table(&contents, delimiter.as_deref(), max_rows))
+}
+
+#[derive(Serialize)]
+pub(crate) struct LineEndingReport {
+    changed: usize,
+}
+
+/// Rewrites every `\r\n`, `\r` and `\n` as `target` and returns how many endings differed from
+/// it. Endings are replaced in place, so a missing final newline stays missing.
+pub(crate) fn convert_line_endings(contents: &str, target: &str) -> (String, usize) {
+    let mut converted = String::with_capacity(contents.len());
+    let mut changed = 0;
+    let mut rest = contents;
+    while let Some(index) = rest.find(['\r', '\n']) {
+        converted.push_str(&rest[..index]);
+        let ending = if rest[index..].starts_with("\r\n") {
+            "\r\n"
+        } else {
+            &rest[index..index + 1]
+        };
+        if ending != target {
+            changed += 1;
+        }
+        converted.push_str(target);
+        rest = &rest[index + ending.len()..];
+    }
+    converted.push_str(rest);
+    (converted, changed)
+}
+
+/// Maps a line-ending name ("lf", "crlf" or "cr", any case) to the characters it stands for.
+pub(crate) fn line_ending_named(name: &str) -> Result<&'static str, String> {
+    match name.to_ascii_lowercase().as_str() {
+        "lf" => Ok("\n"),
+        "crlf" => Ok("\r\n"),
+        "cr" => Ok("\r"),
+        _ => Err(format!("Unknown line ending: {name}")),
+    }
+}
+
+/// Names the line endings used in `contents`: "lf", "crlf" or "cr" when all agree, "mixed"
+/// otherwise, and `None` for text without any line break.
+pub(crate) fn describe_line_endings(contents: &str) -> Option<&'static str> {
+    let crlf = contents.matches("\r\n").count();
+    let lf = contents.matches('\n').count() - crlf;
+    let cr = contents.matches('\r').count() - crlf;
+    match (crlf > 0, lf > 0, cr > 0) {
+        (false, false, false) => None,
+        (true, false, false) => Some("crlf"),
+        (false, true, false) => Some("lf"),
+        (false, false, true) => Some("cr"),
+        _ => Some("mixed"),
+    }
+}
+
+/// Converts all line endings of `path` to `target` ("lf", "crlf" or "cr") and writes the result
+/// to `out_path` in the source encoding.
+#[tauri::command]
+pub(crate) fn normalize_line_endings(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    target: String,
+    out_path: String,
+) -> Result<LineEndingReport, String> {
+    let target = line_ending_named(&target)?;
+    let decoded = read_decoded_text(&settings, &resolve_input_path(&path))?;
+    let (converted, changed) = convert_line_endings(&decoded.contents, target);
+    write_atomic(
+        &resolve_input_path(&out_path),
+        &decoded.encode_like_source(&converted)?,
+    )?;
+    Ok(LineEndingReport { changed })
+}
+
+/// Control characters other than the tab and line breaks NTR files may contain.
+fn is_stray_control(ch: char) -> bool {
+    ch.is_control() && !matches!(ch, '\t' | '\n' | '\r')
+}
+
+#[derive(Serialize)]
+pub(crate) struct ControlChar {
+    /// 1-based line and character column.
+    line: usize,
+    column: usize,
+    /// Code point in `U+XXXX` notation.
+    code_point: String,
+}
+
+#[derive(Serialize)]
+pub(crate) struct ControlCharReport {
+    total: usize,
+    /// The first `MAX_REPORTED_CONTROL_CHARS` occurrences.
+    found: Vec<ControlChar>,
+}
+
+/// Finds control characters (C0, DEL and C1) other than tab, line feed and carriage return,
+/// which render as invisible glitches.
+#[tauri::command]
+pub(crate) fn find_control_chars(
+    settings: tauri::State<SettingsState>,
+    path: String,
+) -> Result<ControlCharReport, String> {
+    let contents = read_decoded(&settings, &resolve_input_path(&path))?;
+    let mut report = ControlCharReport {
+        total: 0,
+        found: Vec::new(),
+    };
+    for (index, line) in contents.lines().enumerate() {
+        for (column, ch) in line.chars().enumerate() {
+            if !is_stray_control(ch) {
+                continue;
+            }
+            report.total += 1;
+            if report.found.len() < MAX_REPORTED_CONTROL_CHARS {
+                report.found.push(ControlChar {
+                    line: index + 1,
+                    column: column + 1,
+                    code_point: format!("U+{:04X}", u32::from(ch)),
+                });
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Writes `path` to `out_path` in the source encoding with every character reported by
+/// `find_control_chars` removed, and returns how many were removed.
+#[tauri::command]
+pub(crate) fn strip_control_chars(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    out_path: String,
+) -> Result<usize, String> {
+    let decoded = read_decoded_text(&settings, &resolve_input_path(&path))?;
+    let cleaned: String = decoded
+        .contents
+        .chars()
+        .filter(|&ch| !is_stray_control(ch))
+        .collect();
+    let removed = decoded.contents.chars().count() - cleaned.chars().count();
+    write_atomic(
+        &resolve_input_path(&out_path),
+        &decoded.encode_like_source(&cleaned)?,
+    )?;
+    Ok(removed)
+}
+
+#[derive(Serialize)]
+pub(crate) struct TrimReport {
+    fields_modified: usize,
+}
+
+/// Trims every field of `line`, and with `collapse` also shrinks inner whitespace runs to one
+/// space outside double-quoted fields. Whitespace-separated records are rejoined with single
+/// spaces. Returns the new line and how many fields changed.
+fn trim_line_fields(line: &str, delimiter: Option<&str>, collapse: bool) -> (String, usize) {
+    let Some(separator) = delimiter.filter(|delimiter| !delimiter.is_empty()) else {
+        return respace_fields(line);
+    };
+    let mut modified = 0;
+    let fields: Vec<String> = split_fields(line, delimiter)
+        .into_iter()
+        .map(|field| {
+            let trimmed = field.trim();
+            let quoted = trimmed.len() >= 2 && trimmed.starts_with('"') && trimmed.ends_with('"');
+            let cleaned = if collapse && !quoted {
+                trimmed.split_whitespace().collect::<Vec<_>>().join(" ")
+            } else {
+                trimmed.to_string()
+            };
+            modified += usize::from(cleaned != field);
+            cleaned
+        })
+        .collect();
+    (fields.join(separator), modified)
+}
+
+/// `trim_line_fields` for whitespace-separated records: the fields are rejoined with single
+/// spaces, and a field counts as changed when the whitespace around it does.
+fn respace_fields(line: &str) -> (String, usize) {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let mut modified = 0;
+    let mut end = 0;
+    for (index, field) in fields.iter().enumerate() {
+        let start = end
+            + line[end..]
+                .find(|ch: char| !ch.is_whitespace())
+                .unwrap_or(0);
+        let expected_gap = if index == 0 { "" } else { " " };
+        let padded_end = index + 1 == fields.len() && start + field.len() < line.len();
+        modified += usize::from(&line[end..start] != expected_gap || padded_end);
+        end = start + field.len();
+    }
+    (fields.join(" "), modified)
+}
+
+/// Writes `path` to `out_path` in the source encoding with leading and trailing whitespace
+/// removed from every field; see `trim_line_fields`. Line endings and blank lines are kept.
+#[tauri::command]
+pub(crate) fn trim_fields(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    delimiter: Option<String>,
+    out_path: String,
+    collapse_whitespace: Option<bool>,
+) -> Result<TrimReport, String> {
+    let collapse = collapse_whitespace.unwrap_or(false);
+    let decoded = read_decoded_text(&settings, &resolve_input_path(&path))?;
+    let mut cleaned = String::with_capacity(decoded.contents.len());
+    let mut report = TrimReport { fields_modified: 0 };
+    for line in decoded.contents.split_inclusive('\n') {
+        let body = line.trim_end_matches(['\r', '\n']);
+        let ending = &line[body.len()..];
+        if body.trim().is_empty() {
+            cleaned.push_str(line);
+            continue;
+        }
+        let (trimmed, modified) = trim_line_fields(body, delimiter.as_deref(), collapse);
+        report.fields_modified += modified;
+        cleaned.push_str(&trimmed);
+        cleaned.push_str(ending);
+    }
+    write_atomic(
+        &resolve_input_path(&out_path),
+        &decoded.encode_like_source(&cleaned)?,
+    )?;
+    Ok(report)
+}
+
+#[derive(Serialize)]
+pub(crate) struct TruncateReport {
+    lines_removed: usize,
+}
+
+/// Rewrites `path` in place keeping only its first (`keep` = "head") or last ("tail")
+/// `max_lines` lines, in the source encoding. A file without a final newline still has none
+/// afterwards.
+#[tauri::command]
+pub(crate) fn truncate_ntr_to_lines(
+    settings: tauri::State<SettingsState>,
+    path: String,
+    max_lines: usize,
+    keep: String,
+) -> Result<TruncateReport, String> {
+    let keep_head = match keep.to_ascii_lowercase().as_str() {
+        "head" => true,
+        "tail" => false,
+        _ => return Err(format!("Unknown truncation mode: {keep}")),
+    };
+    let path = &resolve_input_path(&path);
+    let decoded = read_decoded_text(&settings, path)?;
+    let lines: Vec<&str> = decoded.contents.split_inclusive('\n').collect();
+    if lines.len() <= max_lines {
+        return Ok(TruncateReport { lines_removed: 0 });
+    }
+
+    let kept = if keep_head {
+        &lines[..max_lines]
+    } else {
+        &lines[lines.len() - max_lines..]
+    };
+    let mut truncated = kept.concat();
+    if keep_head && !decoded.contents.ends_with('\n') {
+        truncated.pop();
+        if truncated.ends_with('\r') {
+            truncated.pop();
+        }
+    }
+    write_atomic(path, &decoded.encode_like_source(&truncated)?)?;
+    Ok(TruncateReport {
+        lines_removed: lines.len() - max_lines,
+    })
+}
+
+/// SHA-256 of the decoded text after dropping a leading U+FEFF and converting `\r\n` and `\r`
+/// to `\n`, as lowercase hex. Files that differ only in encoding, BOM or line endings therefore
+/// share a fingerprint; every other difference, including trailing whitespace and a missing
+/// final newline, still changes it.
+#[tauri::command]
+pub(crate) fn normalized_fingerprint(
+    settings: tauri::State<SettingsState>,
+    path: String,
+) -> Result<String, String> {
+    let decoded = read_decoded_text(&settings, &resolve_input_path(&path))?;
+    let text = decoded
+        .contents
+        .strip_prefix('\u{feff}')
+        .unwrap_or(&decoded.contents);
+    let (normalized, _) = convert_line_endings(text, "\n");
+    let digest = Sha256::digest(normalized.as_bytes());
+    Ok(digest.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+/// NTR files under `dir` in path order. With `recursive`, subdirectories are searched too;
+/// symlinked directories are not followed, so a link cycle cannot loop forever.
+fn collect_ntr_files(dir: &Path, recursive: bool, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries =
+        std::fs::read_dir(dir).map_err(|err| format!("Failed to read directory: {err}"))?;
+    let mut subdirs = Vec::new();
+    for entry in entries.filter_map(Result::ok) {
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let path = entry.path();
+        if file_type.is_dir() {
+            subdirs.push(path);
+        } else if is_ntr_file(&path) && path.is_file() {
+            files.push(path);
+        }
+    }
+    if recursive {
+        for subdir in subdirs {
+            if let Err(err) = collect_ntr_files(&subdir, true, files) {
+                log::warn!("Skipping {}: {err}", subdir.display());
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum ConversionStatus {
+    Converted,
+    Skipped,
+    Failed,
+}
+
+#[derive(Clone, Serialize)]
+struct ConversionProgressPayload {
+    path: String,
+    /// 1-based position of this file among `total`.
+    index: usize,
+    total: usize,
+    status: ConversionStatus,
+    reason: Option<String>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct BatchConversionReport {
+    converted: Vec<String>,
+    skipped: Vec<SkippedFile>,
+    failed: Vec<SkippedFile>,
+}
+
+/// Decodes `path` the way loading does (remembered encoding, then sidecar, then detection) and
+/// rewrites it atomically in `target`, without a BOM. An existing `<file>.encoding` sidecar is
+/// rewritten to declare `target`. Returns the reason when the file is left alone because it is
+/// already in `target`.
+fn convert_file_encoding(
+    settings: &SettingsState,
+    path: &Path,
+    target: &'static Encoding,
+) -> Result<Option<String>, String> {
+    let decoded = read_decoded_text(settings, path)?;
+    if decoded.encoding == target {
+        return Ok(Some(format!("Already {}", target.name())));
+    }
+    let contents = decoded
+        .contents
+        .strip_prefix('\u{feff}')
+        .unwrap_or(&decoded.contents);
+    write_atomic(path, &encode_contents(contents, target)?)?;
+    let sidecar = sidecar_path(path);
+    if sidecar.is_file() {
+        write_atomic(&sidecar, target.name().as_bytes())
+            .map_err(|err| format!("Converted, but failed to update the sidecar: {err}"))?;
+    }
+    Ok(None)
+}
+
+/// Converts `files` in order, reporting each outcome to `progress`. A file whose remembered
+/// encoding cannot be updated afterwards is reported as failed; the run carries on.
+fn convert_files(
+    settings: &SettingsState,
+    files: &[PathBuf],
+    target: &'static Encoding,
+    mut progress: impl FnMut(ConversionProgressPayload),
+) -> BatchConversionReport {
+    let total = files.len();
+    let mut report = BatchConversionReport {
+        converted: Vec::new(),
+        skipped: Vec::new(),
+        failed: Vec::new(),
+    };
+    for (index, file) in files.iter().enumerate() {
+        let path = normalize_path(file);
+        let converted = convert_file_encoding(settings, file, target).and_then(|skipped| {
+            if skipped.is_none() {
+                let key = normalize_path(&resolve_absolute_path(file));
+                settings
+                    .update(|settings| {
+                        if let Some(remembered) = settings.file_encodings.get_mut(&key) {
+                            *remembered = target.name().to_string();
+                        }
+                    })
+                    .map_err(|err| format!("Converted, but failed to remember it: {err}"))?;
+            }
+            Ok(skipped)
+        });
+        let (status, reason) = match converted {
+            Ok(None) => {
+                report.converted.push(path.clone());
+                (ConversionStatus::Converted, None)
+            }
+            Ok(Some(reason)) => {
+                report.skipped.push(SkippedFile {
+                    path: path.clone(),
+                    reason: reason.clone(),
+                });
+                (ConversionStatus::Skipped, Some(reason))
+            }
+            Err(reason) => {
+                log::warn!("Failed to convert {path}: {reason}");
+                report.failed.push(SkippedFile {
+                    path: path.clone(),
+                    reason: reason.clone(),
+                });
+                (ConversionStatus::Failed, Some(reason))
+            }
+        };
+        progress(ConversionProgressPayload {
+            path,
+            index: index + 1,
+            total,
+            status,
+            reason,
+        });
+    }
+    report
+}
+
+/// Rewrites every NTR file in `dir` in `target_encoding`, emitting `ntr-batch-convert-progress`
+/// after each file. Files already in the target are skipped and files that fail to decode or
+/// contain characters the target cannot represent are left untouched, so a partial run never
+/// loses data. Remembered per-file encodings of converted files are updated to the target.
+#[tauri::command(async)]
+pub(crate) fn batch_convert_encoding(
+    app: tauri::AppHandle,
+    settings: tauri::State<SettingsState>,
+    dir: String,
+    target_encoding: String,
+    recursive: Option<bool>,
+) -> Result<BatchConversionReport, String> {
+    let target = parse_encoding_label(&target_encoding)?;
+    if target == UTF_16LE || target == UTF_16BE {
+        return Err(format!("Converting to {} is not supported", target.name()));
+    }
+    let mut files = Vec::new();
+    collect_ntr_files(
+        &resolve_input_path(&dir),
+        recursive.unwrap_or(false),
+        &mut files,
+    )?;
+    files.sort();
+
+    let report = convert_files(&settings, &files, target, |payload| {
+        if let Err(err) = app.emit("ntr-batch-convert-progress", payload) {
+            log::error!("Failed to emit batch conversion progress event: {err}");
+        }
+    });
+    log::info!(
+        "Batch conversion to {}: {} converted, {} skipped, {} failed",
+        target.name(),
+        report.converted.len(),
+        report.skipped.len(),
+        report.failed.len()
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::Settings;
+    use encoding_rs::WINDOWS_1252;
+
+    #[test]
+    fn json_keys_are_unquoted_and_unique() {
+        let names = ["\"id\"", "name", "name", "name_2", "name"]
+            .map(String::from)
+            .to_vec();
+        assert_eq!(
+            json_keys(names),
+            ["id", "name", "name_2", "name_2_2", "name_3"]
+        );
+        let row = JsonRow {
+            keys: &["a".to_string(), "b \"c\"".to_string()],
+            values: vec!["1".into(), "x\ty".into()],
+        };
+        let json = serde_json::to_string(&row).expect("row serializes");
+        assert_eq!(json, r#"{"a":"1","b \"c\"":"x\ty"}"#);
+    }
+
+    #[test]
+    fn splits_into_atomically_written_chunks() {
+        let dir = std::env::temp_dir().join(format!("ntr-split-{}", std::process::id()));
+        let source = dir.join("source.ntr");
+        std::fs::create_dir_all(&dir).expect("create dir");
+        std::fs::write(&source, "H\nA\nB\nC").expect("write sample");
+        let settings = SettingsState::in_memory(Settings::default());
+        let parts = split_chunks(&settings, &source, 2, &dir.join("parts"), true).expect("split");
+        assert_eq!(parts.len(), 2);
+        assert_eq!(
+            std::fs::read_to_string(&parts[0]).expect("read"),
+            "H\nA\nB\n"
+        );
+        assert_eq!(std::fs::read_to_string(&parts[1]).expect("read"), "H\nC");
+        let leftovers = std::fs::read_dir(dir.join("parts")).expect("list").count();
+        assert_eq!(leftovers, 2);
+        std::fs::remove_dir_all(&dir).expect("remove dir");
+    }
+
+    #[test]
+    fn batch_conversion_skips_fails_and_remembers() {
+        let dir = std::env::temp_dir().join(format!("ntr-batch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("create dir");
+        let [already, remembered, declared, unmappable] =
+            ["a.ntr", "b.ntr", "c.ntr", "d.ntr"].map(|name| dir.join(name));
+        std::fs::write(&already, b"RO NAME=\xE6\n").expect("write sample");
+        std::fs::write(&remembered, "RO NAME=\u{E6}\n").expect("write sample");
+        std::fs::write(&declared, "RO NAME=\u{E6}\n").expect("write sample");
+        std::fs::write(sidecar_path(&declared), "utf-8").expect("write sidecar");
+        std::fs::write(&unmappable, "RO NAME=\u{6F22}\n").expect("write sample");
+
+        let key = normalize_path(&resolve_absolute_path(&remembered));
+        let mut settings = Settings::default();
+        settings.file_encodings.insert(key.clone(), "utf-8".into());
+        let settings = SettingsState::in_memory(settings);
+
+        let mut files = Vec::new();
+        collect_ntr_files(&dir, false, &mut files).expect("collect files");
+        files.sort();
+        let mut statuses = Vec::new();
+        let report = convert_files(&settings, &files, WINDOWS_1252, |payload| {
+            statuses.push(payload.status);
+        });
+        use ConversionStatus::{Converted, Failed, Skipped};
+        assert_eq!(statuses, [Skipped, Converted, Converted, Failed]);
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.converted.len(), 2);
+        assert_eq!(report.failed.len(), 1);
+
+        assert_eq!(std::fs::read(&remembered).expect("read"), b"RO NAME=\xE6\n");
+        assert_eq!(std::fs::read(&declared).expect("read"), b"RO NAME=\xE6\n");
+        let sidecar = std::fs::read_to_string(sidecar_path(&declared)).expect("read sidecar");
+        assert_eq!(sidecar, "windows-1252");
+        let label = settings.read(|settings| settings.file_encodings.get(&key).cloned());
+        assert_eq!(label.as_deref(), Some("windows-1252"));
+        assert_eq!(
+            std::fs::read(&unmappable).expect("read"),
+            "RO NAME=\u{6F22}\n".as_bytes()
+        );
+        std::fs::remove_dir_all(&dir).expect("remove dir");
+    }
+
+    #[test]
+    fn spans_inclusive_line_ranges_including_an_unterminated_last_line() {
+        let bytes = b"H\nA\r\nB\nC";
+        assert_eq!(line_span(bytes, 1, 1), Ok((2, 0, 2)));
+        assert_eq!(line_span(bytes, 2, 3), Ok((2, 2, 7)));
+        assert_eq!(line_span(bytes, 4, 4), Ok((2, 7, 8)));
+        assert_eq!(line_span(bytes, 5, 5), Err(4));
+        assert_eq!(line_span(bytes, 2, 5), Err(4));
+    }
+
+    #[test]
+    fn renders_markdown_table_with_escaped_pipes_and_truncation_note() {
+        let contents = "NAME;TEXT\nP1;\"a|b\"\n\nP2\nP3;c\n";
+        assert_eq!(
+            markdown_table(contents, Some(";"), 2),
+            "| NAME | TEXT |\n| --- | --- |\n| P1 | a\\|b |\n| P2 |  |\n\
+             \n_Showing 2 of 3 rows; 1 omitted._\n"
+        );
+    }
+
+    #[test]
+    fn trims_fields_and_optionally_collapses_inner_whitespace() {
+        let line = " P1 ;  a   b ;\" x  y \" ;ok";
+        assert_eq!(
+            trim_line_fields(line, Some(";"), false),
+            ("P1;a   b;\" x  y \";ok".to_string(), 3)
+        );
+        assert_eq!(
+            trim_line_fields(line, Some(";"), true),
+            ("P1;a b;\" x  y \";ok".to_string(), 3)
+        );
+        assert_eq!(
+            trim_line_fields("  RO P1=1\tP2=2 DN=10  ", None, false),
+            ("RO P1=1 P2=2 DN=10".to_string(), 3)
+        );
+        assert_eq!(
+            trim_line_fields("RO P1=1", None, true),
+            ("RO P1=1".to_string(), 0)
+        );
+    }
+
+    #[test]
+    fn converts_mixed_line_endings_and_keeps_missing_final_newline() {
+        let (converted, changed) = convert_line_endings("RO 1\r\nRO 2\rRO 3\nRO 4", "\r\n");
+        assert_eq!(converted, "RO 1\r\nRO 2\r\nRO 3\r\nRO 4");
+        assert_eq!(changed, 2);
+    }
+}
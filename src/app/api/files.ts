@@ -3,20 +3,61 @@ import { invoke } from "@tauri-apps/api/core";
 interface OpenFileResponse {
   readonly path: string;
   readonly contents: string;
+  readonly tabs_expanded: boolean;
+  readonly ends_with_newline: boolean;
+  readonly encoding: string;
+  readonly encoding_source: "detected" | "override" | "sidecar";
+  readonly had_bom: boolean;
+  readonly line_ending: "lf" | "crlf" | "cr" | "mixed" | null;
+  readonly ascii_only: boolean;
+}
+
+/** How the file was laid out on disk; saving with it keeps the bytes round-tripping. */
+export interface FileLayout {
+  readonly encoding: string;
+  readonly hadBom: boolean;
+  readonly lineEnding: OpenFileResponse["line_ending"];
 }
 
 export type OpenNtrFileResult =
   | { readonly status: "cancelled" }
-  | { readonly status: "success"; readonly path: string; readonly contents: string }
+  | {
+      readonly status: "success";
+      readonly path: string;
+      readonly contents: string;
+      readonly layout: FileLayout;
+    }
+  | { readonly status: "error"; readonly message: string };
+
+export type SaveNtrFileResult =
+  | { readonly status: "cancelled" }
+  | { readonly status: "success"; readonly path: string }
   | { readonly status: "error"; readonly message: string };
 
+const layoutOf = (response: OpenFileResponse): FileLayout => ({
+  encoding: response.encoding,
+  hadBom: response.had_bom,
+  lineEnding: response.line_ending,
+});
+
+const saveArgs = (layout: FileLayout) => ({
+  encoding: layout.encoding,
+  hadBom: layout.hadBom,
+  lineEnding: layout.lineEnding,
+});
+
 export const openNtrFile = async (): Promise<OpenNtrFileResult> => {
   try {
     const response = await invoke<OpenFileResponse | null>("open_ntr_file");
     if (response === null) {
       return { status: "cancelled" };
     }
-    return { status: "success", path: response.path, contents: response.contents };
+    return {
+      status: "success",
+      path: response.path,
+      contents: response.contents,
+      layout: layoutOf(response),
+    };
   } catch (error) {
     return { status: "error", message: formatError(error) };
   }
@@ -25,7 +66,49 @@ export const openNtrFile = async (): Promise<OpenNtrFileResult> => {
 export const loadNtrFileAtPath = async (path: string): Promise<OpenNtrFileResult> => {
   try {
     const response = await invoke<OpenFileResponse>("load_ntr_file", { path });
-    return { status: "success", path: response.path, contents: response.contents };
+    return {
+      status: "success",
+      path: response.path,
+      contents: response.contents,
+      layout: layoutOf(response),
+    };
+  } catch (error) {
+    return { status: "error", message: formatError(error) };
+  }
+};
+
+export const saveNtrFile = async (
+  path: string,
+  contents: string,
+  layout: FileLayout,
+): Promise<SaveNtrFileResult> => {
+  try {
+    const saved = await invoke<string>("save_ntr_file", {
+      path,
+      contents,
+      ...saveArgs(layout),
+    });
+    return { status: "success", path: saved };
+  } catch (error) {
+    return { status: "error", message: formatError(error) };
+  }
+};
+
+export const saveNtrFileAs = async (
+  contents: string,
+  layout: FileLayout,
+  defaultName?: string,
+): Promise<SaveNtrFileResult> => {
+  try {
+    const saved = await invoke<string | null>("save_ntr_file_as", {
+      contents,
+      defaultName,
+      ...saveArgs(layout),
+    });
+    if (saved === null) {
+      return { status: "cancelled" };
+    }
+    return { status: "success", path: saved };
   } catch (error) {
     return { status: "error", message: formatError(error) };
   }
@@ -54,5 +137,11 @@ const formatError = (error: unknown): string => {
   if (typeof error === "string") {
     return error;
   }
+  if (typeof error === "object" && error !== null && "message" in error) {
+    const { message } = error as { readonly message: unknown };
+    if (typeof message === "string") {
+      return message;
+    }
+  }
   return "Unknown error";
 };
@@ -54,6 +54,9 @@ const LAST_FILE_STORAGE_KEY = "ntr-viewer:last-file-path";
 interface FileChangePayload {
   readonly path: string;
   readonly kind: string;
+  readonly contents?: string;
+  readonly encoding?: string;
+  readonly tag?: string;
 }
 
 type LoadSource = "manual" | "restore" | "watch";
@@ -430,6 +433,11 @@ const handleFileChangeEvent = async (payload: FileChangePayload) => {
     return;
   }
 
+  if (payload.contents !== undefined) {
+    loadFileFromContents(payload.path, payload.contents, "watch");
+    return;
+  }
+
   const result = await loadNtrFileAtPath(payload.path);
   if (result.status === "success") {
     loadFileFromContents(result.path, result.contents, "watch");